@@ -1,7 +1,7 @@
-use ai_lib::{prompt, AiFunctionResult, AiFunctionResponse, AiInitialState, drive, recoverable_err, done};
+use ai_lib::{prompt, add_diagnostics_observer, StderrDiagnostics, AiFunctionResult, AiFunctionResponse, AiInitialState, EditHistory, DEFAULT_ECHO_LIMIT, drive_to_json, elide, recoverable_err, done};
 use ai_macros::ai_functions;
 use schemars::JsonSchema;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use ansi_term::Color;
 
 macro_rules! orange {
@@ -25,6 +25,7 @@ async fn main() {
 }
 */
 
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 struct SimpleExample {
     topic: String,
@@ -37,6 +38,7 @@ impl AiInitialState for SimpleExample {
     }
 }
 
+#[allow(dead_code)]
 #[ai_functions]
 impl SimpleExample {
 
@@ -74,14 +76,25 @@ impl SimpleExample {
 
 #[tokio::main]
 async fn main() {
+    // Show retries and other workarounds while the story is generated
+    add_diagnostics_observer(std::sync::Arc::new(StderrDiagnostics));
     let mut story = Story::new("an alternate history in which the Maya defeat the Spanish with advanced but historically plausible technology, e.g. catapults, ships, fortresses, etc.");
-    drive(&mut story).await.unwrap();
+    let output = drive_to_json(&mut story).await.unwrap();
+    println!("{output:#}");
+}
+
+#[derive(Debug, Serialize)]
+struct StoryOutput {
+    topic: String,
+    premise: String,
+    chapter_summaries: Vec<String>,
 }
 
 #[derive(Debug, Default)]
 struct Story {
     topic: String,
     premise: String,
+    premise_history: EditHistory,
     premise_edits_remaining: u32,
     chapter_summaries: Vec<String>,
 }
@@ -102,6 +115,15 @@ impl AiInitialState for Story {
 #[ai_functions]
 impl Story {
 
+    #[ai_output]
+    fn output(&self) -> StoryOutput {
+        StoryOutput {
+            topic: self.topic.clone(),
+            premise: self.premise.clone(),
+            chapter_summaries: self.chapter_summaries.clone(),
+        }
+    }
+
     #[ai_function(fn_description="Write a story premise", notes="Scratch notes where you ideate")]
     fn write_premise(&mut self, notes: Vec<String>, premise: String) -> AiFunctionResult {
         // Print out chain of thoughts then the premise
@@ -112,6 +134,7 @@ impl Story {
 
         // Update state and then prompt to edit with medium temperature
         self.premise = premise.clone();
+        self.premise_history.push(premise.clone());
         let topic = &self.topic;
         prompt!(0.5, "Liberally edit this story premise Be detailed. Topic: {topic}\nPremise:{premise}" => [edit_premise])
     }
@@ -125,15 +148,17 @@ impl Story {
         orange!("{}\n", rewritten_premise);
 
         // Update state and then prompt to edit with medium temperature, or move on to chapter outlines
-        // after a few rounds of editing
+        // after a few rounds of editing (or once edits stop changing much)
         self.premise = rewritten_premise.clone();
         self.premise_edits_remaining -= 1;
+        self.premise_history.push(rewritten_premise.clone());
+        let previous_changes = self.premise_history.change_summary(5).unwrap_or_default();
 
         let topic = &self.topic;
-        if self.premise_edits_remaining == 0 {
+        if self.premise_edits_remaining == 0 || self.premise_history.converged(0.95) {
             prompt!(0.5, "Write a detailed plot outline for each chapter of a story loosely based on this premise. Topic: {topic}\nPremise: {rewritten_premise}" => [write_chapter_outlines])
         } else {
-            prompt!(0.5, "Liberally edit the following story premise. Be detailed. Topic: {topic}\nPremise: {rewritten_premise}" => [edit_premise])
+            prompt!(0.5, "Liberally edit the following story premise. Be detailed. {previous_changes}\nTopic: {topic}\nPremise: {rewritten_premise}" => [edit_premise])
         }
     }
 
@@ -144,7 +169,7 @@ impl Story {
         for outline in &outlines {
             if outline.len() < 80 {
                 // Sometimes GPT gives only chapter titles, tell it to do better
-                return recoverable_err(format!("Chapter outlines should be a few sentences at least, but this one was only {} characters long: {}. Write longer outlines for each chapter.", outline.len(), outline));
+                return recoverable_err(format!("Chapter outlines should be a few sentences at least, but this one was only {} characters long: {}. Write longer outlines for each chapter.", outline.len(), elide(outline, DEFAULT_ECHO_LIMIT)));
             }
             println!("{outline}\n");
         }
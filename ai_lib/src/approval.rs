@@ -0,0 +1,130 @@
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::AiFunctionError;
+
+/// A function call waiting to be approved.
+#[derive(Debug, Clone, Copy)]
+pub struct ApprovalRequest<'a> {
+    pub function: &'a str,
+    /// The call's arguments, or `Value::Null` if they aren't valid JSON
+    pub arguments: &'a Value,
+    /// What the run has cost so far, in USD, priced with the global pricing table
+    pub session_cost: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Approval {
+    Approve,
+    /// The call isn't made; the reason is fed back to the model so it can try something else
+    Deny(String),
+}
+
+/// Asks a human whether a call may run, e.g. with a terminal prompt or a UI dialog.
+#[async_trait]
+pub trait Approver: Send + Sync {
+    async fn approve(&self, request: &ApprovalRequest<'_>) -> Approval;
+}
+
+type ArgumentPredicate = Arc<dyn Fn(&str, &Value) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+enum Rule {
+    Function(String),
+    Arguments(ArgumentPredicate),
+}
+
+/// Which calls the driver runs without asking and which go to an `Approver`. A call is
+/// auto-approved if any rule matches it and the run's spend is still under the threshold; every
+/// other call interrupts the human.
+#[derive(Clone)]
+pub struct ApprovalPolicy {
+    approver: Arc<dyn Approver>,
+    rules: Vec<Rule>,
+    max_auto_approved_cost: Option<f64>,
+}
+
+impl fmt::Debug for ApprovalPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApprovalPolicy")
+            .field("rules", &self.rules.len())
+            .field("max_auto_approved_cost", &self.max_auto_approved_cost)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ApprovalPolicy {
+    /// A policy that asks `approver` about every call until rules are added.
+    pub fn new(approver: impl Approver + 'static) -> Self {
+        Self { approver: Arc::new(approver), rules: vec![], max_auto_approved_cost: None }
+    }
+
+    /// Auto-approve functions whose name matches `pattern`, where `*` matches any run of
+    /// characters, e.g. `"get_*"`.
+    pub fn allow(mut self, pattern: impl Into<String>) -> Self {
+        self.rules.push(Rule::Function(pattern.into()));
+        self
+    }
+
+    /// Auto-approve calls for which `predicate` returns true, given the function name and its
+    /// parsed arguments.
+    pub fn allow_if(mut self, predicate: impl Fn(&str, &Value) -> bool + Send + Sync + 'static) -> Self {
+        self.rules.push(Rule::Arguments(Arc::new(predicate)));
+        self
+    }
+
+    /// Stop auto-approving once the run has cost more than `limit` USD, so the human signs off on
+    /// everything after that.
+    pub fn max_auto_approved_cost(mut self, limit: f64) -> Self {
+        self.max_auto_approved_cost = Some(limit);
+        self
+    }
+
+    pub fn is_auto_approved(&self, request: &ApprovalRequest<'_>) -> bool {
+        if self.max_auto_approved_cost.is_some_and(|limit| request.session_cost > limit) {
+            return false;
+        }
+        self.rules.iter().any(|rule| match rule {
+            Rule::Function(pattern) => glob_match(pattern, request.function),
+            Rule::Arguments(predicate) => predicate(request.function, request.arguments),
+        })
+    }
+
+    /// Approve the call by rule or by asking, turning a denial into a recoverable error.
+    pub(crate) async fn check(&self, function: &str, arguments: &str, session_cost: f64) -> Result<(), AiFunctionError> {
+        let arguments = serde_json::from_str(arguments).unwrap_or(Value::Null);
+        let request = ApprovalRequest { function, arguments: &arguments, session_cost };
+        if self.is_auto_approved(&request) {
+            return Ok(());
+        }
+        match self.approver.approve(&request).await {
+            Approval::Approve => Ok(()),
+            Approval::Deny(reason) => {
+                Err(AiFunctionError::Recoverable(format!("The call to {function} was not approved: {reason}")))
+            }
+        }
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<_> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
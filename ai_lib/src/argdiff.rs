@@ -0,0 +1,180 @@
+use std::fmt;
+
+use convert_case::{Case, Casing};
+use serde_json::Value;
+
+use crate::{closest_match, elide, DEFAULT_ECHO_LIMIT};
+
+/// How similar an unexpected field's name must be to a missing one to be suggested as a typo.
+const TYPO_THRESHOLD: f64 = 0.6;
+
+/// Where arguments that failed to deserialize differ from the function's schema.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct ArgumentDiff {
+    /// Paths of required fields that weren't given
+    missing: Vec<String>,
+    /// Paths of fields the schema doesn't declare
+    unexpected: Vec<String>,
+    mismatched: Vec<Mismatch>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Mismatch {
+    path: String,
+    expected: String,
+    got: Value,
+}
+
+impl ArgumentDiff {
+    /// Compare `arguments` to `schema`. Top-level keys the schema doesn't declare are looked up in
+    /// camel case too, as `#[ai_function]` accepts arguments in any case but declares them in camel case.
+    pub fn new(arguments: &Value, schema: &Value) -> Self {
+        let definitions = schema.get("$defs").or_else(|| schema.get("definitions")).unwrap_or(&Value::Null);
+        let mut diff = Self::default();
+        match arguments {
+            Value::Object(object) => {
+                let normalized: Vec<_> = object
+                    .iter()
+                    .map(|(key, value)| match schema["properties"].get(key) {
+                        Some(_) => (key.clone(), value),
+                        None => (key.to_case(Case::Camel), value),
+                    })
+                    .collect();
+                diff.object(&normalized, schema, definitions, "", 0);
+            }
+            _ => diff.value(arguments, schema, definitions, "", 0),
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty() && self.mismatched.is_empty()
+    }
+
+    fn object(&mut self, object: &[(String, &Value)], schema: &Value, definitions: &Value, path: &str, depth: usize) {
+        let properties = schema["properties"].as_object();
+        for (key, value) in object {
+            let field_path = join(path, key);
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(property) => self.value(value, property, definitions, &field_path, depth + 1),
+                None if properties.is_some() => self.unexpected.push(field_path),
+                None => {}
+            }
+        }
+        for required in schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+            if !object.iter().any(|(key, _)| key == required) {
+                self.missing.push(join(path, required));
+            }
+        }
+    }
+
+    fn value(&mut self, value: &Value, schema: &Value, definitions: &Value, path: &str, depth: usize) {
+        if depth > 16 {
+            return;
+        }
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.rsplit('/').next().unwrap_or_default();
+            if let Some(definition) = definitions.get(name) {
+                self.value(value, definition, definitions, path, depth + 1);
+            }
+            return;
+        }
+        // Option<T> is anyOf [T, null]; other unions are left to serde
+        if let Some(variants) = schema["anyOf"].as_array() {
+            let non_null: Vec<_> = variants.iter().filter(|variant| variant["type"] != "null").collect();
+            if let ([variant], false) = (non_null.as_slice(), value.is_null()) {
+                self.value(value, variant, definitions, path, depth + 1);
+            }
+            return;
+        }
+        if let Some(variants) = schema["enum"].as_array() {
+            if !variants.contains(value) {
+                let expected = variants.iter().map(Value::to_string).collect::<Vec<_>>().join(", ");
+                self.mismatch(path, format!("one of {expected}"), value);
+            }
+            return;
+        }
+        if let Some(expected) = schema.get("const").filter(|expected| *expected != value) {
+            self.mismatch(path, expected.to_string(), value);
+            return;
+        }
+
+        let types: Vec<&str> = match &schema["type"] {
+            Value::String(ty) => vec![ty.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => return,
+        };
+        if !types.iter().any(|ty| has_type(value, ty)) {
+            self.mismatch(path, types.join(" or "), value);
+            return;
+        }
+        match value {
+            Value::Object(object) => {
+                let object: Vec<_> = object.iter().map(|(key, value)| (key.clone(), value)).collect();
+                self.object(&object, schema, definitions, path, depth);
+            }
+            Value::Array(items) => {
+                if let Some(item) = schema.get("items").filter(|item| item.is_object()) {
+                    for (i, value) in items.iter().enumerate() {
+                        self.value(value, item, definitions, &format!("{path}[{i}]"), depth + 1);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn mismatch(&mut self, path: &str, expected: String, got: &Value) {
+        self.mismatched.push(Mismatch { path: path.to_string(), expected, got: got.clone() });
+    }
+}
+
+impl fmt::Display for ArgumentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The arguments don't match the function's parameters:")?;
+        for path in &self.missing {
+            write!(f, "\n- missing required field `{path}`")?;
+        }
+        for path in &self.unexpected {
+            write!(f, "\n- unexpected field `{path}`")?;
+            if let Some(suggestion) = closest_match(path, self.missing.iter().map(String::as_str), TYPO_THRESHOLD) {
+                write!(f, " (did you mean `{suggestion}`?)")?;
+            }
+        }
+        for Mismatch { path, expected, got } in &self.mismatched {
+            write!(f, "\n- `{path}`: expected {expected}, got {} {}", type_name(got), elide(&got.to_string(), DEFAULT_ECHO_LIMIT))?;
+        }
+        Ok(())
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    match path {
+        "" => key.to_string(),
+        _ => format!("{path}.{key}"),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
@@ -0,0 +1,213 @@
+use std::time::Duration;
+
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::json;
+
+use crate::driver::resolve_aliases;
+use crate::{AiFunctionError, AiFunctionResponse, CalledFunction, ChatError, DriveError, DynAiState, Function, Model, OpenAIClient};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Builder)]
+#[builder(setter(into))]
+pub struct CreateAssistant {
+    pub model: Model,
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// Offered as the assistant's tools, e.g. schemas from `DynAiState::function_schema`
+    #[builder(default)]
+    #[serde(rename = "tools", serialize_with = "serialize_tools")]
+    pub functions: Vec<Function>,
+}
+
+fn serialize_tools<S: Serializer>(functions: &[Function], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(functions.iter().map(|function| json!({ "type": "function", "function": function })))
+}
+
+/// An assistant hosted by the provider.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Assistant {
+    pub id: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub instructions: Option<String>,
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+/// A conversation with an assistant, its history kept by the provider.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Thread {
+    pub id: String,
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ThreadMessage {
+    pub id: String,
+    /// `user` or `assistant`
+    pub role: String,
+    /// Content parts, e.g. `{"type": "text", "text": {"value": ...}}`
+    #[serde(default)]
+    pub content: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+impl ThreadMessage {
+    /// The text parts of the message, joined.
+    pub fn text(&self) -> String {
+        self.content.iter().filter_map(|part| part["text"]["value"].as_str()).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// One execution of an assistant on a thread.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Run {
+    pub id: String,
+    pub thread_id: String,
+    pub assistant_id: String,
+    /// `queued`, `in_progress`, `requires_action`, `cancelling`, `cancelled`, `failed`, `completed`,
+    /// `incomplete`, or `expired`
+    pub status: String,
+    /// The function calls awaiting outputs, when `status` is `requires_action`
+    #[serde(default)]
+    pub required_action: Option<RequiredAction>,
+    #[serde(default)]
+    pub last_error: Option<serde_json::Value>,
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+impl Run {
+    /// Whether the run has stopped, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status.as_str(), "completed" | "failed" | "cancelled" | "incomplete" | "expired")
+    }
+
+    /// The function calls awaiting outputs, if any.
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        self.required_action.as_ref().map(|action| action.submit_tool_outputs.tool_calls.as_slice()).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RequiredAction {
+    pub submit_tool_outputs: SubmitToolOutputs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SubmitToolOutputs {
+    pub tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub function: CalledFunction,
+}
+
+impl OpenAIClient {
+    /// A request to the assistants API, which is opted into with a beta header.
+    fn assistants_request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.request(method, path).header("OpenAI-Beta", "assistants=v2")
+    }
+
+    pub async fn create_assistant(&self, assistant: &CreateAssistant) -> Result<Assistant, ChatError> {
+        self.send_json(self.assistants_request(reqwest::Method::POST, "assistants").json(assistant)).await
+    }
+
+    pub async fn assistant(&self, id: &str) -> Result<Assistant, ChatError> {
+        self.send_json(self.assistants_request(reqwest::Method::GET, &format!("assistants/{id}"))).await
+    }
+
+    pub async fn delete_assistant(&self, id: &str) -> Result<(), ChatError> {
+        let _: serde_json::Value = self.send_json(self.assistants_request(reqwest::Method::DELETE, &format!("assistants/{id}"))).await?;
+        Ok(())
+    }
+
+    pub async fn create_thread(&self) -> Result<Thread, ChatError> {
+        self.send_json(self.assistants_request(reqwest::Method::POST, "threads").json(&json!({}))).await
+    }
+
+    /// Add a user message to a thread.
+    pub async fn add_message(&self, thread_id: &str, content: &str) -> Result<ThreadMessage, ChatError> {
+        let body = json!({ "role": "user", "content": content });
+        self.send_json(self.assistants_request(reqwest::Method::POST, &format!("threads/{thread_id}/messages")).json(&body)).await
+    }
+
+    /// The messages of a thread, newest first.
+    pub async fn list_messages(&self, thread_id: &str) -> Result<Vec<ThreadMessage>, ChatError> {
+        let list: crate::finetuning::List<ThreadMessage> =
+            self.send_json(self.assistants_request(reqwest::Method::GET, &format!("threads/{thread_id}/messages"))).await?;
+        Ok(list.data)
+    }
+
+    /// Start running an assistant on a thread.
+    pub async fn create_run(&self, thread_id: &str, assistant_id: &str) -> Result<Run, ChatError> {
+        let body = json!({ "assistant_id": assistant_id });
+        self.send_json(self.assistants_request(reqwest::Method::POST, &format!("threads/{thread_id}/runs")).json(&body)).await
+    }
+
+    pub async fn run(&self, thread_id: &str, run_id: &str) -> Result<Run, ChatError> {
+        self.send_json(self.assistants_request(reqwest::Method::GET, &format!("threads/{thread_id}/runs/{run_id}"))).await
+    }
+
+    pub async fn cancel_run(&self, thread_id: &str, run_id: &str) -> Result<Run, ChatError> {
+        self.send_json(self.assistants_request(reqwest::Method::POST, &format!("threads/{thread_id}/runs/{run_id}/cancel"))).await
+    }
+
+    /// Answer a run's pending function calls with `(tool call ID, output)` pairs, resuming it.
+    pub async fn submit_tool_outputs(&self, thread_id: &str, run_id: &str, outputs: &[(String, String)]) -> Result<Run, ChatError> {
+        let tool_outputs: Vec<_> = outputs.iter().map(|(id, output)| json!({ "tool_call_id": id, "output": output })).collect();
+        let path = format!("threads/{thread_id}/runs/{run_id}/submit_tool_outputs");
+        self.send_json(self.assistants_request(reqwest::Method::POST, &path).json(&json!({ "tool_outputs": tool_outputs }))).await
+    }
+
+    /// Run an assistant on a thread, polling every `interval`, and answer each function call it makes
+    /// by calling the function on `state`. A call's output is the prompt the function returns, `Done`
+    /// if it finishes the state, or the error message if it fails recoverably. Returns the run in its
+    /// final state; an unrecoverable error cancels the run.
+    pub async fn run_assistant<S: DynAiState + ?Sized>(
+        &self,
+        state: &mut S,
+        thread_id: &str,
+        assistant_id: &str,
+        interval: Duration,
+    ) -> Result<Run, DriveError> {
+        let mut run = self.create_run(thread_id, assistant_id).await?;
+        loop {
+            if run.is_finished() {
+                return Ok(run);
+            }
+            if run.status != "requires_action" {
+                self.clock().sleep(interval).await;
+                run = self.run(thread_id, &run.id).await?;
+                continue;
+            }
+
+            let mut outputs = vec![];
+            for call in run.tool_calls() {
+                let arguments = resolve_aliases(state.function_aliases(&call.function.name), call.function.arguments.clone());
+                let output = match state.call(&call.function.name, &arguments) {
+                    Ok(AiFunctionResponse::Done) => "Done".to_string(),
+                    Ok(AiFunctionResponse::Prompt { prompt, .. }) => prompt,
+                    Err(AiFunctionError::Recoverable(e)) => e,
+                    Err(AiFunctionError::Unrecoverable(e)) => {
+                        self.cancel_run(thread_id, &run.id).await?;
+                        return Err(DriveError::Unrecoverable(e));
+                    }
+                };
+                outputs.push((call.id.clone(), output));
+            }
+            run = self.submit_tool_outputs(thread_id, &run.id, &outputs).await?;
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::error_codes::*;
+use crate::{ArgumentsDelta, BudgetLimit, OnArgumentsDelta, ChatCompletionRequest, ChatCompletionResponse, Model, OpenAIClient};
+
+/// Anything that can answer a chat completion request: OpenAI, another provider, or a test double.
+#[async_trait]
+pub trait ChatBackend: Send + Sync {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError>;
+
+    /// Like `chat`, passing fragments of function call arguments to `on_delta` as they're generated.
+    /// Backends that can't stream send the whole arguments as one fragment once the response arrives.
+    async fn chat_streaming(
+        &self,
+        req: &ChatCompletionRequest,
+        on_delta: &mut OnArgumentsDelta<'_>,
+    ) -> Result<ChatCompletionResponse, ChatError> {
+        let response = self.chat(req).await?;
+        if let Some(function_call) = response.choices.first().and_then(|choice| choice.message.function_call.as_ref()) {
+            let arguments = &function_call.arguments;
+            on_delta(&ArgumentsDelta { function: &function_call.name, fragment: arguments, arguments });
+        }
+        Ok(response)
+    }
+
+    /// The model the driver requests when a prompt doesn't ask for a specific one.
+    fn default_model(&self) -> Model {
+        Model::Gpt3p5Turbo
+    }
+}
+
+#[derive(Debug)]
+pub enum ChatError {
+    Http(reqwest::Error),
+    /// Still rate limited after backing off as long as allowed
+    RateLimited,
+    /// The provider answered with an unsuccessful status and a structured error
+    Api { status: u16, error: ApiError },
+    /// The provider answered with an unsuccessful status and a body that isn't a structured error
+    Status { status: u16, body: String },
+    /// The request, or the run it belongs to, ran out of time
+    Timeout,
+    /// A circuit breaker is open after repeated failures of this provider; no request was sent. This
+    /// replaces the client's former `CircuitOpen` error; see `is_circuit_open`.
+    ProviderUnavailable { provider: String, retry_in: std::time::Duration },
+    /// The client's `Budget` is spent; no request was sent
+    BudgetExceeded(BudgetLimit),
+    Other(String),
+}
+
+/// The `error` object of an OpenAI-style error response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub message: String,
+    /// e.g. `invalid_request_error`
+    #[serde(rename = "type", default)]
+    pub error_type: Option<String>,
+    /// e.g. `context_length_exceeded` or `invalid_api_key`
+    #[serde(default, deserialize_with = "deserialize_code")]
+    pub code: Option<String>,
+    /// The request parameter the error is about, if any
+    #[serde(default)]
+    pub param: Option<String>,
+}
+
+/// Some providers send numeric codes
+fn deserialize_code<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(serde_json::Value::String(code)) => Some(code),
+        Some(serde_json::Value::Null) | None => None,
+        Some(code) => Some(code.to_string()),
+    })
+}
+
+impl ApiError {
+    /// Parse a response body of the form `{"error": {"message": ..., "type": ..., "code": ..., "param": ...}}`.
+    pub fn from_body(body: &str) -> Option<Self> {
+        #[derive(Deserialize)]
+        struct Envelope {
+            error: ApiError,
+        }
+        serde_json::from_str::<Envelope>(body).ok().map(|envelope| envelope.error)
+    }
+
+    /// The stable code for this error (see `error_codes`), from the provider's `code` and `type`;
+    /// `E_API` if neither is recognized.
+    pub fn error_code(&self) -> &'static str {
+        let code = self.code.as_deref().unwrap_or_default();
+        let error_type = self.error_type.as_deref().unwrap_or_default();
+        match (code, error_type) {
+            ("context_length_exceeded", _) => E_CONTEXT_OVERFLOW,
+            ("rate_limit_exceeded", _) | (_, "rate_limit_error" | "rate_limit_exceeded") => E_RATE_LIMITED,
+            ("insufficient_quota", _) | (_, "insufficient_quota") => E_QUOTA,
+            ("invalid_api_key", _) | (_, "authentication_error" | "permission_error") => E_AUTH,
+            ("model_not_found", _) => E_MODEL_NOT_FOUND,
+            ("content_filter" | "content_policy_violation", _) => E_CONTENT_FILTER,
+            (_, "server_error" | "api_error" | "overloaded_error") => E_PROVIDER,
+            _ => E_API,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(code) = self.code.as_ref().or(self.error_type.as_ref()) {
+            write!(f, " ({code})")?;
+        }
+        Ok(())
+    }
+}
+
+impl ChatError {
+    /// The error for an unsuccessful response: `Api` if the body is a structured error, else `Status`.
+    pub fn from_status(status: u16, body: String) -> Self {
+        match ApiError::from_body(&body) {
+            Some(error) => ChatError::Api { status, error },
+            None => ChatError::Status { status, body },
+        }
+    }
+
+    /// The structured error from the provider, if there was one.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            ChatError::Api { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Whether retrying the same request later might succeed. The OpenAI client has already retried
+    /// these per its `RetryPolicy` before returning them; any other error is permanent.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ChatError::Http(e) => crate::client::is_transient(e),
+            ChatError::RateLimited => true,
+            ChatError::Api { status, .. } | ChatError::Status { status, .. } => {
+                matches!(status, 500 | 502 | 503 | 504 | 520..=524 | 529)
+            }
+            ChatError::Timeout | ChatError::ProviderUnavailable { .. } => true,
+            ChatError::BudgetExceeded(_) | ChatError::Other(_) => false,
+        }
+    }
+
+    /// Whether the provider itself appears to be down: server errors, timeouts, and connection
+    /// failures, but not rate limiting or bad requests.
+    pub fn is_outage(&self) -> bool {
+        match self {
+            ChatError::Http(e) => crate::client::is_transient(e),
+            ChatError::Api { status, .. } | ChatError::Status { status, .. } => *status >= 500,
+            ChatError::Timeout => true,
+            ChatError::RateLimited | ChatError::ProviderUnavailable { .. } | ChatError::BudgetExceeded(_) | ChatError::Other(_) => false,
+        }
+    }
+
+    /// Whether a circuit breaker short-circuited the request (`ProviderUnavailable`, formerly
+    /// `CircuitOpen`), whether on an `OpenAIClient` or in a `CircuitBreakerBackend`.
+    pub fn is_circuit_open(&self) -> bool {
+        matches!(self, ChatError::ProviderUnavailable { .. })
+    }
+
+    /// Whether a different model or provider might succeed where this one failed: rate limits,
+    /// outages, and context overflows (a larger-context model may fit the prompt).
+    pub fn is_fallback_worthy(&self) -> bool {
+        match self {
+            ChatError::Http(e) => e.is_connect(),
+            ChatError::RateLimited => true,
+            ChatError::Api { status, error } => *status >= 500 || error.code.as_deref() == Some("context_length_exceeded"),
+            ChatError::Status { status, body } => *status >= 500 || body.contains("context_length_exceeded"),
+            ChatError::Timeout | ChatError::ProviderUnavailable { .. } => true,
+            ChatError::BudgetExceeded(_) | ChatError::Other(_) => false,
+        }
+    }
+}
+
+impl ChatError {
+    /// The stable code for this error; see `error_codes`.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            ChatError::Http(e) if e.is_connect() => E_CONNECT,
+            ChatError::Http(_) => E_HTTP,
+            ChatError::RateLimited => E_RATE_LIMITED,
+            ChatError::Api { status, error } => match error.error_code() {
+                E_API => status_code(*status),
+                code => code,
+            },
+            ChatError::Status { body, .. } if body.contains("context_length_exceeded") => E_CONTEXT_OVERFLOW,
+            ChatError::Status { status, .. } => status_code(*status),
+            ChatError::Timeout => E_TIMEOUT,
+            ChatError::ProviderUnavailable { .. } => E_PROVIDER_UNAVAILABLE,
+            ChatError::BudgetExceeded(_) => E_BUDGET,
+            ChatError::Other(_) => E_OTHER,
+        }
+    }
+
+    /// The description, without the code.
+    pub fn message(&self) -> String {
+        match self {
+            ChatError::Http(e) => format!("HTTP error: {e}"),
+            ChatError::RateLimited => "Rate limited; exceeded max wait time".to_string(),
+            ChatError::Api { status, error } => format!("HTTP {status}: {error}"),
+            ChatError::Status { status, body } => format!("HTTP {status}: {body}"),
+            ChatError::Timeout => "Timed out".to_string(),
+            ChatError::ProviderUnavailable { provider, retry_in } => {
+                format!("{provider} is unavailable after repeated failures; retry in {retry_in:?}")
+            }
+            ChatError::BudgetExceeded(limit) => format!("Budget exceeded: {limit}"),
+            ChatError::Other(e) => e.clone(),
+        }
+    }
+}
+
+fn status_code(status: u16) -> &'static str {
+    match status {
+        401 | 403 => E_AUTH,
+        404 => E_NOT_FOUND,
+        429 => E_RATE_LIMITED,
+        500.. => E_PROVIDER,
+        _ => E_API,
+    }
+}
+
+impl fmt::Display for ChatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.error_code(), self.message())
+    }
+}
+
+/// Serialized form of the library's errors.
+#[derive(Serialize)]
+pub(crate) struct ErrorBody<'a> {
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_error: Option<&'a ApiError>,
+}
+
+/// `{"code": ..., "message": ...}`, plus the provider's `api_error` when there is one.
+impl Serialize for ChatError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorBody { code: self.error_code(), message: self.message(), api_error: self.api_error() }.serialize(serializer)
+    }
+}
+
+impl std::error::Error for ChatError {}
+
+impl From<reqwest::Error> for ChatError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else {
+            Self::Http(e)
+        }
+    }
+}
+
+#[async_trait]
+impl ChatBackend for OpenAIClient {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        self.chat_completion(req).await
+    }
+
+    async fn chat_streaming(
+        &self,
+        req: &ChatCompletionRequest,
+        on_delta: &mut OnArgumentsDelta<'_>,
+    ) -> Result<ChatCompletionResponse, ChatError> {
+        self.chat_completion_streaming(req, on_delta).await
+    }
+
+    fn default_model(&self) -> Model {
+        OpenAIClient::default_model(self)
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::finetuning::List;
+use crate::{ChatCompletionRequest, ChatCompletionResponse, ChatError, OpenAIClient};
+
+const BATCH_ENDPOINT: &str = "/v1/chat/completions";
+
+/// Submits chat completions through OpenAI's Batch API, which answers within 24 hours at half the
+/// price of synchronous requests. Suited to large offline jobs that don't need interactivity.
+///
+/// Requests are written as a JSONL file, uploaded, and run as a batch job; `results` downloads
+/// the answers once the job has finished, matched back to requests by their custom ID.
+pub struct BatchClient {
+    client: OpenAIClient,
+}
+
+/// A batch job, as reported by the API.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Batch {
+    pub id: String,
+    /// `validating`, `in_progress`, `finalizing`, `completed`, `failed`, `expired`, `cancelling`, or
+    /// `cancelled`
+    pub status: String,
+    pub input_file_id: String,
+    /// Set once some requests have succeeded
+    #[serde(default)]
+    pub output_file_id: Option<String>,
+    /// Set once some requests have failed
+    #[serde(default)]
+    pub error_file_id: Option<String>,
+    #[serde(default)]
+    pub request_counts: BatchRequestCounts,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+    /// Why the batch as a whole failed, e.g. an invalid input file
+    #[serde(default)]
+    pub errors: Option<serde_json::Value>,
+}
+
+impl Batch {
+    /// Whether the job has stopped, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status.as_str(), "completed" | "failed" | "expired" | "cancelled")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct BatchRequestCounts {
+    pub total: u64,
+    pub completed: u64,
+    pub failed: u64,
+}
+
+/// The outcome of one request in a batch.
+#[derive(Debug)]
+pub struct BatchResult {
+    pub custom_id: String,
+    pub result: Result<ChatCompletionResponse, ChatError>,
+}
+
+#[derive(Deserialize)]
+struct OutputLine {
+    custom_id: String,
+    #[serde(default)]
+    response: Option<OutputResponse>,
+    #[serde(default)]
+    error: Option<OutputError>,
+}
+
+#[derive(Deserialize)]
+struct OutputResponse {
+    status_code: u16,
+    body: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct OutputError {
+    #[serde(default)]
+    code: Option<String>,
+    message: String,
+}
+
+impl BatchClient {
+    pub fn new(client: OpenAIClient) -> Self {
+        Self { client }
+    }
+
+    /// Upload `requests`, each with a custom ID unique within the batch, and start a batch job.
+    pub async fn submit(&self, requests: impl IntoIterator<Item = (String, ChatCompletionRequest)>) -> Result<Batch, ChatError> {
+        let jsonl: String = requests
+            .into_iter()
+            .map(|(custom_id, req)| {
+                let line = json!({
+                    "custom_id": custom_id,
+                    "method": "POST",
+                    "url": BATCH_ENDPOINT,
+                    "body": self.client.request_body(&req),
+                });
+                format!("{line}\n")
+            })
+            .collect();
+        let file = self.client.upload_file("batch.jsonl", jsonl.into_bytes(), "batch").await?;
+        let body = json!({
+            "input_file_id": file.id,
+            "endpoint": BATCH_ENDPOINT,
+            "completion_window": "24h",
+        });
+        self.client.send_json(self.client.request(reqwest::Method::POST, "batches").json(&body)).await
+    }
+
+    pub async fn batch(&self, id: &str) -> Result<Batch, ChatError> {
+        self.client.send_json(self.client.request(reqwest::Method::GET, &format!("batches/{id}"))).await
+    }
+
+    /// The most recent batches, newest first.
+    pub async fn list(&self) -> Result<Vec<Batch>, ChatError> {
+        let list: List<Batch> = self.client.send_json(self.client.request(reqwest::Method::GET, "batches")).await?;
+        Ok(list.data)
+    }
+
+    pub async fn cancel(&self, id: &str) -> Result<Batch, ChatError> {
+        self.client.send_json(self.client.request(reqwest::Method::POST, &format!("batches/{id}/cancel"))).await
+    }
+
+    /// Poll a batch every `interval` until it finishes, and return it in its final state.
+    pub async fn wait(&self, id: &str, interval: Duration) -> Result<Batch, ChatError> {
+        loop {
+            let batch = self.batch(id).await?;
+            if batch.is_finished() {
+                return Ok(batch);
+            }
+            self.client.clock().sleep(interval).await;
+        }
+    }
+
+    /// The results of a finished batch, successes and failures alike, in no particular order.
+    /// Requests that never ran (e.g. because the batch expired) have no result.
+    pub async fn results(&self, batch: &Batch) -> Result<Vec<BatchResult>, ChatError> {
+        let mut results = vec![];
+        for file_id in [&batch.output_file_id, &batch.error_file_id].into_iter().flatten() {
+            let contents = self.client.file_content(file_id).await?;
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                let line: OutputLine = serde_json::from_str(line)
+                    .map_err(|e| ChatError::Other(format!("Failed to parse batch output ({e}): {line}")))?;
+                results.push(BatchResult { custom_id: line.custom_id.clone(), result: self.parse_result(line) });
+            }
+        }
+        Ok(results)
+    }
+
+    /// Submit `requests`, wait for the batch to finish (polling every `interval`), and return the
+    /// results keyed by custom ID.
+    pub async fn run(
+        &self,
+        requests: impl IntoIterator<Item = (String, ChatCompletionRequest)>,
+        interval: Duration,
+    ) -> Result<HashMap<String, Result<ChatCompletionResponse, ChatError>>, ChatError> {
+        let batch = self.submit(requests).await?;
+        let batch = self.wait(&batch.id, interval).await?;
+        if batch.status == "failed" {
+            let errors = batch.errors.map(|errors| errors.to_string()).unwrap_or_default();
+            return Err(ChatError::Other(format!("Batch {} failed: {errors}", batch.id)));
+        }
+        let results = self.results(&batch).await?;
+        Ok(results.into_iter().map(|result| (result.custom_id, result.result)).collect())
+    }
+
+    fn parse_result(&self, line: OutputLine) -> Result<ChatCompletionResponse, ChatError> {
+        if let Some(error) = line.error {
+            let code = error.code.map(|code| format!(" ({code})")).unwrap_or_default();
+            return Err(ChatError::Other(format!("{}{code}", error.message)));
+        }
+        let Some(response) = line.response else {
+            return Err(ChatError::Other("Batch output has neither a response nor an error".to_string()));
+        };
+        if !(200..300).contains(&response.status_code) {
+            return Err(ChatError::from_status(response.status_code, response.body.to_string()));
+        }
+        let mut response: ChatCompletionResponse = serde_json::from_value(response.body)
+            .map_err(|e| ChatError::Other(format!("Failed to parse batch response: {e}")))?;
+        response.attribution = Some(self.client.attribution());
+        Ok(response)
+    }
+}
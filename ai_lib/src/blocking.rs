@@ -0,0 +1,62 @@
+//! Synchronous wrappers for programs and build scripts that aren't async. Each call runs the async
+//! implementation to completion on a private single-threaded runtime, so these must not be called
+//! from inside an async context.
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{DynAiState, ChatCompletionRequest, ChatCompletionResponse, ChatError, ConfigError, DriveError, DriveOptions};
+
+fn runtime() -> Runtime {
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to build a Tokio runtime")
+}
+
+/// A synchronous `OpenAIClient`.
+pub struct OpenAIClient {
+    inner: crate::OpenAIClient,
+    runtime: Runtime,
+}
+
+impl OpenAIClient {
+    /// Panics if `OPENAI_API_KEY` is unset; see `try_new` for a fallible version.
+    pub fn new() -> Self {
+        Self::from_async(crate::OpenAIClient::new())
+    }
+
+    pub fn try_new() -> Result<Self, ConfigError> {
+        Ok(Self::from_async(crate::OpenAIClient::try_new()?))
+    }
+
+    /// Wrap a client configured with `OpenAIClient::builder()`.
+    pub fn from_async(inner: crate::OpenAIClient) -> Self {
+        Self { inner, runtime: runtime() }
+    }
+
+    pub fn chat_completion(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        self.runtime.block_on(self.inner.chat_completion(req))
+    }
+
+    pub fn into_async(self) -> crate::OpenAIClient {
+        self.inner
+    }
+}
+
+impl Default for OpenAIClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn drive<S: DynAiState + ?Sized>(state: &mut S) -> Result<(), DriveError> {
+    runtime().block_on(crate::drive(state))
+}
+
+pub fn drive_with<S: DynAiState + ?Sized>(state: &mut S, options: &DriveOptions) -> Result<(), DriveError> {
+    runtime().block_on(crate::drive_with(state, options))
+}
+
+pub fn drive_to_json<S: DynAiState + ?Sized>(state: &mut S) -> Result<serde_json::Value, DriveError> {
+    runtime().block_on(crate::drive_to_json(state))
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::{global_pricing, ChatError, Usage};
+
+/// Which limit of a `Budget` was reached.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetLimit {
+    Tokens { spent: u64, max: u64 },
+    /// USD, per the global pricing table
+    Cost { spent: f64, max: f64 },
+}
+
+impl fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetLimit::Tokens { spent, max } => write!(f, "{spent} tokens spent of a {max} token budget"),
+            BudgetLimit::Cost { spent, max } => write!(f, "${spent:.4} spent of a ${max:.4} budget"),
+        }
+    }
+}
+
+/// A hard limit on total tokens and/or dollars spent by a client. Once either is reached, further
+/// requests fail with `ChatError::BudgetExceeded` without being sent, so an unattended batch run
+/// can't keep spending. The request that crosses the limit is still answered, so the total can
+/// overshoot by one response. Responses from models missing from the pricing table count towards
+/// the token limit only.
+///
+/// Set it with `OpenAIClientBuilder::budget`. Clones share the same spend, so several clients can
+/// draw on one budget.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    max_tokens: Option<u64>,
+    max_cost: Option<f64>,
+    spent: Arc<Mutex<Spent>>,
+}
+
+#[derive(Debug, Default)]
+struct Spent {
+    tokens: u64,
+    cost: f64,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit prompt plus completion tokens.
+    pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Limit cost in USD.
+    pub fn max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    pub fn spent_tokens(&self) -> u64 {
+        self.spent.lock().unwrap().tokens
+    }
+
+    pub fn spent_cost(&self) -> f64 {
+        self.spent.lock().unwrap().cost
+    }
+
+    /// The limit that has been reached, if any.
+    pub fn exceeded(&self) -> Option<BudgetLimit> {
+        let spent = self.spent.lock().unwrap();
+        if let Some(max) = self.max_tokens.filter(|&max| spent.tokens >= max) {
+            return Some(BudgetLimit::Tokens { spent: spent.tokens, max });
+        }
+        self.max_cost.filter(|&max| spent.cost >= max).map(|max| BudgetLimit::Cost { spent: spent.cost, max })
+    }
+
+    /// Err if no more requests may be sent.
+    pub(crate) fn check(&self) -> Result<(), ChatError> {
+        match self.exceeded() {
+            Some(limit) => Err(ChatError::BudgetExceeded(limit)),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn record(&self, model: &str, usage: &Usage) {
+        let cost = global_pricing().cost(model, usage).unwrap_or_default();
+        let mut spent = self.spent.lock().unwrap();
+        spent.tokens += (usage.prompt_tokens.max(0) + usage.completion_tokens.max(0)) as u64;
+        spent.cost += cost;
+    }
+}
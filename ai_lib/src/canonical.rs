@@ -0,0 +1,26 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Serialize to JSON with object keys sorted at every level, so equal values always produce
+/// byte-identical output regardless of field or map insertion order.
+pub fn canonical_json(value: &impl Serialize) -> String {
+    sort_keys(serde_json::to_value(value).expect("Value is not serializable")).to_string()
+}
+
+/// Hex SHA-256 of the canonical JSON, suitable as a cache or cassette key.
+pub fn canonical_hash(value: &impl Serialize) -> String {
+    let digest = Sha256::digest(canonical_json(value).as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => {
+            let mut entries: Vec<_> = object.into_iter().map(|(k, v)| (k, sort_keys(v))).collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            serde_json::Value::Object(entries.into_iter().collect())
+        }
+        serde_json::Value::Array(items) => items.into_iter().map(sort_keys).collect(),
+        other => other,
+    }
+}
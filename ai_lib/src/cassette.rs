@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{Attribution, ChatBackend, ChatCompletionRequest, ChatCompletionResponse, ChatError, Model};
+
+/// Whether a `Cassette` may call its backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Replay recorded responses, recording any request that has none
+    #[default]
+    Auto,
+    /// Only replay; a request without a recording fails. Use in CI so a changed prompt can't
+    /// silently reach the real API.
+    Replay,
+    /// Always call the backend, replacing the recordings of every request made
+    Record,
+}
+
+/// Records request/response pairs to a JSON file on the first run and replays them on later runs,
+/// matching on `ChatCompletionRequest::cache_key`, so pipelines run in CI for free and deterministically.
+/// A request made several times in a run replays its recordings in order.
+pub struct Cassette<B> {
+    backend: B,
+    path: PathBuf,
+    mode: CassetteMode,
+    state: Mutex<CassetteState>,
+}
+
+#[derive(Default)]
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    /// How many recordings of each request have been replayed this run
+    replayed: HashMap<String, usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Interaction {
+    key: String,
+    request: serde_json::Value,
+    response: ChatCompletionResponse,
+}
+
+#[derive(Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+impl<B: ChatBackend> Cassette<B> {
+    /// Load the cassette at `path`, or start an empty one if it doesn't exist yet.
+    pub fn open(path: impl AsRef<Path>, backend: B) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let interactions = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str::<CassetteFile>(&json)?.interactions,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        let state = CassetteState { interactions, replayed: HashMap::new() };
+        Ok(Self { backend, path, mode: CassetteMode::Auto, state: Mutex::new(state) })
+    }
+
+    pub fn mode(mut self, mode: CassetteMode) -> Self {
+        self.mode = mode;
+        if mode == CassetteMode::Record {
+            self.state.get_mut().unwrap().interactions.clear();
+        }
+        self
+    }
+
+    /// The next unreplayed recording of the request with `key`.
+    fn replay(&self, key: &str) -> Option<ChatCompletionResponse> {
+        let mut state = self.state.lock().unwrap();
+        let index = *state.replayed.get(key).unwrap_or(&0);
+        let response = state.interactions.iter().filter(|i| i.key == key).nth(index)?.response.clone();
+        state.replayed.insert(key.to_string(), index + 1);
+        Some(response)
+    }
+
+    fn record(&self, key: String, req: &ChatCompletionRequest, response: &ChatCompletionResponse) -> Result<(), ChatError> {
+        let mut state = self.state.lock().unwrap();
+        let request = serde_json::from_str(&req.canonical_json()).unwrap();
+        state.interactions.push(Interaction { key: key.clone(), request, response: response.clone() });
+        *state.replayed.entry(key).or_default() += 1;
+
+        // Written after every recording, so an interrupted run keeps what it paid for
+        let json = serde_json::to_string_pretty(&serde_json::json!({ "interactions": state.interactions })).unwrap();
+        std::fs::write(&self.path, json)
+            .map_err(|e| ChatError::Other(format!("Failed to write cassette {}: {e}", self.path.display())))
+    }
+}
+
+#[async_trait]
+impl<B: ChatBackend> ChatBackend for Cassette<B> {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        let key = req.cache_key();
+        if self.mode != CassetteMode::Record {
+            if let Some(mut response) = self.replay(&key) {
+                response.attribution = Some(Attribution::new("cassette", None));
+                return Ok(response);
+            }
+        }
+        if self.mode == CassetteMode::Replay {
+            return Err(ChatError::Other(format!("No recording of request {key} in cassette {}", self.path.display())));
+        }
+
+        let response = self.backend.chat(req).await?;
+        self.record(key, req, &response)?;
+        Ok(response)
+    }
+
+    fn default_model(&self) -> Model {
+        self.backend.default_model()
+    }
+}
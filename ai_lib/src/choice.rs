@@ -0,0 +1,73 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{validate_arguments, Choice, Function};
+
+type SelectFn = Arc<dyn Fn(&[Choice], &[Function]) -> usize + Send + Sync>;
+
+/// Which of several choices the driver acts on when a prompt samples more than one (see
+/// `DriveOptions::choices`), e.g. to keep the one candidate call out of three that validates.
+#[derive(Clone, Default)]
+pub enum ChoiceSelector {
+    #[default]
+    First,
+    /// The first call to an offered function whose arguments validate against its schema, or the
+    /// first choice if none does
+    FirstValid,
+    /// The choice with the shortest function call arguments (or text, for a text reply)
+    Shortest,
+    /// Given the choices and the offered functions, returns the index of the choice to use
+    Custom(SelectFn),
+}
+
+impl fmt::Debug for ChoiceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChoiceSelector::First => write!(f, "First"),
+            ChoiceSelector::FirstValid => write!(f, "FirstValid"),
+            ChoiceSelector::Shortest => write!(f, "Shortest"),
+            ChoiceSelector::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl ChoiceSelector {
+    pub fn custom(select: impl Fn(&[Choice], &[Function]) -> usize + Send + Sync + 'static) -> Self {
+        ChoiceSelector::Custom(Arc::new(select))
+    }
+
+    /// The index of the choice to use. Out-of-range answers from a custom selector fall back to the
+    /// first choice.
+    pub fn select(&self, choices: &[Choice], functions: &[Function]) -> usize {
+        let index = match self {
+            ChoiceSelector::First => 0,
+            ChoiceSelector::FirstValid => choices.iter().position(|choice| is_valid(choice, functions)).unwrap_or(0),
+            ChoiceSelector::Shortest => {
+                choices.iter().enumerate().min_by_key(|(_, choice)| reply_len(choice)).map_or(0, |(index, _)| index)
+            }
+            ChoiceSelector::Custom(select) => select(choices, functions),
+        };
+        if index < choices.len() {
+            index
+        } else {
+            0
+        }
+    }
+}
+
+fn is_valid(choice: &Choice, functions: &[Function]) -> bool {
+    let Some(call) = &choice.message.function_call else {
+        return false;
+    };
+    functions
+        .iter()
+        .find(|function| function.name == call.name)
+        .is_some_and(|function| validate_arguments(&function.parameters, &call.arguments).is_ok())
+}
+
+fn reply_len(choice: &Choice) -> usize {
+    match &choice.message.function_call {
+        Some(call) => call.arguments.len(),
+        None => choice.message.content.as_deref().unwrap_or_default().len(),
+    }
+}
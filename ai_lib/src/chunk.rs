@@ -0,0 +1,177 @@
+/// Where `chunk_text` prefers to split. Pieces too large for a chunk are split again at the next
+/// finer boundary (headings, then sentences, then words).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChunkBoundary {
+    /// Between whitespace-separated words
+    Words,
+    /// After sentence-ending punctuation and at paragraph breaks
+    #[default]
+    Sentences,
+    /// Before markdown headings, keeping each section together where possible
+    Headings,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkPolicy {
+    /// Largest chunk, in estimated tokens (about four characters each)
+    pub max_tokens: usize,
+    /// Each chunk after the first repeats up to this many tokens from the end of the previous one, so
+    /// context spanning a split isn't lost
+    pub overlap_tokens: usize,
+    pub boundary: ChunkBoundary,
+}
+
+impl Default for ChunkPolicy {
+    fn default() -> Self {
+        Self { max_tokens: 500, overlap_tokens: 50, boundary: ChunkBoundary::Sentences }
+    }
+}
+
+impl ChunkPolicy {
+    pub fn new(max_tokens: usize) -> Self {
+        Self { max_tokens, ..Self::default() }
+    }
+
+    pub fn overlap(mut self, overlap_tokens: usize) -> Self {
+        self.overlap_tokens = overlap_tokens;
+        self
+    }
+
+    pub fn boundary(mut self, boundary: ChunkBoundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+}
+
+/// Split `text` into chunks of at most `policy.max_tokens` estimated tokens, splitting at the
+/// policy's boundary where possible, for map-reduce and retrieval over documents too large for one
+/// prompt.
+pub fn chunk_text(text: &str, policy: &ChunkPolicy) -> Vec<String> {
+    let max_tokens = policy.max_tokens.max(1);
+    let mut pieces = vec![];
+    split(text, policy.boundary, max_tokens, &mut pieces);
+
+    let mut chunks = vec![];
+    let mut current: Vec<&str> = vec![];
+    for piece in pieces {
+        if !current.is_empty() && tokens(&current) + estimate_tokens(piece) > max_tokens {
+            chunks.push(current.concat());
+            // Carry over whole pieces from the end, as many as fit in the overlap
+            let mut overlap = vec![];
+            for previous in current.iter().rev() {
+                if tokens(&overlap) + estimate_tokens(previous) > policy.overlap_tokens {
+                    break;
+                }
+                overlap.insert(0, *previous);
+            }
+            current = overlap;
+            while !current.is_empty() && tokens(&current) + estimate_tokens(piece) > max_tokens {
+                current.remove(0);
+            }
+        }
+        current.push(piece);
+    }
+    if !current.is_empty() {
+        chunks.push(current.concat());
+    }
+
+    chunks
+        .into_iter()
+        .map(|chunk| chunk.trim().to_string())
+        .filter(|chunk| !chunk.is_empty())
+        .collect()
+}
+
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+fn tokens(pieces: &[&str]) -> usize {
+    pieces.iter().map(|piece| estimate_tokens(piece)).sum()
+}
+
+/// Break `text` into contiguous pieces no larger than `max_tokens`, preferring `boundary`.
+fn split<'a>(text: &'a str, boundary: ChunkBoundary, max_tokens: usize, pieces: &mut Vec<&'a str>) {
+    let (segments, finer) = match boundary {
+        ChunkBoundary::Headings => (split_before_headings(text), Some(ChunkBoundary::Sentences)),
+        ChunkBoundary::Sentences => (split_after_sentences(text), Some(ChunkBoundary::Words)),
+        ChunkBoundary::Words => (split_after_words(text), None),
+    };
+    for segment in segments {
+        match finer {
+            Some(finer) if estimate_tokens(segment) > max_tokens => split(segment, finer, max_tokens, pieces),
+            None if estimate_tokens(segment) > max_tokens => pieces.extend(split_chars(segment, max_tokens * 4)),
+            _ => pieces.push(segment),
+        }
+    }
+}
+
+fn split_at(text: &str, ends: impl IntoIterator<Item = usize>) -> Vec<&str> {
+    let mut segments = vec![];
+    let mut start = 0;
+    for end in ends {
+        if end > start {
+            segments.push(&text[start..end]);
+            start = end;
+        }
+    }
+    if start < text.len() {
+        segments.push(&text[start..]);
+    }
+    segments
+}
+
+fn split_before_headings(text: &str) -> Vec<&str> {
+    let mut starts = vec![];
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with('#') {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+    split_at(text, starts)
+}
+
+fn split_after_sentences(text: &str) -> Vec<&str> {
+    let mut ends = vec![];
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let sentence_end = matches!(c, '.' | '!' | '?' | '。' | '！' | '？');
+        let paragraph_end = c == '\n' && chars.peek().is_some_and(|(_, next)| *next == '\n');
+        if sentence_end || paragraph_end {
+            // Keep trailing whitespace with the sentence it follows
+            let mut end = i + c.len_utf8();
+            let mut saw_whitespace = false;
+            while let Some((j, next)) = chars.peek().copied() {
+                if !next.is_whitespace() {
+                    break;
+                }
+                saw_whitespace = true;
+                end = j + next.len_utf8();
+                chars.next();
+            }
+            if saw_whitespace || paragraph_end || matches!(c, '。' | '！' | '？') {
+                ends.push(end);
+            }
+        }
+    }
+    split_at(text, ends)
+}
+
+fn split_after_words(text: &str) -> Vec<&str> {
+    let mut ends = vec![];
+    let mut previous_whitespace = false;
+    for (i, c) in text.char_indices() {
+        if previous_whitespace && !c.is_whitespace() {
+            ends.push(i);
+        }
+        previous_whitespace = c.is_whitespace();
+    }
+    split_at(text, ends)
+}
+
+fn split_chars(text: &str, max_chars: usize) -> Vec<&str> {
+    let ends = text.char_indices().map(|(i, _)| i).skip(max_chars).step_by(max_chars.max(1));
+    split_at(text, ends)
+}
@@ -0,0 +1,131 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::{ChatBackend, ChatCompletionRequest, ChatCompletionResponse, ChatError, Clock, Model, OnArgumentsDelta, SystemClock};
+
+/// Stops sending requests to a provider that keeps failing. After `failure_threshold` consecutive
+/// outages (server errors, timeouts, or connection failures, each after the client's own retries)
+/// the circuit opens, and requests fail immediately with `ChatError::ProviderUnavailable` until
+/// `cooldown` has passed. Then a single half-open probe is let through: success closes the circuit,
+/// failure opens it for another cooldown.
+///
+/// Set one on an `OpenAIClient` with its builder, or put any backend behind one with
+/// `CircuitBreakerBackend`.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<CircuitState>,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self::with_clock(failure_threshold, cooldown, Arc::new(SystemClock))
+    }
+
+    /// Like `new`, timing cooldowns by `clock`, e.g. a `SimulatedClock` in tests. A breaker given to
+    /// an `OpenAIClient` is timed by the client's clock instead.
+    pub fn with_clock(failure_threshold: u32, cooldown: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self { failure_threshold: failure_threshold.max(1), cooldown, state: Mutex::default(), clock }
+    }
+
+    pub(crate) fn timed_by(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    /// Whether the circuit is currently rejecting requests.
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.open_until.is_some_and(|open_until| self.clock.now() < open_until) || state.trial_in_flight
+    }
+
+    /// Err if the request should be short-circuited.
+    pub(crate) fn check(&self, provider: &str) -> Result<(), ChatError> {
+        let unavailable = |retry_in| ChatError::ProviderUnavailable { provider: provider.to_string(), retry_in };
+        let mut state = self.state.lock().unwrap();
+        let Some(open_until) = state.open_until else {
+            return Ok(());
+        };
+        let now = self.clock.now();
+        if now < open_until {
+            return Err(unavailable(open_until - now));
+        }
+        // A trial that never reported back (e.g. its future was dropped) is given up on after a cooldown
+        if state.trial_in_flight && now < open_until + self.cooldown {
+            return Err(unavailable(Duration::ZERO));
+        }
+        state.trial_in_flight = true;
+        Ok(())
+    }
+
+    pub(crate) fn record<T>(&self, result: &Result<T, ChatError>) {
+        let mut state = self.state.lock().unwrap();
+        state.trial_in_flight = false;
+        // Any other error still means the provider is up and answering
+        if !matches!(result, Err(e) if e.is_outage()) {
+            state.consecutive_failures = 0;
+            state.open_until = None;
+            return;
+        }
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.failure_threshold || state.open_until.is_some() {
+            diagnostics::emit(Diagnostic::CircuitOpened { failures: state.consecutive_failures, cooldown: self.cooldown });
+            state.open_until = Some(self.clock.now() + self.cooldown);
+        }
+    }
+}
+
+/// Any backend behind a `CircuitBreaker`, so fleets of agents sharing it fail fast while its
+/// provider is down.
+pub struct CircuitBreakerBackend<B> {
+    backend: B,
+    provider: String,
+    circuit_breaker: CircuitBreaker,
+}
+
+impl<B: ChatBackend> CircuitBreakerBackend<B> {
+    /// `provider` names the backend in `ProviderUnavailable` errors.
+    pub fn new(backend: B, provider: impl Into<String>, circuit_breaker: CircuitBreaker) -> Self {
+        Self { backend, provider: provider.into(), circuit_breaker }
+    }
+
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.circuit_breaker
+    }
+}
+
+#[async_trait]
+impl<B: ChatBackend> ChatBackend for CircuitBreakerBackend<B> {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        self.circuit_breaker.check(&self.provider)?;
+        let result = self.backend.chat(req).await;
+        self.circuit_breaker.record(&result);
+        result
+    }
+
+    async fn chat_streaming(
+        &self,
+        req: &ChatCompletionRequest,
+        on_delta: &mut OnArgumentsDelta<'_>,
+    ) -> Result<ChatCompletionResponse, ChatError> {
+        self.circuit_breaker.check(&self.provider)?;
+        let result = self.backend.chat_streaming(req, on_delta).await;
+        self.circuit_breaker.record(&result);
+        result
+    }
+
+    fn default_model(&self) -> Model {
+        self.backend.default_model()
+    }
+}
@@ -0,0 +1,41 @@
+use crate::Function;
+
+/// Reserved: `#[ai_functions]` rejects a function with this name
+pub(crate) const CLARIFY_FUNCTION: &str = "provide_missing_fields";
+
+/// A pending clarification sub-dialogue: the call that kept failing validation, and the
+/// `provide_missing_fields` function the model is asked to call with just the failing fields.
+pub(crate) struct Clarification {
+    pub function_name: String,
+    pub arguments: String,
+    pub function: Function,
+}
+
+impl Clarification {
+    pub fn new(original: &Function, arguments: String, fields: &[String]) -> Self {
+        let mut parameters = original.parameters.clone();
+        if let Some(properties) = parameters.get_mut("properties").and_then(|p| p.as_object_mut()) {
+            properties.retain(|name, _| fields.contains(name));
+        }
+        parameters["required"] = fields.into();
+
+        Self {
+            function_name: original.name.clone(),
+            arguments,
+            function: Function {
+                name: CLARIFY_FUNCTION.to_string(),
+                description: format!("Provide corrected values for the invalid fields of your {} call", original.name),
+                parameters,
+            },
+        }
+    }
+
+    pub fn prompt(&self, fields: &[String]) -> String {
+        format!(
+            "Your {} call was rejected repeatedly. Keep your other arguments and call {} with corrected values for only these fields: {}",
+            self.function_name,
+            CLARIFY_FUNCTION,
+            fields.join(", "),
+        )
+    }
+}
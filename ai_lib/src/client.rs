@@ -0,0 +1,817 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::Client;
+use tokio::sync::Semaphore;
+use serde::Serialize;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::error_codes;
+use crate::trace;
+use crate::endpoints::Endpoints;
+use crate::keys::ApiKeys;
+use crate::random;
+use crate::ratelimit::{estimate_tokens, Reservation};
+use crate::retry::retry_after;
+use crate::streaming::{self, OnArgumentsDelta};
+use crate::{
+    canonical_hash, global_pricing, ApiError, Attribution, Budget, ChatCompletionRequest, ChatCompletionRequestBuilder, ChatError, ChatCompletionResponse, CircuitBreaker, Clock, KeyRotation, Message, Model,
+    Metric, MetricsSink, RandomSource, RateLimiter, ResponseCache, RetryPolicy, SpooledResponse, SystemClock, ThreadRandom, Usage,
+};
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+/// How long a rate-limited key is skipped when the response doesn't say when its limit resets
+const DEFAULT_KEY_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// The index of the API key a response was served with, kept in the response's extensions.
+#[derive(Debug, Clone, Copy)]
+struct ServedBy(usize);
+
+pub struct OpenAIClient {
+    client: Client,
+    /// Empty for local servers that don't authenticate
+    keys: ApiKeys,
+    endpoints: Endpoints,
+    organization: Option<String>,
+    project: Option<String>,
+    default_model: Model,
+    retry_policy: RetryPolicy,
+    random: Arc<dyn RandomSource>,
+    clock: Arc<dyn Clock>,
+    circuit_breaker: Option<CircuitBreaker>,
+    rate_limiter: Option<RateLimiter>,
+    response_cache: Option<ResponseCache>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    budget: Option<Budget>,
+    in_flight: Option<Semaphore>,
+    /// Provider-specific headers sent with every request (e.g. OpenRouter's `X-Title`)
+    pub(crate) extra_headers: Vec<(String, String)>,
+    /// Query parameters added to every request URL (e.g. Azure's `api-version`)
+    extra_query: Vec<(String, String)>,
+    /// Provider-specific fields merged into every request body (e.g. OpenRouter's `provider`)
+    pub(crate) extra_body: serde_json::Map<String, serde_json::Value>,
+    /// Reported as the provider in usage attribution, instead of the API host
+    pub(crate) provider_name: Option<String>,
+}
+
+impl Default for OpenAIClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenAIClient {
+    /// Panics if `OPENAI_API_KEY` is unset; see `try_new` for a fallible version.
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Configure a client from the environment, returning an error rather than panicking if that fails.
+    pub fn try_new() -> Result<Self, ConfigError> {
+        Self::builder().try_build()
+    }
+
+    pub fn builder() -> OpenAIClientBuilder {
+        OpenAIClientBuilder::default()
+    }
+
+    /// The model used by the driver when a prompt doesn't ask for a specific one.
+    pub fn default_model(&self) -> Model {
+        self.default_model.clone()
+    }
+
+    pub async fn chat_completion(
+        &self,
+        req: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, ChatError> {
+        self.instrumented(req, self.complete(req), |response| Some((response.model.clone(), response.usage.clone()))).await
+    }
+
+    /// Trace `completion` and report it to the metrics sink, with the model and usage `accounting`
+    /// reads from its result.
+    async fn instrumented<T>(
+        &self,
+        req: &ChatCompletionRequest,
+        completion: impl std::future::Future<Output = Result<T, ChatError>>,
+        accounting: impl Fn(&T) -> Option<(String, Usage)>,
+    ) -> Result<T, ChatError> {
+        let span = trace::Span::chat_completion(req.model.as_str());
+        let start = Instant::now();
+        let result = span.instrument(completion).await;
+        let latency = start.elapsed();
+        span.record("latency_ms", latency.as_millis() as u64);
+        let usage = result.as_ref().ok().and_then(&accounting);
+        if let Some((_, usage)) = &usage {
+            span.record("prompt_tokens", usage.prompt_tokens.max(0) as u64);
+            span.record("completion_tokens", usage.completion_tokens.max(0) as u64);
+        }
+        if let Some(metrics) = &self.metrics {
+            let model = req.model.as_str();
+            metrics.record(Metric::Requests, model, 1.0);
+            metrics.record(Metric::Latency, model, latency.as_secs_f64());
+            match (&result, &usage) {
+                (Ok(_), Some((served_model, usage))) => {
+                    metrics.record(Metric::PromptTokens, model, usage.prompt_tokens.max(0) as f64);
+                    metrics.record(Metric::CompletionTokens, model, usage.completion_tokens.max(0) as f64);
+                    if let Some(cost) = global_pricing().cost(served_model, usage) {
+                        metrics.record(Metric::Cost, model, cost);
+                    }
+                }
+                (Ok(_), None) => {}
+                (Err(_), _) => metrics.record(Metric::Errors, model, 1.0),
+            }
+        }
+        result
+    }
+
+    async fn complete(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        let cache = self.response_cache.as_ref().filter(|_| req.temperature == Some(0.0)).map(|cache| (cache, self.cache_key(req)));
+        if let Some((cache, key)) = &cache {
+            if let Some(mut response) = cache.get(key) {
+                response.usage = Usage::default();
+                response.attribution = Some(Attribution::new("cache", None));
+                return Ok(response);
+            }
+        }
+
+        if let Some(budget) = &self.budget {
+            budget.check()?;
+        }
+        let reservation = self.reserve(req).await;
+        let res = self.send(req).await?;
+        let served_by = res.extensions().get::<ServedBy>().copied();
+        let body = res.text().await?;
+
+        // Some gateways report errors with a success status
+        let mut response = serde_json::from_str::<ChatCompletionResponse>(&body).map_err(|e| match ApiError::from_body(&body) {
+            Some(error) => ChatError::Api { status: 200, error },
+            None => ChatError::Other(format!("Failed to parse response ({e}): {body}")),
+        })?;
+        if let Some(reservation) = reservation {
+            reservation.settle(response.usage.total_tokens.max(0) as u32);
+        }
+        if let Some(budget) = &self.budget {
+            budget.record(&response.model, &response.usage);
+        }
+        if let Some((cache, key)) = &cache {
+            cache.put(key, &response);
+        }
+        response.attribution = Some(self.attribution_for(served_by.as_ref()));
+        Ok(response)
+    }
+
+    /// Count one occurrence of `metric` for the request's model.
+    fn count(&self, metric: Metric, req: &ChatCompletionRequest) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record(metric, req.model.as_str(), 1.0);
+        }
+    }
+
+    /// Identifies a request to this server, including the client's extra body parameters.
+    fn cache_key(&self, req: &ChatCompletionRequest) -> String {
+        canonical_hash(&serde_json::json!({
+            "base_url": self.endpoints.primary(),
+            "request": req,
+            "extra_body": self.extra_body,
+        }))
+    }
+
+    /// The provider (identified by the API host) and masked key that requests are billed to.
+    pub fn attribution(&self) -> Attribution {
+        if let Some(provider) = &self.provider_name {
+            return Attribution::new(provider.clone(), self.keys.primary());
+        }
+        let host = reqwest::Url::parse(self.endpoints.primary())
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_else(|| self.endpoints.primary().to_string());
+        Attribution::new(host, self.keys.primary())
+    }
+
+    /// Like `attribution`, for a response served with the key at `key` (see `ServedBy`).
+    fn attribution_for(&self, served_by: Option<&ServedBy>) -> Attribution {
+        let attribution = self.attribution();
+        match served_by.and_then(|served_by| self.keys.get(served_by.0)) {
+            Some(key) => Attribution::new(attribution.provider, Some(key)),
+            None => attribution,
+        }
+    }
+
+    /// Like `chat_completion`, but the response body is streamed to a temporary file instead of being
+    /// buffered in memory, for very large completions.
+    pub async fn chat_completion_to_file(
+        &self,
+        req: &ChatCompletionRequest,
+    ) -> Result<SpooledResponse, ChatError> {
+        let completion = self.complete_to_file(req);
+        let (response, _) = self.instrumented(req, completion, |(_, accounting)| accounting.clone()).await?;
+        Ok(response)
+    }
+
+    /// The spooled response, with the model and usage it reports if it could be read.
+    async fn complete_to_file(&self, req: &ChatCompletionRequest) -> Result<(SpooledResponse, Option<(String, Usage)>), ChatError> {
+        if let Some(budget) = &self.budget {
+            budget.check()?;
+        }
+        let reservation = self.reserve(req).await;
+        let res = self.send(req).await?;
+        let response = SpooledResponse::from_response(res).await?;
+        let accounting = response.accounting();
+        match &accounting {
+            Some((model, usage)) => {
+                if let Some(reservation) = reservation {
+                    reservation.settle(usage.total_tokens.max(0) as u32);
+                }
+                if let Some(budget) = &self.budget {
+                    budget.record(model, usage);
+                }
+            }
+            // The body isn't a completion we can read usage from, so keep the estimate
+            None => {
+                if let Some(reservation) = reservation {
+                    reservation.keep();
+                }
+            }
+        }
+        Ok((response, accounting))
+    }
+
+    /// Like `chat_completion`, but the completion is streamed, with each fragment of function call
+    /// arguments passed to `on_delta` as it arrives. Streamed responses aren't cached.
+    pub async fn chat_completion_streaming(
+        &self,
+        req: &ChatCompletionRequest,
+        on_delta: &mut OnArgumentsDelta<'_>,
+    ) -> Result<ChatCompletionResponse, ChatError> {
+        if let Some(budget) = &self.budget {
+            budget.check()?;
+        }
+        let reservation = self.reserve(req).await;
+        let mut body = self.request_body(req);
+        body["stream"] = true.into();
+        body["stream_options"] = serde_json::json!({ "include_usage": true });
+        let res = self.post("chat/completions", &body, req).await?;
+        let served_by = res.extensions().get::<ServedBy>().copied();
+
+        let mut response = streaming::read_stream(res, on_delta).await?;
+        if let Some(reservation) = reservation {
+            reservation.settle(response.usage.total_tokens.max(0) as u32);
+        }
+        if let Some(budget) = &self.budget {
+            budget.record(&response.model, &response.usage);
+        }
+        response.attribution = Some(self.attribution_for(served_by.as_ref()));
+        Ok(response)
+    }
+
+    /// Take the request's estimated tokens from the rate limiter, if there is one. Dropping the
+    /// reservation unsettled, as an early return on error does, gives them back.
+    async fn reserve(&self, req: &ChatCompletionRequest) -> Option<Reservation<'_>> {
+        match &self.rate_limiter {
+            Some(rate_limiter) => Some(rate_limiter.reserve(estimate_tokens(req)).await),
+            None => None,
+        }
+    }
+
+    async fn send(&self, req: &ChatCompletionRequest) -> Result<reqwest::Response, ChatError> {
+        let req = req.for_model_capabilities();
+        if self.extra_body.is_empty() && !req.messages.iter().any(Message::has_attachments) {
+            self.post("chat/completions", &*req, &req).await
+        } else {
+            self.post("chat/completions", &self.request_body(&req), &req).await
+        }
+    }
+
+    /// The JSON body sent for `req`: file attachments as content parts, plus the client's extra body
+    /// parameters.
+    pub(crate) fn request_body(&self, req: &ChatCompletionRequest) -> serde_json::Value {
+        let req = req.for_model_capabilities();
+        let mut body = serde_json::to_value(&*req).unwrap();
+        let messages: Vec<_> = req.messages.iter().map(Message::to_wire).collect();
+        body["messages"] = messages.into();
+        body.as_object_mut().unwrap().extend(self.extra_body.clone());
+        body
+    }
+
+    /// POST a JSON body to `path` (relative to the base URL), retrying rate limits, transient server
+    /// errors, and transient transport errors per the retry policy. Unsuccessful statuses are returned as errors.
+    /// The timeout, headers, and query parameters of `req` apply to each attempt.
+    pub(crate) async fn post(&self, path: &str, body: &impl Serialize, req: &ChatCompletionRequest) -> Result<reqwest::Response, ChatError> {
+        self.post_with(path, req, |res| res.json(body)).await
+    }
+
+    /// Like `post`, with `body` attaching the body to each attempt, for bodies such as multipart forms
+    /// that can't be reused.
+    async fn post_with(
+        &self,
+        path: &str,
+        req: &ChatCompletionRequest,
+        body: impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, ChatError> {
+        // Tokio's semaphore is fair, so waiting requests are sent in the order they arrived
+        let _permit = match &self.in_flight {
+            Some(in_flight) => Some(in_flight.acquire().await.expect("Semaphore is never closed")),
+            None => None,
+        };
+        let Some(circuit_breaker) = &self.circuit_breaker else {
+            return self.post_with_retries(path, &body, req).await;
+        };
+        circuit_breaker.check(&self.attribution().provider)?;
+        let result = self.post_with_retries(path, &body, req).await;
+        circuit_breaker.record(&result);
+        result
+    }
+
+    /// POST a JSON body to an endpoint other than chat completions and parse the JSON response,
+    /// with the same retries, circuit breaker, and concurrency limit as chat. `model` labels metrics.
+    pub(crate) async fn post_json<T: serde::de::DeserializeOwned>(&self, path: &str, body: &impl Serialize, model: &str) -> Result<T, ChatError> {
+        let body = self.post(path, body, &Self::endpoint_request(model)).await?.text().await?;
+        serde_json::from_str(&body).map_err(|e| ChatError::Other(format!("Failed to parse response ({e}): {body}")))
+    }
+
+    /// Like `post_json`, returning the raw response body, e.g. generated audio.
+    pub(crate) async fn post_bytes(&self, path: &str, body: &impl Serialize, model: &str) -> Result<Vec<u8>, ChatError> {
+        Ok(self.post(path, body, &Self::endpoint_request(model)).await?.bytes().await?.to_vec())
+    }
+
+    /// Like `post_json`, with a multipart body built afresh by `form` for each attempt.
+    pub(crate) async fn post_multipart<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        form: impl Fn() -> reqwest::multipart::Form,
+        model: &str,
+    ) -> Result<T, ChatError> {
+        let res = self.post_with(path, &Self::endpoint_request(model), |res| res.multipart(form())).await?;
+        let body = res.text().await?;
+        serde_json::from_str(&body).map_err(|e| ChatError::Other(format!("Failed to parse response ({e}): {body}")))
+    }
+
+    /// The request standing in for a call to an endpoint other than chat completions. Only its model,
+    /// timeout, headers, and query parameters apply.
+    fn endpoint_request(model: &str) -> ChatCompletionRequest {
+        ChatCompletionRequestBuilder::default()
+            .model(Model::custom(model))
+            .messages(vec![])
+            .function_call(None)
+            .build()
+            .unwrap()
+    }
+
+    /// Replace the HTTP client, for backends that wrap an `OpenAIClient`.
+    #[cfg(any(feature = "mistral", feature = "openrouter"))]
+    pub(crate) fn set_http_client(&mut self, client: Client) {
+        self.client = client;
+    }
+
+    /// The clock retries and polling wait by.
+    pub(crate) fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    /// The primary base URL and API key, for connections made without reqwest.
+    #[cfg(feature = "realtime")]
+    pub(crate) fn base_url_and_key(&self) -> (&str, Option<&str>) {
+        (self.endpoints.primary(), self.keys.primary())
+    }
+
+    /// A request to `path` (relative to the base URL) with the client's authentication and headers.
+    pub(crate) fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.request_to(self.endpoints.primary(), self.keys.primary(), method, path)
+    }
+
+    fn request_to(&self, base_url: &str, api_key: Option<&str>, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let req = self.client.request(method, format!("{base_url}/{path}"));
+        let req = match api_key {
+            Some(api_key) => req.header("Authorization", format!("Bearer {api_key}")),
+            None => req,
+        };
+        let req = match &self.organization {
+            Some(organization) => req.header("OpenAI-Organization", organization),
+            None => req,
+        };
+        let req = match &self.project {
+            Some(project) => req.header("OpenAI-Project", project),
+            None => req,
+        };
+        self.extra_headers
+            .iter()
+            .fold(req, |req, (name, value)| req.header(name, value))
+            .query(&self.extra_query)
+    }
+
+    /// Send a request built with `request` once, without retries, and parse its JSON response.
+    pub(crate) async fn send_json<T: serde::de::DeserializeOwned>(&self, req: reqwest::RequestBuilder) -> Result<T, ChatError> {
+        let res = req.send().await?;
+        let status = res.status();
+        let body = res.text().await?;
+        if !status.is_success() {
+            return Err(ChatError::from_status(status.as_u16(), body));
+        }
+        serde_json::from_str(&body).map_err(|e| ChatError::Other(format!("Failed to parse response ({e}): {body}")))
+    }
+
+    async fn post_with_retries(
+        &self,
+        path: &str,
+        body: &impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+        req: &ChatCompletionRequest,
+    ) -> Result<reqwest::Response, ChatError> {
+        let start = self.clock.now();
+        let elapsed = || self.clock.now().saturating_duration_since(start);
+        let mut attempt = 0;
+
+        // Same key for every retry of this request, so gateways that support it can deduplicate
+        let idempotency_key = random::uuid_v4(&*self.random).to_string();
+    
+        loop {
+            attempt += 1;
+            if attempt > 1 {
+                self.count(Metric::Retries, req);
+            }
+            trace::Span::record_current("retries", u64::from(attempt - 1));
+            let (endpoint, base_url) = self.endpoints.select(self.clock.now());
+            let key = self.keys.select(self.clock.now());
+            let res = self
+                .request_to(base_url, key.map(|(_, api_key)| api_key), reqwest::Method::POST, path)
+                .header("Idempotency-Key", &idempotency_key)
+                .query(&req.extra_query);
+            let res = req
+                .extra_headers
+                .iter()
+                .fold(res, |res, (name, value)| res.header(name, value));
+            let res = match req.timeout {
+                Some(timeout) => res.timeout(timeout),
+                None => res,
+            };
+            let sent_at = self.clock.now();
+            let res = body(res).send().await;
+
+            // After a failure, move straight on to another endpoint if there's a healthy one
+            let fail_over = |endpoint: usize| {
+                let now = self.clock.now();
+                self.endpoints.record_failure(endpoint, now);
+                self.endpoints.len() > 1 && self.endpoints.select(now).0 != endpoint && attempt < self.retry_policy.max_attempts
+            };
+
+            let res = match res {
+                Ok(res) => res,
+                Err(e) if is_transient(&e) && fail_over(endpoint) => {
+                    diagnostics::emit(Diagnostic::FailingOver { error: e.to_string() });
+                    continue;
+                }
+                Err(e) if is_transient(&e) => match self.retry_policy.delay_with_random(attempt, elapsed(), None, &*self.random) {
+                    Some(wait_time) => {
+                        diagnostics::emit(Diagnostic::Retrying { error: e.to_string(), wait: wait_time });
+                        self.clock.sleep(wait_time).await;
+                        continue;
+                    }
+                    None => return Err(e.into()),
+                },
+                Err(e) => return Err(e.into()),
+            };
+
+            match res.status() {
+                reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                    self.count(Metric::RateLimited, req);
+                    // Rest the limited key until its limit resets and move straight on to another
+                    if let Some((key, _)) = key.filter(|_| self.keys.len() > 1) {
+                        let now = self.clock.now();
+                        let cooldown = retry_after(res.headers()).unwrap_or(DEFAULT_KEY_COOLDOWN);
+                        self.keys.record_throttled(key, now, now + cooldown);
+                        if self.keys.has_alternative(key, now) && attempt < self.retry_policy.max_attempts {
+                            diagnostics::emit(Diagnostic::RotatingKey { cooldown });
+                            continue;
+                        }
+                    }
+                    match self.retry_policy.delay_with_random(attempt, elapsed(), retry_after(res.headers()), &*self.random) {
+                        Some(wait_time) => {
+                            diagnostics::emit(Diagnostic::RateLimited { wait: wait_time });
+                            self.clock.sleep(wait_time).await;
+                        }
+                        None => return Err(ChatError::RateLimited),
+                    }
+                }
+                status if !status.is_success() => {
+                    let body = res.text().await.unwrap_or_default();
+                    let error = ChatError::from_status(status.as_u16(), body);
+                    if error.is_transient() {
+                        if fail_over(endpoint) {
+                            diagnostics::emit(Diagnostic::FailingOver { error: error.to_string() });
+                            continue;
+                        }
+                        if let Some(wait_time) = self.retry_policy.delay_with_random(attempt, elapsed(), None, &*self.random) {
+                            diagnostics::emit(Diagnostic::Retrying { error: error.to_string(), wait: wait_time });
+                            self.clock.sleep(wait_time).await;
+                            continue;
+                        }
+                    } else {
+                        self.endpoints.record_success(endpoint, self.clock.now().saturating_duration_since(sent_at));
+                    }
+                    return Err(error);
+                }
+                _ => {
+                    self.endpoints.record_success(endpoint, self.clock.now().saturating_duration_since(sent_at));
+                    let mut res = res;
+                    if let Some((key, _)) = key {
+                        res.extensions_mut().insert(ServedBy(key));
+                    }
+                    return Ok(res);
+                }
+            }
+        }
+    }
+}
+
+/// Transport failures worth retrying: timeouts and connections that failed or dropped mid-request.
+pub(crate) fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect() || e.is_request()
+}
+
+/// Explicit configuration for an `OpenAIClient`. Anything left unset falls back to its default; the
+/// API key falls back to the `OPENAI_API_KEY` environment variable, unless a custom base URL is set.
+#[derive(Default)]
+pub struct OpenAIClientBuilder {
+    api_key: Option<String>,
+    extra_api_keys: Vec<String>,
+    key_rotation: KeyRotation,
+    base_url: Option<String>,
+    extra_base_urls: Vec<String>,
+    organization: Option<String>,
+    project: Option<String>,
+    default_model: Option<Model>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    retry_policy: Option<RetryPolicy>,
+    random: Option<Arc<dyn RandomSource>>,
+    clock: Option<Arc<dyn Clock>>,
+    headers: Vec<(String, String)>,
+    query: Vec<(String, String)>,
+    http_client: Option<Client>,
+    configure_http_client: Option<Box<dyn FnOnce(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send>>,
+    circuit_breaker: Option<CircuitBreaker>,
+    rate_limiter: Option<RateLimiter>,
+    response_cache: Option<ResponseCache>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    budget: Option<Budget>,
+    max_in_flight: Option<usize>,
+}
+
+impl OpenAIClientBuilder {
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Several API keys (e.g. of different organizations) to spread requests across, picked per
+    /// `key_rotation`. A 429 on one key is retried at once with another. The first one identifies the
+    /// key in usage attribution when the serving key isn't known.
+    pub fn api_keys(mut self, api_keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let mut api_keys = api_keys.into_iter().map(Into::into);
+        self.api_key = api_keys.next();
+        self.extra_api_keys = api_keys.collect();
+        self
+    }
+
+    /// How requests pick among several `api_keys`. Defaults to `KeyRotation::RoundRobin`.
+    pub fn key_rotation(mut self, key_rotation: KeyRotation) -> Self {
+        self.key_rotation = key_rotation;
+        self
+    }
+
+    /// Several base URLs for the same API, e.g. regional endpoints of a gateway. Each request goes to
+    /// the healthy endpoint with the lowest observed latency, failing over to the others on outages.
+    /// The first one identifies the provider in usage attribution.
+    pub fn base_urls(mut self, base_urls: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let mut base_urls = base_urls.into_iter().map(Into::into);
+        self.base_url = base_urls.next();
+        self.extra_base_urls = base_urls.collect();
+        self
+    }
+
+    /// Base URL of the API, without a trailing slash. Defaults to `https://api.openai.com/v1`. Any
+    /// OpenAI-compatible server works, e.g. `http://localhost:11434/v1` for Ollama; with a custom base
+    /// URL the API key becomes optional, and `OPENAI_API_KEY` isn't sent to it.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Sent as the `OpenAI-Organization` header. Defaults to the `OPENAI_ORG_ID` environment variable when talking to OpenAI itself.
+    pub fn organization(mut self, organization: impl Into<String>) -> Self {
+        self.organization = Some(organization.into());
+        self
+    }
+
+    /// Sent as the `OpenAI-Project` header, for project-scoped usage and keys. Defaults to the
+    /// `OPENAI_PROJECT_ID` environment variable when talking to OpenAI itself.
+    pub fn project(mut self, project: impl Into<String>) -> Self {
+        self.project = Some(project.into());
+        self
+    }
+
+    pub fn default_model(mut self, model: Model) -> Self {
+        self.default_model = Some(model);
+        self
+    }
+
+    /// Timeout for each HTTP request, from connecting until the response body has been read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// How rate limits and transient errors are retried. Defaults to `RetryPolicy::default()`.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Draw retry jitter and idempotency keys from `random`, e.g. a `SeededRandom` so simulations and
+    /// replays are reproducible.
+    pub fn random_source(mut self, random: Arc<dyn RandomSource>) -> Self {
+        self.random = Some(random);
+        self
+    }
+
+    /// Time retry backoff and polling by `clock`, e.g. a `SimulatedClock` so tests of retries finish
+    /// instantly. Defaults to `SystemClock`.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Send this header with every request, e.g. a gateway's auth token. May be called repeatedly.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Add this query parameter to every request URL, e.g. Azure's `api-version`. May be called repeatedly.
+    pub fn query_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((name.into(), value.into()));
+        self
+    }
+
+    /// Send requests through this HTTP client, e.g. one configured with a proxy, custom root
+    /// certificates, or a client identity for mTLS. The builder's `timeout` and `user_agent` are not
+    /// applied to it.
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Customize the HTTP client the builder creates, after `timeout` and `user_agent` are applied,
+    /// e.g. `.configure_http_client(|b| b.proxy(proxy))`.
+    pub fn configure_http_client(
+        mut self,
+        configure: impl FnOnce(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send + 'static,
+    ) -> Self {
+        self.configure_http_client = Some(Box::new(configure));
+        self
+    }
+
+    /// Stop sending requests for a while once the endpoint keeps failing. Off by default. Its
+    /// cooldowns are timed by the client's `clock`.
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Wait locally for requests-per-minute and tokens-per-minute budgets before sending. Clones of
+    /// one `RateLimiter` given to several clients share its budget.
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Answer repeated temperature-0 requests from `cache` instead of the API. Cached responses
+    /// report zero usage and are attributed to `cache`.
+    pub fn response_cache(mut self, cache: ResponseCache) -> Self {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    /// Report request counts, retries, 429s, latency, tokens, and cost per model to `sink`, e.g. a
+    /// `PrometheusMetrics`. The same sink can be shared by several clients.
+    pub fn metrics(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.metrics = Some(sink);
+        self
+    }
+
+    /// Stop sending requests once `budget` is spent; they fail with `ChatError::BudgetExceeded`.
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Never have more than this many requests (including their retries) in flight at once, however
+    /// many states are driven concurrently with this client. The rest queue in FIFO order.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    /// Panics if the client can't be configured; see `try_build` for a fallible version.
+    pub fn build(self) -> OpenAIClient {
+        self.try_build().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_build(self) -> Result<OpenAIClient, ConfigError> {
+        // OpenAI's environment variables shouldn't leak into requests to other providers
+        let is_openai = self.base_url.is_none();
+        let openai_env = |name: &str| std::env::var(name).ok().filter(|_| is_openai);
+
+        // Local servers generally don't authenticate, so only OpenAI itself requires a key
+        let api_key = match (self.api_key, openai_env("OPENAI_API_KEY")) {
+            (Some(api_key), _) | (None, Some(api_key)) => Some(api_key),
+            (None, None) if !is_openai => None,
+            (None, None) => return Err(ConfigError::MissingApiKey),
+        };
+
+        let client = match self.http_client {
+            Some(client) => client,
+            None => {
+                let mut client = Client::builder();
+                if let Some(timeout) = self.timeout {
+                    client = client.timeout(timeout);
+                }
+                if let Some(user_agent) = self.user_agent {
+                    client = client.user_agent(user_agent);
+                }
+                if let Some(configure) = self.configure_http_client {
+                    client = configure(client);
+                }
+                client.build().map_err(ConfigError::HttpClient)?
+            }
+        };
+
+        let clock: Arc<dyn Clock> = self.clock.unwrap_or_else(|| Arc::new(SystemClock));
+        Ok(OpenAIClient {
+            client,
+            keys: ApiKeys::new(api_key.into_iter().chain(self.extra_api_keys).collect(), self.key_rotation),
+            endpoints: Endpoints::new(
+                std::iter::once(self.base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string()))
+                    .chain(self.extra_base_urls)
+                    .collect(),
+            ),
+            organization: self.organization.or_else(|| openai_env("OPENAI_ORG_ID")),
+            project: self.project.or_else(|| openai_env("OPENAI_PROJECT_ID")),
+            default_model: self.default_model.unwrap_or(Model::Gpt3p5Turbo),
+            retry_policy: self.retry_policy.unwrap_or_default(),
+            random: self.random.unwrap_or_else(|| Arc::new(ThreadRandom)),
+            circuit_breaker: self.circuit_breaker.map(|circuit_breaker| circuit_breaker.timed_by(clock.clone())),
+            clock,
+            rate_limiter: self.rate_limiter,
+            response_cache: self.response_cache,
+            metrics: self.metrics,
+            budget: self.budget,
+            in_flight: self.max_in_flight.map(Semaphore::new),
+            extra_headers: self.headers,
+            extra_query: self.query,
+            extra_body: serde_json::Map::new(),
+            provider_name: None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// No API key was given to the builder, `OPENAI_API_KEY` is unset, and the client points at OpenAI
+    MissingApiKey,
+    /// The underlying HTTP client couldn't be built (e.g. no TLS backend available)
+    HttpClient(reqwest::Error),
+}
+
+impl ConfigError {
+    /// The stable code for this error; see `error_codes`.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            ConfigError::MissingApiKey => error_codes::E_MISSING_API_KEY,
+            ConfigError::HttpClient(_) => error_codes::E_CONFIG,
+        }
+    }
+
+    /// The description, without the code.
+    pub fn message(&self) -> String {
+        match self {
+            ConfigError::MissingApiKey => "No OpenAI API key configured. Set the OPENAI_API_KEY environment variable, pass a key \
+                 with OpenAIClient::builder().api_key(..), or give the driver your own backend with DriveOptionsBuilder::backend"
+                .to_string(),
+            ConfigError::HttpClient(e) => format!("Failed to build HTTP client: {e}"),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.error_code(), self.message())
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+type GroupLock = Arc<AsyncMutex<()>>;
+
+/// Locks for `#[ai_function(concurrency_group = "...")]` groups, for runs driven concurrently that
+/// share an external resource, e.g. several agents writing to one directory. Give clones of one
+/// `ConcurrencyGroups` to those runs' `DriveOptions`, and no two functions of the same group run at
+/// the same time among them; they wait their turn in the order they were called. Functions without
+/// a group, and runs without a `ConcurrencyGroups`, aren't restricted.
+///
+/// This only serializes: each run still executes one function call per step.
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyGroups {
+    locks: Arc<Mutex<HashMap<&'static str, GroupLock>>>,
+}
+
+impl ConcurrencyGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait until no other function of `group` is running, and hold it until the guard is dropped.
+    pub(crate) async fn enter(&self, group: Option<&'static str>) -> Option<OwnedMutexGuard<()>> {
+        let group = group?;
+        let lock = self.locks.lock().unwrap().entry(group).or_default().clone();
+        Some(lock.lock_owned().await)
+    }
+}
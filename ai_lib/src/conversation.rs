@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use serde::de::Error as _;
+use serde_json::Value;
+
+use crate::elide::{elide, DEFAULT_ECHO_LIMIT};
+use crate::{CalledFunction, Message};
+
+/// A message history, e.g. one exported from the OpenAI playground or another SDK, to seed a run
+/// through `DriveOptions::history`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conversation {
+    pub messages: Vec<Message>,
+}
+
+impl Conversation {
+    /// Import an OpenAI-format message array, or an object with a `messages` array (like a request
+    /// body). Content-part arrays keep their text, images, and files; `tool_calls` and
+    /// `function_call`s become the JSON content the driver keeps calls as, and `tool` and `function`
+    /// results become user messages naming the function.
+    pub fn from_openai_json(value: &Value) -> Result<Self, serde_json::Error> {
+        let messages = match value {
+            Value::Array(messages) => messages,
+            Value::Object(object) => match object.get("messages") {
+                Some(Value::Array(messages)) => messages,
+                _ => return Err(serde_json::Error::custom("expected a `messages` array")),
+            },
+            _ => return Err(serde_json::Error::custom("expected a message array or an object with `messages`")),
+        };
+
+        // Tool results only carry the call's ID
+        let mut tool_names = HashMap::new();
+        let mut imported = vec![];
+        for message in messages {
+            let role = message["role"].as_str().ok_or_else(|| serde_json::Error::custom("message without a `role`"))?;
+            let (content, images, files) = content_parts(&message["content"]);
+            match role {
+                "assistant" => {
+                    let mut calls = vec![];
+                    if let Some(function_call) = message.get("function_call").filter(|call| !call.is_null()) {
+                        calls.push(serde_json::from_value::<CalledFunction>(function_call.clone())?);
+                    }
+                    for tool_call in message["tool_calls"].as_array().into_iter().flatten() {
+                        let call = serde_json::from_value::<CalledFunction>(tool_call["function"].clone())?;
+                        if let Some(id) = tool_call["id"].as_str() {
+                            tool_names.insert(id.to_string(), call.name.clone());
+                        }
+                        calls.push(call);
+                    }
+                    if let Some(content) = content.filter(|content| !content.is_empty()) {
+                        imported.push(Message { role: "assistant".to_string(), ..Message::user(content) });
+                    }
+                    for call in calls {
+                        imported.push(Message { function_call: Some(call), ..Message::user("") }.function_to_content());
+                    }
+                }
+                "tool" | "function" => {
+                    let name = message["name"]
+                        .as_str()
+                        .or_else(|| message["tool_call_id"].as_str().and_then(|id| tool_names.get(id)).map(String::as_str))
+                        .unwrap_or("function");
+                    imported.push(Message::user(format!("{name} returned: {}", content.unwrap_or_default())));
+                }
+                role => imported.push(Message {
+                    role: role.to_string(),
+                    content,
+                    function_call: None,
+                    files,
+                    images,
+                }),
+            }
+        }
+        Ok(Self { messages: imported })
+    }
+}
+
+/// The text, image URLs, and file IDs of a message's `content`, which is a string or an array of parts.
+fn content_parts(content: &Value) -> (Option<String>, Vec<String>, Vec<String>) {
+    let Some(parts) = content.as_array() else {
+        return (content.as_str().map(String::from), vec![], vec![]);
+    };
+    let mut text = vec![];
+    let mut images = vec![];
+    let mut files = vec![];
+    for part in parts {
+        match part["type"].as_str() {
+            Some("text") | Some("input_text") => text.extend(part["text"].as_str()),
+            Some("image_url") => images.extend(part["image_url"]["url"].as_str().or(part["image_url"].as_str()).map(String::from)),
+            Some("file") => files.extend(part["file"]["file_id"].as_str().map(String::from)),
+            _ => {}
+        }
+    }
+    ((!text.is_empty()).then(|| text.join("\n")), images, files)
+}
+
+/// Histories of the named conversations in a run. Prompts without a conversation start from an empty
+/// history (as they always have); prompts in a named conversation continue its history. Entering a
+/// conversation that isn't active nests it under the current one, and returning to an outer
+/// conversation summarizes the nested ones back into it, so focused sub-tasks don't pollute the
+/// parent's context with their back-and-forth.
+#[derive(Default)]
+pub(crate) struct Conversations {
+    histories: HashMap<String, Vec<Message>>,
+    stack: Vec<String>,
+}
+
+impl Conversations {
+    /// Make `name` the active conversation and return the history a prompt in it continues from.
+    pub fn enter(&mut self, name: Option<&str>) -> Vec<Message> {
+        let Some(name) = name else {
+            return vec![];
+        };
+
+        match self.stack.iter().position(|active| active == name) {
+            // Returning to an outer conversation: fold each nested one into its parent
+            Some(position) => {
+                while self.stack.len() > position + 1 {
+                    let finished = self.stack.pop().unwrap();
+                    let parent = self.stack.last().unwrap().clone();
+                    self.summarize_into(&finished, &parent);
+                }
+            }
+            None => self.stack.push(name.to_string()),
+        }
+        self.histories.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Store the history of a completed prompt in a named conversation.
+    pub fn finish(&mut self, name: Option<&str>, messages: Vec<Message>) {
+        if let Some(name) = name {
+            self.histories.insert(name.to_string(), messages);
+        }
+    }
+
+    fn summarize_into(&mut self, finished: &str, parent: &str) {
+        let Some(history) = self.histories.remove(finished) else {
+            return;
+        };
+        let outcome = history
+            .iter()
+            .rev()
+            .find(|message| message.role == "assistant")
+            .and_then(|message| message.content.clone())
+            .unwrap_or_default();
+        let summary = Message::user(format!(
+            "Sub-task \"{finished}\" completed after {} messages. Its final result was: {}",
+            history.len(),
+            elide(&outcome, DEFAULT_ECHO_LIMIT * 5),
+        ));
+        self.histories.entry(parent.to_string()).or_default().push(summary);
+    }
+}
@@ -0,0 +1,150 @@
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::driver::{drive_from, resolve_aliases};
+use crate::{
+    pinned, AiFunctionError, AiFunctionResponse, ChatCompletionRequest, ChatCompletionResponse, DriveError, DriveOptions, DynAiState,
+    Transcript, TranscriptEntry,
+};
+
+/// Steps through a recorded session, one chat completion at a time, and rebuilds the state as it
+/// was at any step by replaying the accepted replies against a fresh state. From there the run can
+/// be re-executed against a live or simulated backend, e.g. to see whether a prompt change fixes a
+/// bad step without paying for the steps before it.
+///
+/// Replay calls functions with the model's recorded arguments, so it's only faithful for functions
+/// that depend on nothing but their state and arguments. Clarification sub-dialogues and argument
+/// normalization aren't replayed.
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    steps: Vec<TranscriptEntry>,
+    position: usize,
+}
+
+/// The state as of a step, before the step's reply was applied.
+pub struct Snapshot<S> {
+    pub state: S,
+    /// The prompt the state had returned, which the step's request was made for
+    pub prompt: AiFunctionResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Recording {
+    Transcript(Vec<TranscriptEntry>),
+    Cassette { interactions: Vec<Interaction> },
+}
+
+#[derive(Deserialize)]
+struct Interaction {
+    request: ChatCompletionRequest,
+    response: ChatCompletionResponse,
+}
+
+impl Debugger {
+    pub fn new(steps: Vec<TranscriptEntry>) -> Self {
+        Self { steps, position: 0 }
+    }
+
+    pub fn from_transcript(transcript: &Transcript) -> Self {
+        Self::new(transcript.entries())
+    }
+
+    /// Load a transcript saved with `Transcript::to_json`, or a `Cassette` file. Cassettes don't
+    /// record which replies the driver retried, so every interaction is treated as accepted.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        let steps = match serde_json::from_str(&json)? {
+            Recording::Transcript(entries) => entries,
+            Recording::Cassette { interactions } => interactions
+                .into_iter()
+                .map(|interaction| TranscriptEntry { request: interaction.request, response: interaction.response, accepted: true })
+                .collect(),
+        };
+        Ok(Self::new(steps))
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn steps(&self) -> &[TranscriptEntry] {
+        &self.steps
+    }
+
+    /// The index of the current step.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The current step, or `None` past the end of the session.
+    pub fn current(&self) -> Option<&TranscriptEntry> {
+        self.steps.get(self.position)
+    }
+
+    /// Move to the next step, returning it.
+    pub fn forward(&mut self) -> Option<&TranscriptEntry> {
+        self.position = (self.position + 1).min(self.steps.len());
+        self.current()
+    }
+
+    /// Move to the previous step, returning it. Stays on the first step.
+    pub fn back(&mut self) -> Option<&TranscriptEntry> {
+        self.position = self.position.saturating_sub(1);
+        self.current()
+    }
+
+    /// Move to step `step`, or just past the last step if it's out of range.
+    pub fn seek(&mut self, step: usize) -> Option<&TranscriptEntry> {
+        self.position = step.min(self.steps.len());
+        self.current()
+    }
+
+    /// Rebuild the state as of `step` by replaying every accepted reply before it against `state`,
+    /// which should be fresh. A reply that the state now rejects ends the replay with
+    /// `DriveError::Unrecoverable`, since the session can't be reconstructed past it.
+    pub fn snapshot<S: DynAiState>(&self, step: usize, mut state: S) -> Result<Snapshot<S>, DriveError> {
+        let mut prompt = state.initial();
+        for (index, entry) in self.steps.iter().enumerate().take(step) {
+            if !entry.accepted {
+                continue;
+            }
+            let AiFunctionResponse::Prompt { pinned, .. } = &prompt else {
+                break;
+            };
+            let Some(choice) = entry.response.choices.first() else {
+                continue;
+            };
+            let result = match &choice.message.function_call {
+                Some(call) => {
+                    let arguments = resolve_aliases(state.function_aliases(&call.name), call.arguments.clone());
+                    let arguments = pinned::pin_arguments(&call.name, &arguments, pinned)
+                        .map_err(|e| DriveError::Unrecoverable(format!("Step {index} didn't replay: {e}")))?;
+                    state.call(&call.name, &arguments)
+                }
+                None => state.text_reply(choice.message.content.as_deref().unwrap_or_default()),
+            };
+            prompt = match result {
+                Ok(next) => next,
+                Err(AiFunctionError::Recoverable(e) | AiFunctionError::Unrecoverable(e)) => {
+                    return Err(DriveError::Unrecoverable(format!("Step {index} didn't replay: {e}")));
+                }
+            };
+        }
+        Ok(Snapshot { state, prompt })
+    }
+
+    /// Rebuild the state as of `step` and drive it on from there with `options`, whose backend
+    /// answers every step from then on. Returns the state once the run finishes.
+    pub async fn resume<S: DynAiState>(&self, step: usize, state: S, options: &DriveOptions) -> Result<S, DriveError> {
+        let Snapshot { mut state, prompt } = self.snapshot(step, state)?;
+        drive_from(&mut state, prompt, options).await?;
+        Ok(state)
+    }
+}
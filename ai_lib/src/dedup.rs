@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+
+use crate::canonical::sort_keys;
+
+/// What the driver does when the model repeats a call it just made with identical arguments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateCallPolicy {
+    /// Skip the call and tell the model to change course, costing one retry
+    #[default]
+    Correct,
+    /// Stop driving with an error
+    Fail,
+}
+
+/// The last few calls dispatched by the driver, with arguments canonicalized so that key order and
+/// formatting differences don't hide a repeat.
+pub(crate) struct RecentCalls {
+    window: usize,
+    calls: VecDeque<(String, String)>,
+}
+
+impl RecentCalls {
+    pub fn new(window: usize) -> Self {
+        Self { window, calls: VecDeque::with_capacity(window) }
+    }
+
+    pub fn is_duplicate(&self, name: &str, arguments: &str) -> bool {
+        let arguments = canonicalize(arguments);
+        self.calls.iter().any(|(n, a)| n == name && *a == arguments)
+    }
+
+    pub fn record(&mut self, name: &str, arguments: &str) {
+        if self.window == 0 {
+            return;
+        }
+        if self.calls.len() == self.window {
+            self.calls.pop_front();
+        }
+        self.calls.push_back((name.to_string(), canonicalize(arguments)));
+    }
+}
+
+fn canonicalize(arguments: &str) -> String {
+    match serde_json::from_str(arguments) {
+        Ok(value) => sort_keys(value).to_string(),
+        Err(_) => arguments.to_string(),
+    }
+}
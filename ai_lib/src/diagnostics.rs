@@ -0,0 +1,94 @@
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+
+use crate::Observer;
+
+/// Something the library worked around on its own: retries, failovers, and corrections. Nothing is
+/// printed by default; diagnostics go to `Observer::diagnostic` on the run's observers and on any
+/// registered with `add_diagnostics_observer`. Register `StderrDiagnostics` to see them on stderr.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Diagnostic {
+    /// A request failed transiently and will be retried after `wait`
+    Retrying { error: String, wait: Duration },
+    /// A request failed transiently and is being retried on another endpoint
+    FailingOver { error: String },
+    /// The provider returned 429; the request will be retried after `wait`
+    RateLimited { wait: Duration },
+    /// The provider returned 429 for one of several API keys; the request is retried at once with
+    /// another, and the limited key is skipped for `cooldown`
+    RotatingKey { cooldown: Duration },
+    /// An endpoint kept failing and is out of rotation for `cooldown`
+    EndpointDown { base_url: String, cooldown: Duration },
+    /// A circuit breaker opened after `failures` consecutive outages
+    CircuitOpened { failures: u32, cooldown: Duration },
+    /// A fallback chain moved on from `model` to `next` after `error`
+    FallingBack { model: String, next: String, error: String },
+    /// The model called an unknown function, taken to mean the closest offered one
+    FunctionNameCorrected { called: String, corrected: String },
+    /// Responses within a run came from different backend configurations, so a seeded run may not
+    /// reproduce
+    FingerprintChanged { from: String, to: String },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::Retrying { error, wait } => write!(f, "Transient error ({error}), retrying in {wait:?}"),
+            Diagnostic::FailingOver { error } => write!(f, "Transient error ({error}), failing over"),
+            Diagnostic::RateLimited { wait } => write!(f, "Too many requests, retrying in {wait:?}"),
+            Diagnostic::RotatingKey { cooldown } => {
+                write!(f, "Too many requests, retrying with another API key and resting this one for {cooldown:?}")
+            }
+            Diagnostic::EndpointDown { base_url, cooldown } => {
+                write!(f, "Endpoint {base_url} is failing, taking it out of rotation for {cooldown:?}")
+            }
+            Diagnostic::CircuitOpened { failures, cooldown } => {
+                write!(f, "Circuit open after {failures} consecutive failures, pausing requests for {cooldown:?}")
+            }
+            Diagnostic::FallingBack { model, next, error } => write!(f, "{model} failed ({error}), falling back to {next}"),
+            Diagnostic::FunctionNameCorrected { called, corrected } => {
+                write!(f, "Corrected call to unknown function {called} to {corrected}")
+            }
+            Diagnostic::FingerprintChanged { from, to } => write!(f, "System fingerprint changed from {from} to {to}"),
+        }
+    }
+}
+
+/// Prints every diagnostic to stderr, as the library did before diagnostics were quiet by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StderrDiagnostics;
+
+impl Observer for StderrDiagnostics {
+    fn diagnostic(&self, diagnostic: &Diagnostic) {
+        eprintln!("{diagnostic}");
+    }
+}
+
+fn global() -> &'static RwLock<Vec<Arc<dyn Observer>>> {
+    static OBSERVERS: OnceLock<RwLock<Vec<Arc<dyn Observer>>>> = OnceLock::new();
+    OBSERVERS.get_or_init(|| RwLock::new(vec![]))
+}
+
+/// Send diagnostics from every client and run in the process to `observer`, including those from
+/// layers below the driver (retries, failover, circuit breakers) that have no run to report to.
+pub fn add_diagnostics_observer(observer: Arc<dyn Observer>) {
+    global().write().unwrap().push(observer);
+}
+
+/// Go back to the quiet default.
+pub fn clear_diagnostics_observers() {
+    global().write().unwrap().clear();
+}
+
+/// Report to the process-wide diagnostics observers.
+pub(crate) fn emit(diagnostic: Diagnostic) {
+    emit_to(&[], diagnostic);
+}
+
+/// Report to `observers` as well as the process-wide diagnostics observers.
+pub(crate) fn emit_to(observers: &[Arc<dyn Observer>], diagnostic: Diagnostic) {
+    for observer in observers.iter().chain(global().read().unwrap().iter()) {
+        observer.diagnostic(&diagnostic);
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::sync::Arc;
+
+use serde::Serialize;
+use similar::{DiffTag, TextDiff};
+
+use crate::Observer;
+
+/// A single word-level change between two versions of a text.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Change {
+    Inserted(String),
+    Deleted(String),
+    Replaced { old: String, new: String },
+}
+
+/// The changes made in one round of an iterative edit.
+#[derive(Debug, Clone, Serialize)]
+pub struct EditDiff {
+    /// 1-based edit round, i.e. the diff between version `round - 1` and version `round`
+    pub round: usize,
+    pub changes: Vec<Change>,
+    /// Word-level similarity between the two versions, from 0.0 (nothing in common) to 1.0 (identical)
+    pub similarity: f32,
+}
+
+impl EditDiff {
+    pub fn between(round: usize, old: &str, new: &str) -> Self {
+        let diff = TextDiff::from_words(old, new);
+        let (old_words, new_words) = (diff.old_slices(), diff.new_slices());
+        let changes = diff
+            .ops()
+            .iter()
+            .filter_map(|op| {
+                let (tag, old_range, new_range) = op.as_tag_tuple();
+                let old = old_words[old_range].concat().trim().to_string();
+                let new = new_words[new_range].concat().trim().to_string();
+                match tag {
+                    DiffTag::Equal => None,
+                    // Whitespace-only changes aren't worth telling the model about
+                    _ if old.is_empty() && new.is_empty() => None,
+                    DiffTag::Insert => Some(Change::Inserted(new)),
+                    DiffTag::Delete => Some(Change::Deleted(old)),
+                    DiffTag::Replace => Some(Change::Replaced { old, new }),
+                }
+            })
+            .collect();
+        Self { round, changes, similarity: diff.ratio() }
+    }
+
+    /// A compact, prompt-ready description of the first `max_changes` changes.
+    pub fn summary(&self, max_changes: usize) -> String {
+        if self.changes.is_empty() {
+            return "you made no changes".to_string();
+        }
+        let mut parts: Vec<_> = self
+            .changes
+            .iter()
+            .take(max_changes)
+            .map(|change| match change {
+                Change::Inserted(new) => format!("added \"{new}\""),
+                Change::Deleted(old) => format!("removed \"{old}\""),
+                Change::Replaced { old, new } => format!("changed \"{old}\" to \"{new}\""),
+            })
+            .collect();
+        if self.changes.len() > max_changes {
+            parts.push(format!("and {} more changes", self.changes.len() - max_changes));
+        }
+        format!("you {}", parts.join(", "))
+    }
+}
+
+/// Tracks successive versions of a text being edited over several rounds (like a premise being
+/// rewritten), diffing each new version against the previous one.
+#[derive(Default)]
+pub struct EditHistory {
+    versions: Vec<String>,
+    diffs: Vec<EditDiff>,
+    observers: Vec<Arc<dyn Observer>>,
+}
+
+impl fmt::Debug for EditHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EditHistory")
+            .field("versions", &self.versions)
+            .field("diffs", &self.diffs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EditHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Notify `observer` of every diff as it is computed, e.g. to show edits in a UI.
+    pub fn with_observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Record a new version, returning its diff against the previous version (if there was one).
+    pub fn push(&mut self, version: impl Into<String>) -> Option<&EditDiff> {
+        let version = version.into();
+        let Some(previous) = self.versions.last() else {
+            self.versions.push(version);
+            return None;
+        };
+
+        let diff = EditDiff::between(self.versions.len(), previous, &version);
+        for observer in &self.observers {
+            observer.edit_diff(&diff);
+        }
+        self.diffs.push(diff);
+        self.versions.push(version);
+        self.diffs.last()
+    }
+
+    pub fn versions(&self) -> &[String] {
+        &self.versions
+    }
+
+    pub fn diffs(&self) -> &[EditDiff] {
+        &self.diffs
+    }
+
+    /// Whether the last edit round left the text at least `threshold` similar to the version before it,
+    /// i.e. further rounds are unlikely to be worth their cost.
+    pub fn converged(&self, threshold: f32) -> bool {
+        self.diffs.last().is_some_and(|diff| diff.similarity >= threshold)
+    }
+
+    /// A "you previously changed X, Y" note for the next edit prompt, or `None` before the first edit.
+    pub fn change_summary(&self, max_changes: usize) -> Option<String> {
+        self.diffs
+            .last()
+            .map(|diff| format!("In your previous edit, {}.", diff.summary(max_changes)))
+    }
+}
@@ -0,0 +1,649 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use derive_builder::Builder;
+
+use crate::{
+    backend::ErrorBody, clarify, conversation, dedup, diagnostics, error_codes, language, metrics, trace, slo, pinned, validate, closest_match, DuplicateCallPolicy, DEFAULT_ECHO_LIMIT, global_pricing, AiFunctionError, AiFunctionResponse, DynAiState,
+    ApprovalPolicy, ArgumentsDelta, CalledFunction, ChatBackend, ChatError, ChoiceSelector, Clock, ConcurrencyGroups, ConfigError, Conversation, Diagnostic, Observer, SessionStats, SessionUsage, StepTiming, SloPolicy, SloViolation, StringNormalization, Transcript, ChatCompletionRequestBuilder, Function, FunctionCall, Message, OpenAIClient, PinnedArgument, Profile, PromptOptions, SystemClock,
+};
+
+/// Called by the driver with each streamed fragment of function call arguments.
+pub type ArgumentsDeltaCallback = Arc<dyn Fn(&ArgumentsDelta<'_>) + Send + Sync>;
+
+/// Knobs for the driver loop. `DriveOptions::default()` gives the behavior of plain `drive`.
+#[derive(Clone, Default, Builder)]
+#[builder(setter(into), default)]
+pub struct DriveOptions {
+    /// Validate the raw arguments against the function's JSON schema before deserializing them, so
+    /// errors fed back to the model reference schema paths rather than serde internals
+    pub validate_arguments: bool,
+    /// If set, a call to an unknown function whose name is at least this similar (0.0 to 1.0, by
+    /// normalized Levenshtein distance) to one of the offered functions is redirected to it instead
+    /// of costing a retry
+    #[builder(setter(strip_option))]
+    pub correct_function_names: Option<f64>,
+    /// If set, once a call has failed schema validation this many times the model is switched into a
+    /// clarification sub-dialogue that only asks for the failing fields (via `provide_missing_fields`)
+    /// instead of the whole payload. Implies schema validation.
+    #[builder(setter(strip_option))]
+    pub clarify_after: Option<usize>,
+    /// Longest value (in chars) echoed back verbatim in validation errors; longer ones are elided to
+    /// their head and tail. Defaults to `DEFAULT_ECHO_LIMIT`.
+    #[builder(setter(strip_option))]
+    pub echo_limit: Option<usize>,
+    /// If set, a call identical (same function, same arguments) to one of the last this-many calls
+    /// isn't executed again; `duplicate_call_policy` decides what happens instead
+    #[builder(setter(strip_option))]
+    pub dedup_window: Option<usize>,
+    pub duplicate_call_policy: DuplicateCallPolicy,
+    /// Where chat completions are sent. Defaults to an `OpenAIClient` configured from the environment.
+    #[builder(setter(custom))]
+    pub backend: Option<Arc<dyn ChatBackend>>,
+    /// Every response's token usage is recorded here, attributed to the provider and key that served it
+    #[builder(setter(strip_option))]
+    pub usage: Option<SessionUsage>,
+    /// Every step's model latency and function execution time is recorded here
+    #[builder(setter(strip_option))]
+    pub stats: Option<SessionStats>,
+    /// Every request and response is recorded here, e.g. to attach a `redacted` copy to a bug report
+    #[builder(setter(strip_option))]
+    pub transcript: Option<Transcript>,
+    /// If set (an ISO 639-1 code such as `"de"`), string arguments detected to be in another language
+    /// are rejected and the model is asked to rewrite them in this one
+    #[builder(setter(into, strip_option))]
+    pub language: Option<String>,
+    /// Applied to every string argument before validation and dispatch
+    pub normalization: StringNormalization,
+    /// Timeout for each HTTP attempt at a chat completion, overriding the backend's
+    #[builder(setter(strip_option))]
+    pub request_timeout: Option<Duration>,
+    /// The whole run fails with `DriveError::Timeout` if it hasn't finished within this long
+    #[builder(setter(strip_option))]
+    pub deadline: Option<Duration>,
+    /// What the deadline and step latencies are measured with. Defaults to the `SystemClock`.
+    #[builder(setter(custom))]
+    pub clock: Option<Arc<dyn Clock>>,
+    /// Latency and cost objectives, checked after every chat completion
+    #[builder(setter(strip_option))]
+    pub slo: Option<SloPolicy>,
+    /// If set above 1, this many choices are sampled at every step and `choice_selector` picks the
+    /// one acted on
+    #[builder(setter(strip_option))]
+    pub choices: Option<u32>,
+    pub choice_selector: ChoiceSelector,
+    /// Sent as every request's `seed`, for (approximately) reproducible runs
+    #[builder(setter(strip_option))]
+    pub seed: Option<i64>,
+    /// Messages the first prompt continues from, e.g. a transcript imported with
+    /// `Conversation::from_openai_json`
+    #[builder(setter(strip_option))]
+    pub history: Option<Conversation>,
+    /// Which function calls run without asking and which wait for a human's approval. Calls aren't
+    /// gated if unset.
+    #[builder(setter(strip_option))]
+    pub approval: Option<ApprovalPolicy>,
+    /// If set, completions are streamed and this is called with each fragment of function call
+    /// arguments as it arrives, e.g. to render partial output before the call is complete
+    #[builder(setter(custom))]
+    pub on_arguments_delta: Option<ArgumentsDeltaCallback>,
+    /// The tenant configuration the run is confined to. Its system prompt, function scope, model,
+    /// and budget apply, and its guardrails (approval, language, SLOs) replace those set here.
+    #[builder(setter(strip_option))]
+    pub profile: Option<Profile>,
+    /// Shared with other runs to keep functions of the same concurrency group from running at once
+    #[builder(setter(strip_option))]
+    pub concurrency_groups: Option<ConcurrencyGroups>,
+    /// Notified of run events, such as the model giving up
+    #[builder(setter(custom))]
+    pub observers: Vec<Arc<dyn Observer>>,
+}
+
+impl DriveOptionsBuilder {
+    pub fn observer(&mut self, observer: Arc<dyn Observer>) -> &mut Self {
+        self.observers.get_or_insert_with(Vec::new).push(observer);
+        self
+    }
+
+    pub fn on_arguments_delta(&mut self, on_delta: impl Fn(&ArgumentsDelta<'_>) + Send + Sync + 'static) -> &mut Self {
+        self.on_arguments_delta = Some(Some(Arc::new(on_delta)));
+        self
+    }
+
+    pub fn backend(&mut self, backend: impl ChatBackend + 'static) -> &mut Self {
+        self.backend = Some(Some(Arc::new(backend)));
+        self
+    }
+
+    pub fn clock(&mut self, clock: impl Clock + 'static) -> &mut Self {
+        self.clock = Some(Some(Arc::new(clock)));
+        self
+    }
+}
+
+/// The schemas offered for a prompt listing `functions`: its own plus the state's escape hatches,
+/// heaviest first, with pinned arguments removed. A prompt listing a function twice is an error,
+/// since providers reject duplicate declarations.
+pub(crate) fn offered_functions<S: DynAiState + ?Sized>(
+    state: &S,
+    mut functions: Vec<String>,
+    pinned: &[PinnedArgument],
+) -> Result<Vec<Function>, String> {
+    if let Some((_, duplicate)) = functions.iter().enumerate().find(|(i, f)| functions[..*i].contains(f)) {
+        return Err(format!("The prompt lists function {duplicate} more than once"));
+    }
+    // An escape hatch the prompt lists explicitly is offered once
+    for escape_hatch in state.escape_hatch_functions() {
+        if !functions.iter().any(|f| f == escape_hatch) {
+            functions.push(escape_hatch.to_string());
+        }
+    }
+    functions.sort_by_key(|f| std::cmp::Reverse(state.function_weight_of(f)));
+
+    Ok(functions
+        .into_iter()
+        .map(|f| {
+            let mut function = state.function_schema(&f).unwrap();
+            pinned::unpin_schema(&mut function, pinned);
+            function
+        })
+        .collect())
+}
+
+/// The model calling a function with this name ends the run with `DriveError::ModelGaveUp`, after
+/// the function itself has run. Mark it `#[ai_function(escape_hatch)]` to offer it at every step.
+pub const GIVE_UP_FUNCTION: &str = "give_up";
+
+/// Why a run ended without finishing.
+#[derive(Debug)]
+pub enum DriveError {
+    /// No backend was given and the default OpenAI client couldn't be configured
+    Config(ConfigError),
+    Chat(ChatError),
+    /// A function returned `AiFunctionError::Unrecoverable`
+    Unrecoverable(String),
+    /// The model repeated a call under `DuplicateCallPolicy::Fail`
+    RepeatedCall { function: String },
+    /// Every attempt at a prompt failed
+    TooManyErrors,
+    /// The run's `deadline` passed
+    Timeout,
+    /// The model called `give_up`, declaring the task impossible
+    ModelGaveUp {
+        reason: String,
+        /// The messages of the prompt it gave up on
+        transcript: Vec<Message>,
+    },
+    /// An `SloPolicy` with `abort` set was violated
+    SloViolated(SloViolation),
+    /// The finished state's output couldn't be serialized
+    Output(serde_json::Error),
+}
+
+impl DriveError {
+    /// The stable code for this error; see `error_codes`.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            DriveError::Config(e) => e.error_code(),
+            DriveError::Chat(e) => e.error_code(),
+            DriveError::Unrecoverable(_) => error_codes::E_UNRECOVERABLE,
+            DriveError::RepeatedCall { .. } => error_codes::E_REPEATED_CALL,
+            DriveError::TooManyErrors => error_codes::E_TOO_MANY_ERRORS,
+            DriveError::Timeout => error_codes::E_DEADLINE,
+            DriveError::ModelGaveUp { .. } => error_codes::E_GAVE_UP,
+            DriveError::SloViolated(SloViolation::SessionCost { .. }) => error_codes::E_BUDGET,
+            DriveError::SloViolated(SloViolation::StepLatency { .. }) => error_codes::E_SLO_LATENCY,
+            DriveError::Output(_) => error_codes::E_OUTPUT,
+        }
+    }
+
+    /// The description, without the code.
+    pub fn message(&self) -> String {
+        match self {
+            DriveError::Config(e) => e.message(),
+            DriveError::Chat(e) => e.message(),
+            DriveError::Unrecoverable(e) => e.clone(),
+            DriveError::RepeatedCall { function } => format!("Model repeated an identical call to {function}"),
+            DriveError::TooManyErrors => "Too many errors".to_string(),
+            DriveError::Timeout => "Run exceeded its deadline".to_string(),
+            DriveError::ModelGaveUp { reason, .. } => format!("Model gave up: {reason}"),
+            DriveError::SloViolated(violation) => format!("SLO violated: {violation}"),
+            DriveError::Output(e) => format!("Failed to serialize output: {e}"),
+        }
+    }
+}
+
+impl fmt::Display for DriveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.error_code(), self.message())
+    }
+}
+
+/// `{"code": ..., "message": ...}`, plus the provider's `api_error` when there is one.
+impl serde::Serialize for DriveError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let api_error = match self {
+            DriveError::Chat(e) => e.api_error(),
+            _ => None,
+        };
+        ErrorBody { code: self.error_code(), message: self.message(), api_error }.serialize(serializer)
+    }
+}
+
+impl std::error::Error for DriveError {}
+
+impl From<ChatError> for DriveError {
+    fn from(e: ChatError) -> Self {
+        DriveError::Chat(e)
+    }
+}
+
+impl From<ConfigError> for DriveError {
+    fn from(e: ConfigError) -> Self {
+        DriveError::Config(e)
+    }
+}
+
+pub async fn drive<S: DynAiState + ?Sized>(state: &mut S) -> Result<(), DriveError> {
+    drive_with(state, &DriveOptions::default()).await
+}
+
+/// Run with the settings of `profile`, e.g. one tenant's of a multi-tenant host.
+pub async fn drive_with_profile<S: DynAiState + ?Sized>(state: &mut S, profile: &Profile) -> Result<(), DriveError> {
+    drive_with(state, &DriveOptions { profile: Some(profile.clone()), ..Default::default() }).await
+}
+
+pub async fn drive_with<S: DynAiState + ?Sized>(state: &mut S, options: &DriveOptions) -> Result<(), DriveError> {
+    let prompt = state.initial();
+    drive_from(state, prompt, options).await
+}
+
+/// Like `drive_with`, starting from `prompt` rather than the state's initial prompt.
+pub(crate) async fn drive_from<S: DynAiState + ?Sized>(state: &mut S, prompt: AiFunctionResponse, options: &DriveOptions) -> Result<(), DriveError> {
+    let span = trace::Span::drive(state.state_name());
+    span.instrument(drive_in_span(state, prompt, options, &span)).await
+}
+
+async fn drive_in_span<S: DynAiState + ?Sized>(
+    state: &mut S,
+    mut next_prompt: AiFunctionResponse,
+    options: &DriveOptions,
+    span: &trace::Span,
+) -> Result<(), DriveError> {
+    let echo_limit = options.echo_limit.unwrap_or(DEFAULT_ECHO_LIMIT);
+    let mut recent_calls = dedup::RecentCalls::new(options.dedup_window.unwrap_or(0));
+    let mut conversations = conversation::Conversations::default();
+    let mut history = options.history.clone();
+    let profile = options.profile.as_ref();
+    let approval = profile.map_or(options.approval.as_ref(), |profile| profile.approval.as_ref());
+    let language = profile.map_or(options.language.as_ref(), |profile| profile.language.as_ref());
+    let slo_policy = profile.map_or(options.slo.as_ref(), |profile| profile.slo.as_ref());
+    let clock = options.clock.clone().unwrap_or_else(|| Arc::new(SystemClock));
+    let deadline = options.deadline.map(|deadline| clock.now() + deadline);
+    let mut slo_tracker = slo::SloTracker::default();
+    let mut run_cost = 0.0;
+    let mut fingerprint: Option<String> = None;
+    let usage_run = options.usage.as_ref().map(SessionUsage::start_run);
+
+    let backend = match &options.backend {
+        Some(backend) => backend.clone(),
+        None => Arc::new(OpenAIClient::try_new()?),
+    };
+
+    let mut iteration = 0;
+    'next: loop {
+        match next_prompt {
+            AiFunctionResponse::Done => return Ok(()),
+            AiFunctionResponse::Prompt { temperature, prompt, functions, pinned, options: prompt_options } => {
+                let prompt_options = *prompt_options;
+                let conversation = prompt_options.conversation;
+                let PromptOptions { top_p, presence_penalty, frequency_penalty, stop, logit_bias, .. } = prompt_options;
+                let conversation = conversation.as_deref();
+
+                let mut messages = conversations.enter(conversation);
+                if let Some(history) = history.take() {
+                    messages.splice(0..0, history.messages);
+                }
+                if let Some(system_prompt) = profile.and_then(|profile| profile.system_prompt.as_ref()) {
+                    if !messages.iter().any(|message| message.role == "system") {
+                        messages.insert(0, Message::system(system_prompt));
+                    }
+                }
+                messages.push(
+                    Message::user(prompt)
+                        .with_files(prompt_options.files.unwrap_or_default())
+                        .with_images(prompt_options.images.unwrap_or_default()),
+                );
+
+                let mut functions = offered_functions(state, functions, &pinned).map_err(DriveError::Unrecoverable)?;
+                if let Some(profile) = profile {
+                    functions = profile.scope(functions);
+                    if functions.is_empty() {
+                        return Err(DriveError::Unrecoverable(format!(
+                            "profile {} allows none of the functions this prompt offers",
+                            profile.name
+                        )));
+                    }
+                }
+
+                iteration += 1;
+                let names: Vec<_> = functions.iter().map(|f| f.name.clone()).collect();
+                let prompt_span = span.prompt(iteration, &names);
+
+                let function_call = match prompt_options.function_call {
+                    Some(function_call) => function_call,
+                    None if functions.len() == 1 => FunctionCall::Exact { name: functions[0].name.clone() },
+                    None => FunctionCall::Auto,
+                };
+
+                let mut validation_failures = 0;
+                let mut clarifying: Option<clarify::Clarification> = None;
+
+                for attempt in 1..=5 {
+                    prompt_span.record("attempts", attempt);
+                    let (request_functions, request_function_call) = match &clarifying {
+                        Some(clarification) => (
+                            vec![clarification.function.clone()],
+                            FunctionCall::Exact { name: clarify::CLARIFY_FUNCTION.to_string() },
+                        ),
+                        None => (functions.clone(), function_call.clone()),
+                    };
+
+                    let request = ChatCompletionRequestBuilder::default()
+                        .model(profile.and_then(|profile| profile.model.clone()).unwrap_or_else(|| backend.default_model()))
+                        .messages(messages.clone())
+                        .functions(request_functions)
+                        .function_call(request_function_call)
+                        .temperature(temperature)
+                        .top_p(top_p)
+                        .presence_penalty(presence_penalty)
+                        .frequency_penalty(frequency_penalty)
+                        .stop(stop.clone())
+                        .logit_bias(logit_bias.clone())
+                        .n(options.choices.filter(|&n| n > 1))
+                        .seed(options.seed)
+                        .timeout(options.request_timeout)
+                        .build()
+                        .unwrap();
+
+                    if let Some(budget) = profile.and_then(|profile| profile.budget.as_ref()) {
+                        budget.check()?;
+                    }
+                    let step_start = clock.now();
+                    let chat = async {
+                        match &options.on_arguments_delta {
+                            Some(on_delta) => backend.chat_streaming(&request, &mut |delta| on_delta(delta)).await,
+                            None => backend.chat(&request).await,
+                        }
+                    };
+                    let response = match deadline {
+                        Some(deadline) => {
+                            let remaining = deadline.saturating_duration_since(clock.now());
+                            let chat = async {
+                                tokio::select! {
+                                    biased;
+                                    response = chat => Ok(response),
+                                    _ = clock.sleep(remaining) => Err(DriveError::Timeout),
+                                }
+                            };
+                            prompt_span.instrument(chat).await??
+                        }
+                        None => prompt_span.instrument(chat).await?,
+                    };
+                    let model_latency = clock.now().saturating_duration_since(step_start);
+                    if let (Some(usage), Some(run)) = (&options.usage, usage_run) {
+                        usage.record_step(run, &response);
+                    }
+                    if let Some(budget) = profile.and_then(|profile| profile.budget.as_ref()) {
+                        budget.record(&response.model, &response.usage);
+                    }
+                    if let Some(current) = &response.system_fingerprint {
+                        match fingerprint.replace(current.clone()) {
+                            Some(previous) if previous != *current => {
+                                diagnostics::emit_to(&options.observers, Diagnostic::FingerprintChanged { from: previous, to: current.clone() });
+                            }
+                            _ => {}
+                        }
+                    }
+                    run_cost += global_pricing().cost(&response.model, &response.usage).unwrap_or(0.0);
+                    if let Some(transcript) = &options.transcript {
+                        transcript.record(&request, &response);
+                    }
+                    if let Some(slo) = slo_policy {
+                        for violation in slo_tracker.record(slo, model_latency, &response) {
+                            for observer in &options.observers {
+                                observer.slo_violated(&violation);
+                            }
+                            if slo.abort {
+                                return Err(DriveError::SloViolated(violation));
+                            }
+                        }
+                    }
+                    let selected = options.choice_selector.select(&response.choices, request.functions.as_deref().unwrap_or_default());
+                    let Some(choice) = response.choices.get(selected) else {
+                        return Err(ChatError::Other("The response had no choices".to_string()).into());
+                    };
+                    let message = choice.message.clone();
+                    match &message.function_call {
+                        Some(_) => messages.push(message.clone().function_to_content()),
+                        None => messages.push(message.clone()),
+                    }
+                    match message.function_call {
+                        None => {
+                            let (result, execution, external_calls) =
+                                metrics::timed(|| state.text_reply(message.content.as_deref().unwrap_or_default()));
+                            let succeeded = result.is_ok();
+                            report_step(options, StepTiming { function: None, model_latency, execution, external_calls, succeeded });
+                            match result {
+                                Ok(next) => {
+                                    if let Some(transcript) = &options.transcript {
+                                        transcript.accept_last();
+                                    }
+                                    conversations.finish(conversation, messages);
+                                    next_prompt = next;
+                                    continue 'next;
+                                }
+                                Err(AiFunctionError::Recoverable(e)) => {
+                                    messages.push(Message::user(format!("Error: {}", e)));
+                                }
+                                Err(AiFunctionError::Unrecoverable(e)) => {
+                                    return Err(DriveError::Unrecoverable(e));
+                                }
+                            }
+                        },
+                        Some(CalledFunction { mut name, mut arguments }) => {
+                            if let Some(clarification) = clarifying.take() {
+                                if name == clarify::CLARIFY_FUNCTION {
+                                    let fields = serde_json::from_str(&arguments)
+                                        .and_then(|fields| pinned::merge_arguments(&clarification.arguments, fields));
+                                    match fields {
+                                        Ok(merged) => {
+                                            name = clarification.function_name;
+                                            arguments = merged;
+                                        }
+                                        Err(e) => {
+                                            messages.push(Message::user(format!("Error: {}", e)));
+                                            clarifying = Some(clarification);
+                                            continue;
+                                        }
+                                    }
+                                }
+                            }
+                            if let Some(threshold) = options.correct_function_names {
+                                if !functions.iter().any(|f| f.name == name) {
+                                    if let Some(closest) = closest_match(&name, functions.iter().map(|f| f.name.as_str()), threshold) {
+                                        diagnostics::emit_to(
+                                            &options.observers,
+                                            Diagnostic::FunctionNameCorrected { called: name.clone(), corrected: closest.to_string() },
+                                        );
+                                        name = closest.to_string();
+                                    }
+                                }
+                            }
+                            arguments = resolve_aliases(state.function_aliases(&name), arguments);
+                            arguments = match pinned::pin_arguments(&name, &arguments, &pinned) {
+                                Ok(arguments) => arguments,
+                                Err(e) => {
+                                    messages.push(Message::user(format!("Error: {}", e)));
+                                    continue;
+                                }
+                            };
+                            if !options.normalization.is_noop() {
+                                if let Ok(value) = serde_json::from_str(&arguments) {
+                                    arguments = options.normalization.apply_value(value).to_string();
+                                }
+                            }
+                            if recent_calls.is_duplicate(&name, &arguments) {
+                                match options.duplicate_call_policy {
+                                    DuplicateCallPolicy::Correct => {
+                                        messages.push(Message::user(format!(
+                                            "You already called {name} with exactly these arguments. Don't repeat the same call; change the arguments or call a different function.",
+                                        )));
+                                        continue;
+                                    }
+                                    DuplicateCallPolicy::Fail => return Err(DriveError::RepeatedCall { function: name }),
+                                }
+                            }
+                            recent_calls.record(&name, &arguments);
+
+                            let validation = match functions.iter().find(|f| f.name == name) {
+                                Some(function) if options.clarify_after.is_some() => {
+                                    match validate::schema_errors(&function.parameters, &arguments, echo_limit) {
+                                        Ok(errors) if errors.is_empty() => Ok(()),
+                                        Ok(errors) => {
+                                            validation_failures += 1;
+                                            let mut error = validate::errors_to_recoverable(&errors);
+                                            if Some(validation_failures) >= options.clarify_after {
+                                                let mut fields: Vec<_> = errors.into_iter().filter_map(|e| e.field).collect();
+                                                fields.dedup();
+                                                if !fields.is_empty() {
+                                                    let clarification = clarify::Clarification::new(function, arguments.clone(), &fields);
+                                                    if let AiFunctionError::Recoverable(message) = &mut error {
+                                                        message.push_str(&format!("\n{}", clarification.prompt(&fields)));
+                                                    }
+                                                    clarifying = Some(clarification);
+                                                }
+                                            }
+                                            Err(error)
+                                        }
+                                        Err(e) => Err(e),
+                                    }
+                                }
+                                Some(function) if options.validate_arguments => {
+                                    validate::validate_arguments_with_limit(&function.parameters, &arguments, echo_limit)
+                                }
+                                _ => Ok(()),
+                            };
+                            let validation = validation.and_then(|_| match language {
+                                Some(language) => check_language(language, &arguments),
+                                None => Ok(()),
+                            });
+                            let validation = match (validation, approval) {
+                                (Ok(()), Some(approval)) => approval.check(&name, &arguments, run_cost).await,
+                                (validation, _) => validation,
+                            };
+                            let result = match validation {
+                                Ok(()) => {
+                                    let function_span = prompt_span.call_function(&name);
+                                    let _group = match &options.concurrency_groups {
+                                        Some(groups) => groups.enter(state.function_concurrency_group(&name)).await,
+                                        None => None,
+                                    };
+                                    let (result, execution, external_calls) =
+                                        function_span.in_scope(|| metrics::timed(|| state.call(&name, &arguments)));
+                                    let function = Some(name.clone());
+                                    let succeeded = result.is_ok();
+                                    function_span.record("latency_ms", execution.as_millis() as u64);
+                                    function_span.record_bool("succeeded", succeeded);
+                                    report_step(options, StepTiming { function, model_latency, execution, external_calls, succeeded });
+                                    result
+                                }
+                                Err(e) => Err(e),
+                            };
+                            match result {
+                                Ok(_) if name == GIVE_UP_FUNCTION => {
+                                    let reason = serde_json::from_str::<serde_json::Value>(&arguments)
+                                        .ok()
+                                        .and_then(|arguments| arguments["reason"].as_str().map(String::from))
+                                        .unwrap_or(arguments);
+                                    for observer in &options.observers {
+                                        observer.gave_up(&reason);
+                                    }
+                                    return Err(DriveError::ModelGaveUp { reason, transcript: messages });
+                                }
+                                Ok(next) => {
+                                    if let Some(transcript) = &options.transcript {
+                                        transcript.accept_last();
+                                    }
+                                    conversations.finish(conversation, messages);
+                                    next_prompt = next;
+                                    continue 'next;
+                                }
+                                Err(AiFunctionError::Recoverable(e)) => {
+                                    messages.push(Message::user(format!("Error: {}", e)));
+                                },
+                                Err(AiFunctionError::Unrecoverable(e)) => {
+                                    return Err(DriveError::Unrecoverable(e));
+                                }
+                            }
+                        }
+                    }
+                }
+                return Err(DriveError::TooManyErrors);
+            }
+        }
+    }
+}
+
+fn report_step(options: &DriveOptions, step: StepTiming) {
+    for observer in &options.observers {
+        observer.step(&step);
+    }
+    if let Some(stats) = &options.stats {
+        stats.record(step);
+    }
+}
+
+/// Rename top-level keys that are custom aliases to the parameter they stand for, so validation
+/// and pinning see its name.
+pub(crate) fn resolve_aliases(aliases: &[(&str, &str)], arguments: String) -> String {
+    if aliases.is_empty() {
+        return arguments;
+    }
+    let Ok(serde_json::Value::Object(object)) = serde_json::from_str(&arguments) else {
+        return arguments;
+    };
+    let object: serde_json::Map<_, _> = object
+        .into_iter()
+        .map(|(key, value)| match aliases.iter().find(|(alias, _)| *alias == key) {
+            Some((_, parameter)) => (parameter.to_string(), value),
+            None => (key, value),
+        })
+        .collect();
+    serde_json::Value::Object(object).to_string()
+}
+
+fn check_language(target: &str, arguments: &str) -> Result<(), AiFunctionError> {
+    let arguments: serde_json::Value = serde_json::from_str(arguments)?;
+    let mut strings = vec![];
+    language::string_values(&arguments, String::new(), &mut strings);
+    let mismatches: Vec<_> = strings
+        .iter()
+        .filter_map(|(path, text)| language::language_mismatch(target, text).map(|detected| format!("{path} appears to be in \"{detected}\"")))
+        .collect();
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(AiFunctionError::Recoverable(format!(
+            "All text must be written in language \"{target}\", but {}. Rewrite it in \"{target}\".",
+            mismatches.join(", "),
+        )))
+    }
+}
+
+/// Drive the state to completion and return its `Output` serialized as JSON.
+pub async fn drive_to_json<S: DynAiState + ?Sized>(state: &mut S) -> Result<serde_json::Value, DriveError> {
+    drive(state).await?;
+    state.output_json().map_err(DriveError::Output)
+}
@@ -0,0 +1,29 @@
+/// How many characters of an offending value are echoed back to the model in error messages by default.
+pub const DEFAULT_ECHO_LIMIT: usize = 200;
+
+/// Shorten `text` to at most about `max_len` chars by keeping its head and tail and noting how much
+/// was cut, e.g. `Once upon a time…[4,812 chars omitted]…happily ever after.`
+pub fn elide(text: &str, max_len: usize) -> String {
+    let len = text.chars().count();
+    if len <= max_len {
+        return text.to_string();
+    }
+    let head_len = max_len / 2;
+    let tail_len = max_len - head_len;
+    let head: String = text.chars().take(head_len).collect();
+    let tail: String = text.chars().skip(len - tail_len).collect();
+    format!("{head}…[{} chars omitted]…{tail}", len - head_len - tail_len)
+}
+
+/// Elide every string inside a JSON value.
+pub(crate) fn elide_value(value: &serde_json::Value, max_len: usize) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(s) => serde_json::Value::String(elide(s, max_len)),
+        serde_json::Value::Array(items) => items.iter().map(|item| elide_value(item, max_len)).collect(),
+        serde_json::Value::Object(object) => object
+            .iter()
+            .map(|(key, value)| (key.clone(), elide_value(value, max_len)))
+            .collect(),
+        other => other.clone(),
+    }
+}
@@ -0,0 +1,38 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{ChatError, OpenAIClient};
+
+/// Most inputs OpenAI accepts in one embeddings request
+const MAX_INPUTS_PER_REQUEST: usize = 2048;
+
+#[derive(Deserialize)]
+struct EmbeddingList {
+    data: Vec<Embedding>,
+}
+
+#[derive(Deserialize)]
+struct Embedding {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl OpenAIClient {
+    /// Embed each of `inputs` with `model` (e.g. `text-embedding-3-small`), returning one vector per
+    /// input in the same order. Large inputs are split into several requests, each retried like chat
+    /// completions per the client's `RetryPolicy`.
+    pub async fn embeddings(&self, model: &str, inputs: &[impl AsRef<str>]) -> Result<Vec<Vec<f32>>, ChatError> {
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        for chunk in inputs.chunks(MAX_INPUTS_PER_REQUEST) {
+            let input: Vec<&str> = chunk.iter().map(AsRef::as_ref).collect();
+            let mut list: EmbeddingList = self.post_json("embeddings", &json!({ "model": model, "input": input }), model).await?;
+            if list.data.len() != chunk.len() {
+                return Err(ChatError::Other(format!("Expected {} embeddings, got {}", chunk.len(), list.data.len())));
+            }
+            list.data.sort_by_key(|embedding| embedding.index);
+            embeddings.extend(list.data.into_iter().map(|embedding| embedding.embedding));
+        }
+        Ok(embeddings)
+    }
+}
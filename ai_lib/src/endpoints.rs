@@ -0,0 +1,84 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::diagnostics::{self, Diagnostic};
+
+/// Regional endpoints of one API, with health and latency tracked per endpoint. Requests go to the
+/// healthy endpoint with the lowest observed latency (untried endpoints count as fastest, so each
+/// gets probed); an endpoint that fails repeatedly is taken out of rotation for a while.
+#[derive(Debug)]
+pub(crate) struct Endpoints {
+    endpoints: Vec<Endpoint>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    base_url: String,
+    health: Mutex<Health>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    /// Exponentially weighted moving average of request latency
+    latency: Option<Duration>,
+    consecutive_failures: u32,
+    down_until: Option<Instant>,
+}
+
+/// Failures in a row before an endpoint is taken out of rotation
+const FAILURES_BEFORE_DOWN: u32 = 2;
+
+impl Endpoints {
+    pub fn new(base_urls: Vec<String>) -> Self {
+        let endpoints = base_urls
+            .into_iter()
+            .map(|base_url| Endpoint { base_url, health: Mutex::default() })
+            .collect();
+        Self { endpoints }
+    }
+
+    /// The first configured endpoint, which identifies the provider.
+    pub fn primary(&self) -> &str {
+        &self.endpoints[0].base_url
+    }
+
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// The index and base URL of the endpoint to use next, as of `now`.
+    pub fn select(&self, now: Instant) -> (usize, &str) {
+        if self.endpoints.len() == 1 {
+            return (0, self.primary());
+        }
+        let health: Vec<_> = self.endpoints.iter().map(|e| e.health.lock().unwrap()).collect();
+        let up = (0..health.len()).filter(|&i| health[i].down_until.is_none_or(|until| until <= now));
+        let best = up.min_by_key(|&i| health[i].latency.unwrap_or(Duration::ZERO)).unwrap_or_else(|| {
+            // Everything is down: try whichever comes back soonest
+            (0..health.len()).min_by_key(|&i| health[i].down_until).unwrap()
+        });
+        (best, &self.endpoints[best].base_url)
+    }
+
+    pub fn record_success(&self, index: usize, latency: Duration) {
+        let mut health = self.endpoints[index].health.lock().unwrap();
+        health.latency = Some(match health.latency {
+            Some(average) => average.mul_f64(0.8) + latency.mul_f64(0.2),
+            None => latency,
+        });
+        health.consecutive_failures = 0;
+        health.down_until = None;
+    }
+
+    pub fn record_failure(&self, index: usize, now: Instant) {
+        let mut health = self.endpoints[index].health.lock().unwrap();
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= FAILURES_BEFORE_DOWN && self.endpoints.len() > 1 {
+            // 30s, doubling with further failures, up to 5 minutes
+            let exponent = (health.consecutive_failures - FAILURES_BEFORE_DOWN).min(4);
+            let cooldown = (Duration::from_secs(30) * 2u32.pow(exponent)).min(Duration::from_secs(300));
+            diagnostics::emit(Diagnostic::EndpointDown { base_url: self.endpoints[index].base_url.clone(), cooldown });
+            health.down_until = Some(now + cooldown);
+        }
+    }
+}
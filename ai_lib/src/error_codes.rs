@@ -0,0 +1,53 @@
+//! Stable, machine-readable codes for every error the library returns, from `error_code()` on
+//! `DriveError`, `ChatError`, `ApiError`, and `ConfigError`. They prefix each error's `Display` as
+//! `[E_...]` and appear as `code` in its serialized form, so host applications can branch on them
+//! instead of matching message text. Codes are never renamed or reused; new ones may be added.
+
+/// The prompt plus completion exceeded the model's context window
+pub const E_CONTEXT_OVERFLOW: &str = "E_CONTEXT_OVERFLOW";
+/// Still rate limited after backing off as long as allowed
+pub const E_RATE_LIMITED: &str = "E_RATE_LIMITED";
+/// The account is out of credit or over its quota
+pub const E_QUOTA: &str = "E_QUOTA";
+/// The API key was missing, invalid, or lacks permission
+pub const E_AUTH: &str = "E_AUTH";
+/// The requested model doesn't exist or isn't available to this key
+pub const E_MODEL_NOT_FOUND: &str = "E_MODEL_NOT_FOUND";
+/// The endpoint or resource doesn't exist
+pub const E_NOT_FOUND: &str = "E_NOT_FOUND";
+/// The provider's content filter rejected the request or response
+pub const E_CONTENT_FILTER: &str = "E_CONTENT_FILTER";
+/// The provider failed with a server error
+pub const E_PROVIDER: &str = "E_PROVIDER";
+/// A circuit breaker is open after repeated provider failures; no request was sent
+pub const E_PROVIDER_UNAVAILABLE: &str = "E_PROVIDER_UNAVAILABLE";
+/// Any other error reported by the provider, e.g. an invalid request
+pub const E_API: &str = "E_API";
+/// The provider couldn't be reached
+pub const E_CONNECT: &str = "E_CONNECT";
+/// Any other HTTP failure
+pub const E_HTTP: &str = "E_HTTP";
+/// A request timed out
+pub const E_TIMEOUT: &str = "E_TIMEOUT";
+/// No API key was configured
+pub const E_MISSING_API_KEY: &str = "E_MISSING_API_KEY";
+/// The client couldn't be configured
+pub const E_CONFIG: &str = "E_CONFIG";
+/// A function returned an unrecoverable error
+pub const E_UNRECOVERABLE: &str = "E_UNRECOVERABLE";
+/// The model repeated an identical call under `DuplicateCallPolicy::Fail`
+pub const E_REPEATED_CALL: &str = "E_REPEATED_CALL";
+/// Every attempt at a prompt failed, e.g. because the model's calls never parsed or validated
+pub const E_TOO_MANY_ERRORS: &str = "E_TOO_MANY_ERRORS";
+/// The run's deadline passed
+pub const E_DEADLINE: &str = "E_DEADLINE";
+/// The model called `give_up`
+pub const E_GAVE_UP: &str = "E_GAVE_UP";
+/// A client `Budget` or a run's cost objective was exceeded
+pub const E_BUDGET: &str = "E_BUDGET";
+/// The run exceeded its latency objective
+pub const E_SLO_LATENCY: &str = "E_SLO_LATENCY";
+/// The finished state's output couldn't be serialized
+pub const E_OUTPUT: &str = "E_OUTPUT";
+/// Anything else
+pub const E_OTHER: &str = "E_OTHER";
@@ -0,0 +1,120 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde_json::{json, Map, Value};
+
+/// Nesting beyond this is elided (as `null`, or an empty array), so recursive types still produce a
+/// finite example.
+const MAX_EXAMPLE_DEPTH: usize = 8;
+
+static SCHEMA_EXAMPLES: AtomicBool = AtomicBool::new(false);
+
+/// Whether every `#[ai_function]` schema gets an `examples` entry synthesized from its types, from
+/// now on. Off by default. Example-bearing schemas noticeably cut malformed calls for complex,
+/// nested arguments, at the cost of a longer prompt. Functions declared with
+/// `#[ai_function(example = "...")]` always carry their own examples.
+pub fn set_schema_examples(enabled: bool) {
+    SCHEMA_EXAMPLES.store(enabled, Ordering::Relaxed);
+}
+
+pub fn schema_examples() -> bool {
+    SCHEMA_EXAMPLES.load(Ordering::Relaxed)
+}
+
+/// Set the `examples` of a function's parameter schema: `samples` (JSON documents) if there are
+/// any, otherwise one synthesized from the schema if `schema_examples` is on. Called by the code
+/// `#[ai_function]` generates.
+pub fn add_examples(parameters: &mut Value, samples: &[&str]) {
+    let examples: Vec<Value> = if !samples.is_empty() {
+        samples
+            .iter()
+            .map(|sample| serde_json::from_str(sample).unwrap_or_else(|e| panic!("Invalid example {sample}: {e}")))
+            .collect()
+    } else if schema_examples() {
+        vec![example_for_schema(parameters)]
+    } else {
+        return;
+    };
+    if let Some(object) = parameters.as_object_mut() {
+        object.insert("examples".to_string(), examples.into());
+    }
+}
+
+/// A value matching `schema`, with placeholder strings named after their fields, enums' first
+/// variants, and integers at their minimum. `$ref`s into the schema's `$defs` are followed.
+pub fn example_for_schema(schema: &Value) -> Value {
+    Synthesizer { root: schema }.example(schema, None, 0)
+}
+
+struct Synthesizer<'a> {
+    root: &'a Value,
+}
+
+impl Synthesizer<'_> {
+    fn example(&self, schema: &Value, name: Option<&str>, depth: usize) -> Value {
+        if depth > MAX_EXAMPLE_DEPTH {
+            return Value::Null;
+        }
+        if let Some(reference) = schema["$ref"].as_str() {
+            return match self.resolve(reference) {
+                Some(schema) => self.example(schema, name, depth + 1),
+                None => Value::Null,
+            };
+        }
+        if let Some(value) = schema.get("const") {
+            return value.clone();
+        }
+        if let Some(value) = schema["enum"].as_array().and_then(|values| values.first()) {
+            return value.clone();
+        }
+        for combinator in ["anyOf", "oneOf", "allOf"] {
+            if let Some(variants) = schema[combinator].as_array() {
+                let variant = variants.iter().find(|variant| variant["type"] != "null").or(variants.first());
+                if let Some(variant) = variant {
+                    return self.example(variant, name, depth + 1);
+                }
+            }
+        }
+
+        let ty = match &schema["type"] {
+            Value::String(ty) => ty.as_str(),
+            // e.g. ["string", "null"] for an Option
+            Value::Array(types) => types.iter().filter_map(Value::as_str).find(|ty| *ty != "null").unwrap_or("null"),
+            _ if schema.get("properties").is_some() => "object",
+            _ => "null",
+        };
+        match ty {
+            "object" => {
+                let mut object = Map::new();
+                if let Some(properties) = schema["properties"].as_object() {
+                    for (key, property) in properties {
+                        object.insert(key.clone(), self.example(property, Some(key), depth + 1));
+                    }
+                }
+                Value::Object(object)
+            }
+            "array" => {
+                let item = match &schema["items"] {
+                    Value::Null => Value::Null,
+                    items => self.example(items, name, depth + 1),
+                };
+                // Where a recursive type was cut off, an empty array is still a valid example
+                if item.is_null() {
+                    return json!([]);
+                }
+                let len = schema["minItems"].as_u64().unwrap_or(1).max(1);
+                Value::Array(vec![item; len as usize])
+            }
+            "string" => json!(name.unwrap_or("string")),
+            "integer" => json!(schema["minimum"].as_i64().unwrap_or(0)),
+            "number" => json!(schema["minimum"].as_f64().unwrap_or(0.0)),
+            "boolean" => json!(false),
+            _ => Value::Null,
+        }
+    }
+
+    fn resolve(&self, reference: &str) -> Option<&Value> {
+        let pointer = reference.strip_prefix('#')?;
+        self.root.pointer(pointer)
+    }
+}
+
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ExtractError {
+    Io(std::io::Error),
+    /// The file type isn't supported, or its extraction feature isn't enabled
+    Unsupported(String),
+    Pdf(String),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Io(e) => write!(f, "Failed to read document: {e}"),
+            ExtractError::Unsupported(e) => write!(f, "{e}"),
+            ExtractError::Pdf(e) => write!(f, "Failed to extract PDF text: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+impl From<std::io::Error> for ExtractError {
+    fn from(e: std::io::Error) -> Self {
+        ExtractError::Io(e)
+    }
+}
+
+/// Prompt-ready text from a document, chosen by file extension: PDFs (with the `pdf` feature), HTML
+/// (with the `html` feature), and anything else read as UTF-8 text.
+pub fn extract_text(path: impl AsRef<Path>) -> Result<String, ExtractError> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "pdf" => pdf_to_text(&std::fs::read(path)?),
+        "html" | "htm" => html_to_text(&std::fs::read_to_string(path)?),
+        _ => Ok(std::fs::read_to_string(path)?),
+    }
+}
+
+/// The text of a PDF, with whitespace tidied up.
+#[cfg(feature = "pdf")]
+pub fn pdf_to_text(bytes: &[u8]) -> Result<String, ExtractError> {
+    let text = pdf_extract::extract_text_from_mem(bytes).map_err(|e| ExtractError::Pdf(e.to_string()))?;
+    Ok(tidy(&text))
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn pdf_to_text(_bytes: &[u8]) -> Result<String, ExtractError> {
+    Err(ExtractError::Unsupported("PDF extraction requires the `pdf` feature".to_string()))
+}
+
+/// The readable text of a scraped page. Scripts, styles, navigation, headers, footers, sidebars, and
+/// forms are dropped, and if the page has a `<main>` or `<article>` only that is kept. Block elements
+/// become line breaks.
+#[cfg(feature = "html")]
+pub fn html_to_text(html: &str) -> Result<String, ExtractError> {
+    use scraper::{ElementRef, Html, Node, Selector};
+
+    const SKIPPED: &[&str] = &["script", "style", "noscript", "template", "nav", "header", "footer", "aside", "form", "svg", "iframe"];
+    const BLOCKS: &[&str] = &[
+        "p", "div", "section", "article", "main", "br", "li", "ul", "ol", "tr", "table", "blockquote", "pre",
+        "h1", "h2", "h3", "h4", "h5", "h6",
+    ];
+
+    fn walk(element: ElementRef, text: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(t) => text.push_str(t),
+                Node::Element(e) if SKIPPED.contains(&e.name()) => {}
+                Node::Element(e) => {
+                    let block = BLOCKS.contains(&e.name());
+                    if block {
+                        text.push('\n');
+                    }
+                    if let Some(child) = ElementRef::wrap(child) {
+                        walk(child, text);
+                    }
+                    if block {
+                        text.push('\n');
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let document = Html::parse_document(html);
+    let content = Selector::parse("main, article").unwrap();
+    let body = Selector::parse("body").unwrap();
+    let root = document
+        .select(&content)
+        .next()
+        .or_else(|| document.select(&body).next())
+        .unwrap_or_else(|| document.root_element());
+
+    let mut text = String::new();
+    walk(root, &mut text);
+    Ok(tidy(&text))
+}
+
+#[cfg(not(feature = "html"))]
+pub fn html_to_text(_html: &str) -> Result<String, ExtractError> {
+    Err(ExtractError::Unsupported("HTML extraction requires the `html` feature".to_string()))
+}
+
+/// Collapse runs of spaces within lines and runs of blank lines, and trim each line.
+#[cfg(any(feature = "pdf", feature = "html"))]
+fn tidy(text: &str) -> String {
+    let mut tidied = String::new();
+    let mut blank = true;
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            if !blank {
+                tidied.push('\n');
+            }
+            blank = true;
+        } else {
+            tidied.push_str(&line);
+            tidied.push('\n');
+            blank = false;
+        }
+    }
+    tidied.trim().to_string()
+}
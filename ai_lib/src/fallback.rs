@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::{ChatBackend, ChatCompletionRequest, ChatCompletionResponse, ChatError, Model};
+
+/// Tries an ordered chain of models (possibly on different backends), moving on to the next one when
+/// a request fails in a way another model might not: rate limiting, provider outages, or context
+/// overflow. The response's `model` and `attribution` report which model and provider served it.
+#[derive(Clone, Default)]
+pub struct FallbackBackend {
+    chain: Vec<(Arc<dyn ChatBackend>, Model)>,
+}
+
+impl FallbackBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every model in `models`, in order, on the same backend.
+    pub fn models(backend: impl ChatBackend + 'static, models: impl IntoIterator<Item = Model>) -> Self {
+        let backend: Arc<dyn ChatBackend> = Arc::new(backend);
+        Self { chain: models.into_iter().map(|model| (backend.clone(), model)).collect() }
+    }
+
+    /// Append `model` on `backend` to the end of the chain.
+    pub fn then(mut self, backend: impl ChatBackend + 'static, model: Model) -> Self {
+        self.chain.push((Arc::new(backend), model));
+        self
+    }
+
+    /// Append `backend` with its own default model to the end of the chain.
+    pub fn then_default(self, backend: impl ChatBackend + 'static) -> Self {
+        let model = backend.default_model();
+        self.then(backend, model)
+    }
+}
+
+#[async_trait]
+impl ChatBackend for FallbackBackend {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        let mut last_error = ChatError::Other("Fallback chain is empty".to_string());
+        for (i, (backend, model)) in self.chain.iter().enumerate() {
+            let mut req = req.clone();
+            req.model = model.clone();
+            match backend.chat(&req).await {
+                Ok(response) => return Ok(response),
+                Err(e) if e.is_fallback_worthy() => {
+                    if let Some((_, next)) = self.chain.get(i + 1) {
+                        diagnostics::emit(Diagnostic::FallingBack {
+                            model: model.as_str().to_string(),
+                            next: next.as_str().to_string(),
+                            error: e.to_string(),
+                        });
+                    }
+                    last_error = e;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error)
+    }
+
+    fn default_model(&self) -> Model {
+        self.chain.first().map(|(_, model)| model.clone()).unwrap_or(Model::Gpt3p5Turbo)
+    }
+}
@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{CalledFunction, ChatError, Message, Model, OpenAIClient, Transcript};
+
+/// A file stored with the provider, e.g. uploaded training data.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FileObject {
+    pub id: String,
+    #[serde(default)]
+    pub bytes: u64,
+    #[serde(default)]
+    pub filename: String,
+    #[serde(default)]
+    pub purpose: String,
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Builder)]
+#[builder(setter(into))]
+pub struct CreateFineTuningJob {
+    /// Base model to fine-tune
+    pub model: Model,
+    /// ID of an uploaded file with purpose `fine-tune`
+    pub training_file: String,
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validation_file: Option<String>,
+    /// Appended to the resulting model's name
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FineTuningJob {
+    pub id: String,
+    #[serde(default)]
+    pub model: String,
+    /// `validating_files`, `queued`, `running`, `succeeded`, `failed`, or `cancelled`
+    pub status: String,
+    /// The resulting model, once the job has succeeded
+    #[serde(default)]
+    pub fine_tuned_model: Option<String>,
+    #[serde(default)]
+    pub training_file: String,
+    #[serde(default)]
+    pub trained_tokens: Option<u64>,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    #[serde(default)]
+    pub error: Option<serde_json::Value>,
+}
+
+impl FineTuningJob {
+    /// Whether the job has stopped, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status.as_str(), "succeeded" | "failed" | "cancelled")
+    }
+}
+
+/// A progress message from a fine-tuning job, e.g. the loss at a training step.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FineTuningEvent {
+    pub id: String,
+    /// `info`, `warn`, or `error`
+    #[serde(default)]
+    pub level: String,
+    pub message: String,
+    #[serde(default)]
+    pub created_at: u64,
+    /// Step metrics such as `train_loss`, for `metrics` events
+    #[serde(default)]
+    pub data: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct List<T> {
+    pub data: Vec<T>,
+}
+
+impl OpenAIClient {
+    /// Upload a file for use with `purpose`, e.g. `fine-tune`.
+    pub async fn upload_file(&self, filename: impl Into<String>, contents: Vec<u8>, purpose: &str) -> Result<FileObject, ChatError> {
+        let form = reqwest::multipart::Form::new()
+            .text("purpose", purpose.to_string())
+            .part("file", reqwest::multipart::Part::bytes(contents).file_name(filename.into()));
+        self.send_json(self.request(reqwest::Method::POST, "files").multipart(form)).await
+    }
+
+    /// Stored files, optionally only those uploaded for `purpose`.
+    pub async fn list_files(&self, purpose: Option<&str>) -> Result<Vec<FileObject>, ChatError> {
+        let req = self.request(reqwest::Method::GET, "files");
+        let req = match purpose {
+            Some(purpose) => req.query(&[("purpose", purpose)]),
+            None => req,
+        };
+        let list: List<FileObject> = self.send_json(req).await?;
+        Ok(list.data)
+    }
+
+    pub async fn file(&self, id: &str) -> Result<FileObject, ChatError> {
+        self.send_json(self.request(reqwest::Method::GET, &format!("files/{id}"))).await
+    }
+
+    pub async fn delete_file(&self, id: &str) -> Result<(), ChatError> {
+        let _: serde_json::Value = self.send_json(self.request(reqwest::Method::DELETE, &format!("files/{id}"))).await?;
+        Ok(())
+    }
+
+    /// The contents of a stored file, e.g. a batch's output.
+    pub async fn file_content(&self, id: &str) -> Result<String, ChatError> {
+        let res = self.request(reqwest::Method::GET, &format!("files/{id}/content")).send().await?;
+        let status = res.status();
+        let body = res.text().await?;
+        if !status.is_success() {
+            return Err(ChatError::from_status(status.as_u16(), body));
+        }
+        Ok(body)
+    }
+
+    /// Upload a document (e.g. a PDF) to attach to prompts via `Message::with_files` or the `files`
+    /// prompt option.
+    pub async fn upload_document(&self, path: impl AsRef<std::path::Path>) -> Result<FileObject, ChatError> {
+        let path = path.as_ref();
+        let contents = tokio::fs::read(path).await.map_err(|e| ChatError::Other(format!("Failed to read {}: {e}", path.display())))?;
+        let filename = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        self.upload_file(filename, contents, "user_data").await
+    }
+
+    /// Upload training data, e.g. from `Transcript::to_fine_tuning_jsonl`.
+    pub async fn upload_training_file(&self, jsonl: impl Into<String>) -> Result<FileObject, ChatError> {
+        self.upload_file("training.jsonl", jsonl.into().into_bytes(), "fine-tune").await
+    }
+
+    pub async fn create_fine_tuning_job(&self, job: &CreateFineTuningJob) -> Result<FineTuningJob, ChatError> {
+        self.send_json(self.request(reqwest::Method::POST, "fine_tuning/jobs").json(job)).await
+    }
+
+    pub async fn fine_tuning_job(&self, id: &str) -> Result<FineTuningJob, ChatError> {
+        self.send_json(self.request(reqwest::Method::GET, &format!("fine_tuning/jobs/{id}"))).await
+    }
+
+    /// The most recent fine-tuning jobs, newest first.
+    pub async fn list_fine_tuning_jobs(&self) -> Result<Vec<FineTuningJob>, ChatError> {
+        let list: List<FineTuningJob> = self.send_json(self.request(reqwest::Method::GET, "fine_tuning/jobs")).await?;
+        Ok(list.data)
+    }
+
+    pub async fn cancel_fine_tuning_job(&self, id: &str) -> Result<FineTuningJob, ChatError> {
+        self.send_json(self.request(reqwest::Method::POST, &format!("fine_tuning/jobs/{id}/cancel"))).await
+    }
+
+    /// A job's progress messages, newest first.
+    pub async fn fine_tuning_events(&self, id: &str) -> Result<Vec<FineTuningEvent>, ChatError> {
+        let list: List<FineTuningEvent> = self.send_json(self.request(reqwest::Method::GET, &format!("fine_tuning/jobs/{id}/events"))).await?;
+        Ok(list.data)
+    }
+
+    /// Poll a job every `interval` until it finishes, and return it in its final state.
+    pub async fn wait_for_fine_tuning_job(&self, id: &str, interval: Duration) -> Result<FineTuningJob, ChatError> {
+        loop {
+            let job = self.fine_tuning_job(id).await?;
+            if job.is_finished() {
+                return Ok(job);
+            }
+            self.clock().sleep(interval).await;
+        }
+    }
+
+    /// Models produced by successful fine-tuning jobs, ready to use as `Model::custom`.
+    pub async fn fine_tuned_models(&self) -> Result<Vec<Model>, ChatError> {
+        Ok(self
+            .list_fine_tuning_jobs()
+            .await?
+            .into_iter()
+            .filter_map(|job| job.fine_tuned_model)
+            .map(Model::custom)
+            .collect())
+    }
+}
+
+impl Transcript {
+    /// One chat-format fine-tuning example per completed prompt of the run, in OpenAI's JSONL format
+    /// (`{"messages": [...], "functions": [...]}` per line). Failed attempts are left out: each
+    /// example is the prompt and its history followed by the call the model finally got right, so a
+    /// model trained on them learns the successful trajectory directly.
+    pub fn to_fine_tuning_jsonl(&self) -> String {
+        self.fine_tuning_examples()
+            .iter()
+            .map(|example| format!("{example}\n"))
+            .collect()
+    }
+
+    pub fn fine_tuning_examples(&self) -> Vec<serde_json::Value> {
+        let entries = self.entries();
+        let mut examples = vec![];
+        // The attempts at a prompt are contiguous, ending with the accepted one
+        let mut first_attempt = 0;
+        for (i, entry) in entries.iter().enumerate() {
+            if !entry.accepted {
+                continue;
+            }
+            let first = &entries[first_attempt];
+            first_attempt = i + 1;
+
+            let Some(choice) = entry.response.choices.first() else {
+                continue;
+            };
+            let mut messages: Vec<_> = first.request.messages.iter().map(training_message).collect();
+            messages.push(training_message(&choice.message));
+            let mut example = json!({ "messages": messages });
+            if let Some(functions) = &entry.request.functions {
+                example["functions"] = serde_json::to_value(functions).unwrap();
+            }
+            examples.push(example);
+        }
+        examples
+    }
+}
+
+/// The driver keeps function calls in the history as JSON content; fine-tuning wants them back as
+/// `function_call` messages.
+fn training_message(message: &Message) -> serde_json::Value {
+    if message.role == "assistant" && message.function_call.is_none() {
+        if let Some(function_call) = message.content.as_deref().and_then(|content| serde_json::from_str::<CalledFunction>(content).ok()) {
+            return json!({ "role": "assistant", "content": null, "function_call": function_call });
+        }
+    }
+    let mut value = serde_json::to_value(message).unwrap();
+    if message.function_call.is_some() {
+        value["content"] = serde_json::Value::Null;
+    }
+    value
+}
@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::{
+    Attribution, CalledFunction, ChatBackend, ChatCompletionRequest, ChatCompletionResponse, ChatError, Choice,
+    FunctionCall, gemini_schema, Message, Model, ResponseFormat, Usage,
+};
+
+const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Google Gemini via the `generateContent` API, translating OpenAI-style function calling to
+/// Gemini's `function_declarations`/`functionCall` parts.
+pub struct GeminiBackend {
+    client: Client,
+    api_key: String,
+    base_url: String,
+    model: String,
+}
+
+impl GeminiBackend {
+    /// `model` is a Gemini model name such as `gemini-1.5-pro`.
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            client: Client::new(),
+            api_key: api_key.into(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            model: model.into(),
+        }
+    }
+
+    /// Configure from the `GEMINI_API_KEY` environment variable.
+    pub fn from_env(model: impl Into<String>) -> Result<Self, ChatError> {
+        let api_key = std::env::var("GEMINI_API_KEY").map_err(|_| ChatError::Other("GEMINI_API_KEY not set".to_string()))?;
+        Ok(Self::new(api_key, model))
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Send requests through this HTTP client, e.g. one configured with a proxy or custom root certificates.
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    fn request_body(&self, req: &ChatCompletionRequest) -> Value {
+        let mut system = vec![];
+        let mut contents = vec![];
+        for message in &req.messages {
+            let text = message.content.clone().unwrap_or_default();
+            match message.role.as_str() {
+                "system" => system.push(json!({ "text": text })),
+                "assistant" => contents.push(json!({ "role": "model", "parts": [{ "text": text }] })),
+                _ => {
+                    let mut parts = vec![json!({ "text": text })];
+                    parts.extend(message.images.iter().map(|url| match crate::image::split_data_url(url) {
+                        Some((mime_type, data)) => json!({ "inlineData": { "mimeType": mime_type, "data": data } }),
+                        None => json!({ "fileData": { "fileUri": url } }),
+                    }));
+                    contents.push(json!({ "role": "user", "parts": parts }));
+                }
+            }
+        }
+
+        let mut generation_config = json!({});
+        if let Some(temperature) = req.temperature {
+            generation_config["temperature"] = temperature.into();
+        }
+        if let Some(max_tokens) = req.max_tokens {
+            generation_config["maxOutputTokens"] = max_tokens.into();
+        }
+        if let Some(top_p) = req.top_p {
+            generation_config["topP"] = top_p.into();
+        }
+        if let Some(presence_penalty) = req.presence_penalty {
+            generation_config["presencePenalty"] = presence_penalty.into();
+        }
+        if let Some(frequency_penalty) = req.frequency_penalty {
+            generation_config["frequencyPenalty"] = frequency_penalty.into();
+        }
+        if let Some(stop) = &req.stop {
+            generation_config["stopSequences"] = stop.clone().into();
+        }
+        if req.response_format == Some(ResponseFormat::JsonObject) {
+            generation_config["responseMimeType"] = "application/json".into();
+        }
+
+        let mut body = json!({ "contents": contents, "generationConfig": generation_config });
+        if !system.is_empty() {
+            body["systemInstruction"] = json!({ "parts": system });
+        }
+        if let Some(functions) = &req.functions {
+            let declarations: Vec<_> = functions
+                .iter()
+                .map(|f| json!({ "name": f.name, "description": f.description, "parameters": gemini_schema(&f.parameters) }))
+                .collect();
+            body["tools"] = json!([{ "function_declarations": declarations }]);
+            body["tool_config"] = match &req.function_call {
+                Some(FunctionCall::Exact { name }) => json!({ "function_calling_config": { "mode": "ANY", "allowed_function_names": [name] } }),
+                Some(FunctionCall::None) => json!({ "function_calling_config": { "mode": "NONE" } }),
+                _ => json!({ "function_calling_config": { "mode": "AUTO" } }),
+            };
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl ChatBackend for GeminiBackend {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        let res = self
+            .client
+            .post(format!("{}/models/{}:generateContent", self.base_url, self.model))
+            .query(&[("key", &self.api_key)])
+            .query(&req.extra_query)
+            .json(&self.request_body(req));
+        let res = req
+            .extra_headers
+            .iter()
+            .fold(res, |res, (name, value)| res.header(name, value));
+        let res = match req.timeout {
+            Some(timeout) => res.timeout(timeout),
+            None => res,
+        };
+        let res = res.send().await?;
+        let status = res.status();
+        let text = res.text().await?;
+        if !status.is_success() {
+            return Err(ChatError::from_status(status.as_u16(), text));
+        }
+        let body: Value = serde_json::from_str(&text).map_err(|e| ChatError::Other(format!("Failed to parse Gemini response ({e}): {text}")))?;
+
+        let candidate = &body["candidates"][0];
+        let mut text = String::new();
+        let mut function_call = None;
+        for part in candidate["content"]["parts"].as_array().into_iter().flatten() {
+            if let Some(call) = part.get("functionCall") {
+                function_call = Some(CalledFunction {
+                    name: call["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: call.get("args").cloned().unwrap_or_else(|| json!({})).to_string(),
+                });
+            } else if let Some(t) = part["text"].as_str() {
+                text.push_str(t);
+            }
+        }
+
+        let usage = &body["usageMetadata"];
+        let token_count = |key: &str| usage[key].as_i64().unwrap_or(0) as i32;
+
+        Ok(ChatCompletionResponse {
+            created: 0,
+            model: self.model.clone(),
+            choices: vec![Choice {
+                index: 0,
+                message: Message {
+                    role: "assistant".to_string(),
+                    content: (!text.is_empty()).then_some(text),
+                    function_call,
+                    files: vec![],
+                    images: vec![],
+                },
+                finish_reason: candidate["finishReason"].as_str().unwrap_or_default().to_lowercase(),
+                logprobs: None,
+            }],
+            usage: Usage {
+                prompt_tokens: token_count("promptTokenCount"),
+                completion_tokens: token_count("candidatesTokenCount"),
+                total_tokens: token_count("totalTokenCount"),
+            },
+            system_fingerprint: None,
+            attribution: Some(Attribution::new("gemini", Some(&self.api_key))),
+        })
+    }
+
+    fn default_model(&self) -> Model {
+        Model::custom(self.model.clone())
+    }
+}
@@ -0,0 +1,89 @@
+use serde_json::{json, Value};
+
+/// Convert a JSON schema from `schema()` into the OpenAPI subset Gemini accepts: `$ref`s are inlined,
+/// `const` becomes a one-value `enum`, nullable unions become `nullable: true`, types are uppercase,
+/// and unsupported keywords are dropped.
+pub fn gemini_schema(schema: &Value) -> Value {
+    let definitions = schema
+        .get("$defs")
+        .or_else(|| schema.get("definitions"))
+        .cloned()
+        .unwrap_or(Value::Null);
+    convert(schema, &definitions, 0)
+}
+
+fn convert(schema: &Value, definitions: &Value, depth: usize) -> Value {
+    let Some(object) = schema.as_object() else {
+        return schema.clone();
+    };
+
+    // Inline references, with a depth limit in case of recursive types
+    if let Some(reference) = object.get("$ref").and_then(|r| r.as_str()) {
+        let name = reference.rsplit('/').next().unwrap_or_default();
+        return match definitions.get(name) {
+            Some(definition) if depth < 16 => convert(definition, definitions, depth + 1),
+            _ => json!({ "type": "OBJECT" }),
+        };
+    }
+
+    // Option<T> comes through as anyOf [T, null] or type [T, "null"]
+    for key in ["anyOf", "oneOf"] {
+        if let Some(variants) = object.get(key).and_then(|v| v.as_array()) {
+            let non_null: Vec<_> = variants.iter().filter(|v| v["type"] != "null").collect();
+            if non_null.len() == 1 && variants.len() == 2 {
+                let mut converted = convert(non_null[0], definitions, depth + 1);
+                converted["nullable"] = true.into();
+                return converted;
+            }
+            if non_null.iter().all(|v| v.get("const").is_some()) {
+                let values: Vec<_> = non_null.iter().map(|v| v["const"].clone()).collect();
+                return json!({ "type": "STRING", "enum": values });
+            }
+        }
+    }
+
+    let mut converted = serde_json::Map::new();
+    for (key, value) in object {
+        match key.as_str() {
+            "type" => match value {
+                Value::Array(types) => {
+                    let non_null: Vec<_> = types.iter().filter(|t| *t != "null").collect();
+                    if let Some(t) = non_null.first().and_then(|t| t.as_str()) {
+                        converted.insert("type".to_string(), t.to_uppercase().into());
+                    }
+                    if non_null.len() < types.len() {
+                        converted.insert("nullable".to_string(), true.into());
+                    }
+                }
+                Value::String(t) => {
+                    converted.insert("type".to_string(), t.to_uppercase().into());
+                }
+                _ => {}
+            },
+            "const" => {
+                converted.insert("enum".to_string(), json!([value]));
+            }
+            "properties" => {
+                let properties: serde_json::Map<_, _> = value
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, property)| (name.clone(), convert(property, definitions, depth + 1)))
+                    .collect();
+                converted.insert(key.clone(), properties.into());
+            }
+            "items" => {
+                converted.insert(key.clone(), convert(value, definitions, depth + 1));
+            }
+            "description" | "enum" | "required" | "format" | "nullable" | "minItems" | "maxItems" | "minimum" | "maximum" => {
+                converted.insert(key.clone(), value.clone());
+            }
+            // $schema, $defs, title, additionalProperties, default, examples, ...
+            _ => {}
+        }
+    }
+    if converted.get("enum").is_some() && converted.get("type").is_none() {
+        converted.insert("type".to_string(), "STRING".into());
+    }
+    converted.into()
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{ChatCompletionRequest, Message, Transcript};
+
+/// What part of a request a `HeatmapSegment` covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum SegmentKind {
+    /// A system message
+    System,
+    /// Prompt text from `prompt!`
+    Prompt,
+    /// A function's name, description, and schema overhead, excluding its fields
+    Schema,
+    /// One top-level field of a function's parameters, e.g. `write_chapter.text`
+    Field,
+    /// The model's earlier replies and calls, re-sent as history
+    History,
+    /// `Error: ...` feedback on rejected calls
+    ErrorFeedback,
+}
+
+/// The prompt tokens one part of the requests accounted for over a session.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeatmapSegment {
+    pub kind: SegmentKind,
+    pub label: String,
+    /// Prompt tokens attributed to this segment, summed over every request that sent it
+    pub tokens: f64,
+    /// Fraction of all prompt tokens in the session
+    pub share: f64,
+    /// How many times this segment was sent, counting every request it was part of
+    pub requests: usize,
+}
+
+/// Where a session's prompt tokens go, to guide prompt slimming. Each request's billed prompt tokens
+/// are split across its prompts, function schemas (down to individual fields), and history in
+/// proportion to their serialized size; requests without usage are estimated at four characters per
+/// token. Segments are sorted by tokens, largest first, and `Display` renders them as a table.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TokenHeatmap {
+    pub segments: Vec<HeatmapSegment>,
+    pub prompt_tokens: f64,
+}
+
+impl TokenHeatmap {
+    pub fn from_transcript(transcript: &Transcript) -> Self {
+        let mut totals: HashMap<(SegmentKind, String), (f64, usize)> = HashMap::new();
+        for entry in transcript.entries() {
+            let sizes = segment_sizes(&entry.request);
+            let chars: usize = sizes.iter().map(|(_, _, size)| size).sum();
+            let billed = entry.response.usage.prompt_tokens;
+            let tokens_per_char = if billed > 0 && chars > 0 { billed as f64 / chars as f64 } else { 0.25 };
+            for (kind, label, size) in sizes {
+                let total = totals.entry((kind, label)).or_default();
+                total.0 += size as f64 * tokens_per_char;
+                total.1 += 1;
+            }
+        }
+
+        let prompt_tokens: f64 = totals.values().map(|(tokens, _)| tokens).sum();
+        let mut segments: Vec<_> = totals
+            .into_iter()
+            .map(|((kind, label), (tokens, requests))| HeatmapSegment {
+                kind,
+                label,
+                tokens,
+                share: if prompt_tokens > 0.0 { tokens / prompt_tokens } else { 0.0 },
+                requests,
+            })
+            .collect();
+        segments.sort_by(|a, b| b.tokens.total_cmp(&a.tokens).then_with(|| a.label.cmp(&b.label)));
+        Self { segments, prompt_tokens }
+    }
+
+    /// Total tokens per kind of segment, largest first.
+    pub fn by_kind(&self) -> Vec<(SegmentKind, f64)> {
+        let mut totals: Vec<(SegmentKind, f64)> = vec![];
+        for segment in &self.segments {
+            match totals.iter_mut().find(|(kind, _)| *kind == segment.kind) {
+                Some((_, tokens)) => *tokens += segment.tokens,
+                None => totals.push((segment.kind, segment.tokens)),
+            }
+        }
+        totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+        totals
+    }
+}
+
+impl fmt::Display for TokenHeatmap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:.0} prompt tokens", self.prompt_tokens)?;
+        for segment in &self.segments {
+            let bar = "█".repeat((segment.share * 40.0).round() as usize);
+            writeln!(
+                f,
+                "{:>8.0} {:>5.1}% {:<40} {:?} {} (x{})",
+                segment.tokens,
+                segment.share * 100.0,
+                bar,
+                segment.kind,
+                segment.label,
+                segment.requests,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Transcript {
+    /// See `TokenHeatmap`.
+    pub fn token_heatmap(&self) -> TokenHeatmap {
+        TokenHeatmap::from_transcript(self)
+    }
+}
+
+/// The serialized size of each segment of a request.
+fn segment_sizes(req: &ChatCompletionRequest) -> Vec<(SegmentKind, String, usize)> {
+    let mut sizes = vec![];
+    for message in &req.messages {
+        let (kind, label) = classify(message);
+        sizes.push((kind, label, json_len(message)));
+    }
+    for function in req.functions.iter().flatten() {
+        let mut field_chars = 0;
+        for (field, schema) in function.parameters.get("properties").and_then(|p| p.as_object()).into_iter().flatten() {
+            let size = field.len() + json_len(schema);
+            field_chars += size;
+            sizes.push((SegmentKind::Field, format!("{}.{field}", function.name), size));
+        }
+        sizes.push((SegmentKind::Schema, function.name.clone(), json_len(function).saturating_sub(field_chars)));
+    }
+    sizes
+}
+
+fn classify(message: &Message) -> (SegmentKind, String) {
+    let content = message.content.as_deref().unwrap_or_default();
+    match (message.role.as_str(), &message.function_call) {
+        ("system", _) => (SegmentKind::System, label(content)),
+        (_, Some(call)) => (SegmentKind::History, format!("call to {}", call.name)),
+        ("assistant", None) => (SegmentKind::History, "text reply".to_string()),
+        (_, None) if content.starts_with("Error:") => (SegmentKind::ErrorFeedback, "errors".to_string()),
+        _ => (SegmentKind::Prompt, label(content)),
+    }
+}
+
+/// The start of the first line of a message, to tell prompts apart.
+fn label(content: &str) -> String {
+    let line = content.lines().next().unwrap_or_default();
+    match line.char_indices().nth(60) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None if line.len() < content.len() => format!("{line}…"),
+        None => line.to_string(),
+    }
+}
+
+fn json_len(value: &impl Serialize) -> usize {
+    serde_json::to_string(value).map(|s| s.len()).unwrap_or(0)
+}
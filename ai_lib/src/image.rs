@@ -0,0 +1,30 @@
+use std::path::Path;
+
+use base64::Engine;
+
+/// A `data:` URL embedding an image, to attach with `Message::with_images` or the `images` prompt
+/// option when the image isn't reachable at a public URL (e.g. a screenshot).
+pub fn image_data_url(bytes: &[u8], mime_type: &str) -> String {
+    format!("data:{mime_type};base64,{}", base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+/// A `data:` URL embedding the image file at `path`, its type guessed from the extension (PNG, JPEG,
+/// GIF, or WebP).
+pub fn image_file_data_url(path: impl AsRef<Path>) -> std::io::Result<String> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default().to_lowercase();
+    let mime_type = match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => "image/png",
+    };
+    Ok(image_data_url(&std::fs::read(path)?, mime_type))
+}
+
+/// The MIME type and base64 data of a `data:` URL, for providers that take images inline.
+#[cfg(feature = "gemini")]
+pub(crate) fn split_data_url(url: &str) -> Option<(&str, &str)> {
+    let (mime_type, data) = url.strip_prefix("data:")?.split_once(";base64,")?;
+    Some((mime_type, data))
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    parse_arguments, schema, AiFunctionError, ChatCompletionRequestBuilder, ChatError, DriveError, DriveOptions, Message, OpenAIClient,
+    ResponseFormat,
+};
+
+/// Attempts at a reply that parses before giving up with `DriveError::TooManyErrors`, as for a prompt.
+const MAX_ATTEMPTS: usize = 5;
+
+/// Ask for a reply in JSON mode (`response_format: json_object`) and parse it into `T`, for
+/// providers or prompts that don't use function calling. `T`'s schema is sent along with the
+/// prompt; a reply that doesn't match it is fed back to the model with what was wrong, as a failed
+/// function call would be. The backend, usage, transcript, timeout, and profile model of `options`
+/// apply.
+pub async fn drive_json<T: DeserializeOwned + JsonSchema>(prompt: &str, options: &DriveOptions) -> Result<T, DriveError> {
+    let backend = match &options.backend {
+        Some(backend) => backend.clone(),
+        None => Arc::new(OpenAIClient::try_new()?),
+    };
+    let model = options.profile.as_ref().and_then(|profile| profile.model.clone()).unwrap_or_else(|| backend.default_model());
+    let schema = schema::<T>();
+
+    let mut messages = vec![];
+    if let Some(system_prompt) = options.profile.as_ref().and_then(|profile| profile.system_prompt.as_ref()) {
+        messages.push(Message::system(system_prompt));
+    }
+    messages.push(Message::user(format!("{prompt}\n\nReply with a JSON object matching this JSON schema:\n{schema}")));
+
+    for _ in 0..MAX_ATTEMPTS {
+        let request = ChatCompletionRequestBuilder::default()
+            .model(model.clone())
+            .messages(messages.clone())
+            .function_call(None)
+            .response_format(ResponseFormat::JsonObject)
+            .seed(options.seed)
+            .timeout(options.request_timeout)
+            .build()
+            .unwrap();
+        let response = backend.chat(&request).await?;
+        if let Some(usage) = &options.usage {
+            usage.record_response(&response);
+        }
+        if let Some(transcript) = &options.transcript {
+            transcript.record(&request, &response);
+        }
+
+        let Some(choice) = response.choices.first() else {
+            return Err(ChatError::Other("The response had no choices".to_string()).into());
+        };
+        let message = choice.message.clone();
+        let content = message.content.clone().unwrap_or_default();
+        messages.push(message);
+        match parse_arguments::<T>(&content, || Some(schema.clone())) {
+            Ok(value) => {
+                if let Some(transcript) = &options.transcript {
+                    transcript.accept_last();
+                }
+                return Ok(value);
+            }
+            Err(AiFunctionError::Recoverable(e) | AiFunctionError::Unrecoverable(e)) => {
+                messages.push(Message::user(format!("Error: {e}")));
+            }
+        }
+    }
+    Err(DriveError::TooManyErrors)
+}
@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// How a client with several API keys picks one for each request. Either way, a key that was just
+/// rate limited is skipped until its limit resets, so a 429 on one key moves on to another.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyRotation {
+    /// Each request uses the next key in turn
+    #[default]
+    RoundRobin,
+    /// Each request uses the key that has gone longest without being rate limited
+    LeastRecentlyThrottled,
+}
+
+/// The API keys requests are spread across, with when each was last rate limited.
+#[derive(Debug)]
+pub(crate) struct ApiKeys {
+    keys: Vec<ApiKey>,
+    rotation: KeyRotation,
+    next: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct ApiKey {
+    key: String,
+    throttle: Mutex<Throttle>,
+}
+
+#[derive(Debug, Default)]
+struct Throttle {
+    last_throttled: Option<Instant>,
+    throttled_until: Option<Instant>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: Vec<String>, rotation: KeyRotation) -> Self {
+        let keys = keys.into_iter().map(|key| ApiKey { key, throttle: Mutex::default() }).collect();
+        Self { keys, rotation, next: AtomicUsize::new(0) }
+    }
+
+    /// The first key, which usage is attributed to when the serving key isn't known.
+    pub fn primary(&self) -> Option<&str> {
+        self.keys.first().map(|key| key.key.as_str())
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.keys.get(index).map(|key| key.key.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// The index and key to use next, or `None` if requests aren't authenticated.
+    pub fn select(&self, now: Instant) -> Option<(usize, &str)> {
+        match self.keys.len() {
+            0 => return None,
+            1 => return Some((0, &self.keys[0].key)),
+            _ => {}
+        }
+        let throttles: Vec<_> = self.keys.iter().map(|key| key.throttle.lock().unwrap()).collect();
+        let available = |i: &usize| throttles[*i].throttled_until.is_none_or(|until| until <= now);
+        let index = match self.rotation {
+            KeyRotation::RoundRobin => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..self.keys.len()).map(|offset| (start + offset) % self.keys.len()).find(available)
+            }
+            KeyRotation::LeastRecentlyThrottled => {
+                (0..self.keys.len()).filter(available).min_by_key(|&i| throttles[i].last_throttled)
+            }
+        };
+        // Every key is rate limited: use whichever resets soonest
+        let index = index.unwrap_or_else(|| (0..self.keys.len()).min_by_key(|&i| throttles[i].throttled_until).unwrap());
+        Some((index, &self.keys[index].key))
+    }
+
+    /// Skip the key until `until` after it was rate limited at `now`.
+    pub fn record_throttled(&self, index: usize, now: Instant, until: Instant) {
+        let mut throttle = self.keys[index].throttle.lock().unwrap();
+        throttle.last_throttled = Some(now);
+        throttle.throttled_until = Some(until);
+    }
+
+    /// Whether a key other than `index` isn't rate limited at `now`.
+    pub fn has_alternative(&self, index: usize, now: Instant) -> bool {
+        self.keys.iter().enumerate().any(|(i, key)| {
+            i != index && key.throttle.lock().unwrap().throttled_until.is_none_or(|until| until <= now)
+        })
+    }
+}
@@ -0,0 +1,94 @@
+/// Languages told apart by their most common words. Others are only recognized by script.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "of", "to", "is", "in", "that", "it", "with", "for", "was", "on", "are", "this", "be"]),
+    ("es", &["el", "la", "de", "que", "y", "en", "los", "se", "del", "las", "por", "un", "una", "con", "es"]),
+    ("fr", &["le", "la", "de", "et", "les", "des", "est", "un", "une", "du", "que", "dans", "pour", "pas", "en"]),
+    ("de", &["der", "die", "und", "das", "ist", "nicht", "ein", "eine", "zu", "den", "mit", "von", "sich", "auf", "dem"]),
+    ("it", &["il", "di", "che", "e", "la", "un", "una", "per", "non", "sono", "del", "della", "con", "le", "gli"]),
+    ("pt", &["o", "a", "de", "que", "e", "do", "da", "em", "um", "uma", "para", "com", "não", "os", "as"]),
+    ("nl", &["de", "het", "een", "en", "van", "is", "dat", "niet", "op", "te", "zijn", "met", "voor", "ook", "maar"]),
+];
+
+/// Languages written in a script, and whether a character belongs to it.
+type Script = (&'static [&'static str], fn(char) -> bool);
+
+/// Languages written in each non-Latin script; the first is reported when text is in that script.
+const SCRIPTS: &[Script] = &[
+    (&["ru", "uk", "bg", "sr", "be", "mk", "kk"], |c| ('\u{0400}'..='\u{04FF}').contains(&c)),
+    (&["el"], |c| ('\u{0370}'..='\u{03FF}').contains(&c)),
+    (&["ar", "fa", "ur"], |c| ('\u{0600}'..='\u{06FF}').contains(&c)),
+    (&["he", "yi"], |c| ('\u{0590}'..='\u{05FF}').contains(&c)),
+    (&["hi", "mr", "ne"], |c| ('\u{0900}'..='\u{097F}').contains(&c)),
+    (&["th"], |c| ('\u{0E00}'..='\u{0E7F}').contains(&c)),
+    (&["ko"], |c| ('\u{AC00}'..='\u{D7AF}').contains(&c) || ('\u{1100}'..='\u{11FF}').contains(&c)),
+    (&["ja"], is_kana),
+    (&["zh"], |c| ('\u{4E00}'..='\u{9FFF}').contains(&c)),
+];
+
+fn is_kana(c: char) -> bool {
+    ('\u{3040}'..='\u{30FF}').contains(&c)
+}
+
+/// A best guess at the language (ISO 639-1 code) of `text`, or `None` if it is too short or
+/// ambiguous. Non-Latin scripts are recognized by script alone, so e.g. all Cyrillic text is `ru`;
+/// Latin-script text is matched against the common words of a handful of European languages.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() < 12 {
+        return None;
+    }
+
+    // Japanese mixes kana with Chinese characters, so kana anywhere means Japanese
+    if letters.iter().any(|c| is_kana(*c)) {
+        return Some("ja");
+    }
+    for (languages, in_script) in SCRIPTS {
+        if letters.iter().filter(|c| in_script(**c)).count() * 2 > letters.len() {
+            return Some(languages[0]);
+        }
+    }
+
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    if words.len() < 4 {
+        return None;
+    }
+    let mut scores: Vec<(&str, usize)> = STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| (*language, words.iter().filter(|w| stopwords.contains(&w.as_str())).count()))
+        .collect();
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    let (best, best_score) = scores[0];
+    let runner_up = scores[1].1;
+    // Require a single winner, and common words to make up a plausible share of the text
+    (best_score * 10 >= words.len() && best_score > runner_up).then_some(best)
+}
+
+/// If `text` is confidently in a language other than `target`, that language.
+pub(crate) fn language_mismatch(target: &str, text: &str) -> Option<&'static str> {
+    let detected = detect_language(text)?;
+    let same_script = SCRIPTS.iter().any(|(languages, _)| languages.contains(&target) && languages.contains(&detected));
+    (detected != target && !same_script).then_some(detected)
+}
+
+/// Every string in `value` long enough to judge, with its path, e.g. `chapters[1].summary`.
+pub(crate) fn string_values(value: &serde_json::Value, path: String, strings: &mut Vec<(String, String)>) {
+    match value {
+        serde_json::Value::String(s) if s.split_whitespace().count() >= 4 => strings.push((path, s.clone())),
+        serde_json::Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                string_values(item, format!("{path}[{i}]"), strings);
+            }
+        }
+        serde_json::Value::Object(object) => {
+            for (key, item) in object {
+                let path = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                string_values(item, path, strings);
+            }
+        }
+        _ => {}
+    }
+}
@@ -1,24 +1,290 @@
+// Without the client, the driver's helpers in otherwise schema-level modules go unused
+#![cfg_attr(not(feature = "openai"), allow(dead_code))]
+
+use std::collections::BTreeMap;
 use std::fmt;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 use derive_builder::Builder;
 use enum_as_inner::EnumAsInner;
-use reqwest::Client;
 use schemars::JsonSchema;
 use schemars::gen::SchemaSettings;
-use schemars::schema::{RootSchema, Schema, SchemaObject};
+use schemars::schema::{NumberValidation, RootSchema, Schema, SchemaObject};
 use schemars::visit::{Visitor, visit_root_schema, visit_schema, visit_schema_object};
 use serde::ser::SerializeMap;
 use serde::{Serialize, Deserialize, Serializer};
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+#[cfg(feature = "openai")]
+mod approval;
+mod argdiff;
+#[cfg(feature = "openai")]
+mod assistants;
+#[cfg(feature = "openai")]
+mod backend;
+#[cfg(feature = "openai")]
+mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "openai")]
+mod budget;
+#[cfg(feature = "openai")]
+mod cassette;
+mod canonical;
+#[cfg(feature = "openai")]
+mod choice;
+mod chunk;
+#[cfg(feature = "openai")]
+mod circuit;
+#[cfg(feature = "openai")]
+mod clarify;
+#[cfg(feature = "openai")]
+mod client;
+#[cfg(feature = "openai")]
+mod clock;
+#[cfg(feature = "openai")]
+mod concurrency;
+#[cfg(feature = "openai")]
+mod conversation;
+#[cfg(feature = "openai")]
+mod debugger;
+mod dedup;
+mod diagnostics;
+mod diff;
+#[cfg(feature = "openai")]
+mod driver;
+mod elide;
+#[cfg(feature = "openai")]
+mod embeddings;
+#[cfg(feature = "openai")]
+mod endpoints;
+pub mod error_codes;
+mod examples;
+mod extract;
+#[cfg(feature = "openai")]
+mod fallback;
+#[cfg(feature = "openai")]
+mod finetuning;
+#[cfg(feature = "gemini")]
+mod gemini;
+mod gemini_schema;
+mod heatmap;
+mod image;
+#[cfg(feature = "openai")]
+mod json_mode;
+#[cfg(feature = "openai")]
+mod keys;
+mod language;
+mod manifest;
+#[cfg(feature = "openai")]
+mod media;
+mod metrics;
+#[cfg(feature = "mistral")]
+mod mistral;
+#[cfg(feature = "openai")]
+mod mock;
+#[cfg(feature = "openai")]
+mod moderation;
+mod normalize;
+mod numeric;
+mod observer;
+#[cfg(feature = "openrouter")]
+mod openrouter;
+mod pinned;
+mod pricing;
+#[cfg(feature = "openai")]
+mod profile;
+#[cfg(feature = "openai")]
+mod random;
+#[cfg(feature = "openai")]
+mod ratelimit;
+#[cfg(feature = "realtime")]
+mod realtime;
+#[cfg(feature = "openai")]
+mod registry;
+#[cfg(feature = "openai")]
+mod response_cache;
+#[cfg(feature = "openai")]
+mod retry;
+mod schema_depth;
+#[cfg(feature = "openai")]
+mod semantic_cache;
+mod similarity;
+mod slo;
+#[cfg(feature = "openai")]
+mod shadow;
+#[cfg(feature = "openai")]
+mod spool;
+#[cfg(feature = "openai")]
+mod streaming;
+mod telemetry;
+mod thread_safety;
+#[cfg(feature = "openai")]
+mod trace;
+mod transcript;
+#[cfg(feature = "openai")]
+mod transcription;
+mod usage;
+mod validate;
+
+#[cfg(feature = "openai")]
+pub use approval::{Approval, ApprovalPolicy, ApprovalRequest, Approver};
+#[cfg(feature = "openai")]
+pub use assistants::{
+    Assistant, CreateAssistant, CreateAssistantBuilder, RequiredAction, Run, SubmitToolOutputs, Thread, ThreadMessage, ToolCall,
+};
+#[cfg(feature = "openai")]
+pub use backend::{ApiError, ChatBackend, ChatError};
+#[cfg(feature = "openai")]
+pub use batch::{Batch, BatchClient, BatchRequestCounts, BatchResult};
+#[cfg(feature = "openai")]
+pub use budget::{Budget, BudgetLimit};
+#[cfg(feature = "openai")]
+pub use cassette::{Cassette, CassetteMode};
+pub use canonical::{canonical_hash, canonical_json};
+#[cfg(feature = "openai")]
+pub use choice::ChoiceSelector;
+pub use chunk::{chunk_text, ChunkBoundary, ChunkPolicy};
+#[cfg(feature = "openai")]
+pub use circuit::{CircuitBreaker, CircuitBreakerBackend};
+#[cfg(feature = "openai")]
+pub use client::{ConfigError, OpenAIClient, OpenAIClientBuilder};
+#[cfg(feature = "openai")]
+pub use clock::{Clock, SimulatedClock, SystemClock};
+#[cfg(feature = "openai")]
+pub use concurrency::ConcurrencyGroups;
+#[cfg(feature = "openai")]
+pub use conversation::Conversation;
+#[cfg(feature = "openai")]
+pub use debugger::{Debugger, Snapshot};
+pub use dedup::DuplicateCallPolicy;
+pub use diagnostics::{add_diagnostics_observer, clear_diagnostics_observers, Diagnostic, StderrDiagnostics};
+pub use diff::{Change, EditDiff, EditHistory};
+pub use elide::{elide, DEFAULT_ECHO_LIMIT};
+pub use examples::{add_examples, example_for_schema, schema_examples, set_schema_examples};
+pub use extract::{extract_text, html_to_text, pdf_to_text, ExtractError};
+#[cfg(feature = "openai")]
+pub use fallback::FallbackBackend;
+#[cfg(feature = "openai")]
+pub use finetuning::{CreateFineTuningJob, CreateFineTuningJobBuilder, FileObject, FineTuningEvent, FineTuningJob};
+#[cfg(feature = "gemini")]
+pub use gemini::GeminiBackend;
+pub use gemini_schema::gemini_schema;
+pub use heatmap::{HeatmapSegment, SegmentKind, TokenHeatmap};
+pub use image::{image_data_url, image_file_data_url};
+#[cfg(feature = "openai")]
+pub use json_mode::drive_json;
+#[cfg(feature = "openai")]
+pub use keys::KeyRotation;
+pub use language::detect_language;
+pub use manifest::{AgentManifest, FunctionManifest};
+#[cfg(feature = "openai")]
+pub use media::GeneratedImage;
+pub use metrics::{external_call, ExternalCall, FunctionStats, SessionStats, StepTiming};
+#[cfg(feature = "openai")]
+pub use driver::{drive, drive_to_json, drive_with, drive_with_profile, ArgumentsDeltaCallback, DriveError, DriveOptions, DriveOptionsBuilder, GIVE_UP_FUNCTION};
+#[cfg(feature = "mistral")]
+pub use mistral::MistralBackend;
+#[cfg(feature = "openai")]
+pub use mock::MockBackend;
+#[cfg(feature = "openai")]
+pub use moderation::ModerationResult;
+pub use normalize::StringNormalization;
+pub use numeric::parse_arguments;
+pub use observer::Observer;
+#[cfg(feature = "openrouter")]
+pub use openrouter::{OpenRouterBackend, ProviderPreferences};
+pub use pinned::PinnedArgument;
+pub use pricing::{extend_global_pricing, global_pricing, set_global_pricing, ModelPrice, PricingTable};
+#[cfg(feature = "openai")]
+pub use profile::{Profile, ProfileBuilder};
+#[cfg(feature = "openai")]
+pub use random::{RandomSource, SeededRandom, ThreadRandom};
+#[cfg(feature = "openai")]
+pub use ratelimit::RateLimiter;
+#[cfg(feature = "realtime")]
+pub use realtime::{drive_realtime, RealtimeError, RealtimeEvent, RealtimeSender, RealtimeSession};
+#[cfg(feature = "openai")]
+pub use registry::{FunctionRegistry, NameCollision, RegisteredFunction};
+#[cfg(feature = "openai")]
+pub use response_cache::{CacheStore, DiskCacheStore, ResponseCache};
+#[cfg(feature = "openai")]
+pub use retry::RetryPolicy;
+pub use schema_depth::{limit_schema_depth, max_schema_depth, set_max_schema_depth};
+#[cfg(feature = "openai")]
+pub use semantic_cache::{Embedder, OpenAIEmbedder, SemanticCache};
+#[cfg(feature = "openai")]
+pub use shadow::{ShadowAgreement, ShadowBackend, ShadowLog, ShadowRecord};
+pub use similarity::{closest_match, cosine_similarity, levenshtein, similarity};
+pub use slo::{SloPolicy, SloViolation};
+#[cfg(feature = "openai")]
+pub use spool::SpooledResponse;
+#[cfg(feature = "openai")]
+pub use streaming::{ArgumentsDelta, OnArgumentsDelta};
+pub use telemetry::{Metric, MetricsSink, LATENCY_BUCKETS};
+#[cfg(feature = "opentelemetry")]
+pub use telemetry::OtelMetrics;
+#[cfg(feature = "prometheus")]
+pub use telemetry::PrometheusMetrics;
+pub use transcript::{PromptRedaction, RedactionProfile, Transcript, TranscriptEntry};
+#[cfg(feature = "openai")]
+pub use transcription::{Transcription, TranscriptionSegment};
+pub use usage::{mask_key, Attribution, SessionUsage, UsageRecord, UsageTotals};
+pub use validate::validate_arguments;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub enum Model {
     #[serde(rename = "gpt-3.5-turbo-0613")]
     Gpt3p5Turbo,
     #[serde(rename = "gpt-4-0613")]
     Gpt4,
+    /// Any other model name, e.g. `llama3` on an Ollama server
+    #[serde(untagged)]
+    Custom(String),
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+impl Model {
+    /// The model name sent on the wire.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Model::Gpt3p5Turbo => "gpt-3.5-turbo-0613",
+            Model::Gpt4 => "gpt-4-0613",
+            Model::Custom(name) => name,
+        }
+    }
+
+    pub fn custom(name: impl Into<String>) -> Self {
+        Model::Custom(name.into())
+    }
+
+    /// Which optional request parameters the model accepts. Custom models are assumed to accept
+    /// everything, except OpenAI's reasoning models (`o1`, `o3`, ...), which reject `temperature`.
+    pub fn capabilities(&self) -> ModelCapabilities {
+        match self {
+            Model::Custom(name) if is_reasoning_model(name) => ModelCapabilities { temperature: false, sampling: false },
+            _ => ModelCapabilities::default(),
+        }
+    }
+}
+
+fn is_reasoning_model(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Optional request parameters a model accepts; unsupported ones are stripped before sending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    pub temperature: bool,
+    /// `top_p`, `presence_penalty`, `frequency_penalty`, and `logit_bias`
+    pub sampling: bool,
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self { temperature: true, sampling: true }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "camelCase")]
 pub enum Role {
     User,
@@ -27,7 +293,7 @@ pub enum Role {
     System,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct Message {
     pub role: String,
     #[serde(default)]
@@ -35,6 +301,14 @@ pub struct Message {
     pub content: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub function_call: Option<CalledFunction>,
+    /// IDs of uploaded files (see `OpenAIClient::upload_document`) attached to this message, for
+    /// providers that accept file inputs. Sent as `file` content parts alongside the text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    /// Image URLs (`https:` or `data:`, see `image_data_url`) attached to this message, for
+    /// vision-capable models. Sent as `image_url` content parts alongside the text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
 }
 
 impl Message {
@@ -43,30 +317,97 @@ impl Message {
             role: "assistant".to_string(),
             content: Some(serde_json::to_string(&self.function_call.unwrap()).unwrap()),
             function_call: None,
+            files: vec![],
+            images: vec![],
+        }
+    }
+
+    pub fn system(content: impl fmt::Display) -> Self {
+        Self {
+            role: "system".to_string(),
+            content: Some(content.to_string()),
+            function_call: None,
+            files: vec![],
+            images: vec![],
         }
     }
 
     pub fn user(content: impl fmt::Display) -> Self {
-        Self { role: "user".to_string(), content: Some(content.to_string()), function_call: None }
+        Self {
+            role: "user".to_string(),
+            content: Some(content.to_string()),
+            function_call: None,
+            files: vec![],
+            images: vec![],
+        }
+    }
+
+    pub fn with_files(mut self, files: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.files.extend(files.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_images(mut self, images: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.images.extend(images.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether the message has attachments, which are sent as content parts.
+    pub fn has_attachments(&self) -> bool {
+        !self.files.is_empty() || !self.images.is_empty()
+    }
+
+    #[cfg(feature = "openai")]
+    /// The message in OpenAI's wire format, where attached files and images turn the content into
+    /// an array of text, `file`, and `image_url` parts.
+    pub(crate) fn to_wire(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap();
+        if let Some(object) = value.as_object_mut() {
+            object.remove("files");
+            object.remove("images");
+            if self.has_attachments() {
+                let mut parts = vec![];
+                if let Some(text) = &self.content {
+                    parts.push(serde_json::json!({ "type": "text", "text": text }));
+                }
+                parts.extend(self.files.iter().map(|id| serde_json::json!({ "type": "file", "file": { "file_id": id } })));
+                parts.extend(self.images.iter().map(|url| serde_json::json!({ "type": "image_url", "image_url": { "url": url } })));
+                object.insert("content".to_string(), parts.into());
+            }
+        }
+        value
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct CalledFunction {
     pub name: String,
+    #[serde(deserialize_with = "deserialize_arguments")]
     pub arguments: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// OpenAI sends arguments as a JSON-encoded string, but some OpenAI-compatible servers (e.g. Ollama)
+/// send the JSON object itself, so accept both.
+fn deserialize_arguments<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(arguments) => Ok(arguments),
+        arguments => Ok(arguments.to_string()),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct Function {
     pub name: String,
     pub description: String,
     pub parameters: serde_json::Value,
 }
 
-#[derive(Clone, EnumAsInner)]
+#[derive(Debug, Clone, PartialEq, Eq, EnumAsInner)]
 pub enum FunctionCall {
+    /// The model may call any offered function, or reply with text instead
     Auto,
+    /// The model must not call a function
+    None,
     Exact {
         name: String,
     }
@@ -76,6 +417,7 @@ impl Serialize for FunctionCall {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         match self {
             FunctionCall::Auto => serializer.serialize_str("auto"),
+            FunctionCall::None => serializer.serialize_str("none"),
             FunctionCall::Exact { name } => {
                 let mut map = serializer.serialize_map(Some(1))?;
                 map.serialize_entry("name", name)?;
@@ -85,7 +427,26 @@ impl Serialize for FunctionCall {
     }
 }
 
-#[derive(Serialize, Builder)]
+impl<'de> Deserialize<'de> for FunctionCall {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Wire {
+            Mode(String),
+            Exact { name: String },
+        }
+
+        match Wire::deserialize(deserializer)? {
+            Wire::Mode(mode) if mode == "auto" => Ok(FunctionCall::Auto),
+            Wire::Mode(mode) if mode == "none" => Ok(FunctionCall::None),
+            Wire::Mode(mode) => Err(serde::de::Error::unknown_variant(&mode, &["auto", "none"])),
+            Wire::Exact { name } => Ok(FunctionCall::Exact { name }),
+        }
+    }
+}
+
+// PartialEq only: the f32 sampling parameters rule out Eq. Use `cache_key` for hashing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Builder)]
 #[builder(setter(into))]
 pub struct ChatCompletionRequest {
     pub model: Model,
@@ -95,89 +456,256 @@ pub struct ChatCompletionRequest {
     pub functions: Option<Vec<Function>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub function_call: Option<FunctionCall>,
+    /// `None` leaves it to the provider's default; some models reject the parameter entirely
     #[builder(default)]
-    pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<i32>,
+    /// Nucleus sampling: only tokens within this top probability mass are considered
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// -2.0 to 2.0; positive values penalize tokens that have appeared at all, encouraging new topics
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    /// -2.0 to 2.0; positive values penalize tokens by how often they've appeared, discouraging repetition
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    /// Up to 4 sequences at which generation stops
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    /// Token ID to a bias from -100 (ban) to 100 (force) added to its logit
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<BTreeMap<u32, f32>>,
+    /// How many choices to generate
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    /// Ask the provider to sample deterministically, so repeated requests with the same seed and
+    /// parameters mostly return the same result. Compare `ChatCompletionResponse::system_fingerprint`
+    /// to tell when the backend changed underneath.
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// Constrain the reply's format, e.g. `ResponseFormat::JsonObject` for a JSON reply without
+    /// function calling
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// Return the log probability of each output token, in `Choice::logprobs`
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    /// How many of the most likely alternatives (0 to 20) to return at each token. Requires `logprobs`.
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u32>,
+    /// Timeout for each HTTP attempt at this request, overriding the client's. Not sent to the API.
+    #[builder(default)]
+    #[serde(skip)]
+    pub timeout: Option<std::time::Duration>,
+    /// Headers sent with this request in addition to the client's (e.g. tracing headers)
+    #[builder(default)]
+    #[serde(skip)]
+    pub extra_headers: Vec<(String, String)>,
+    /// Query parameters added to this request's URL in addition to the client's
+    #[builder(default)]
+    #[serde(skip)]
+    pub extra_query: Vec<(String, String)>,
+}
+
+impl ChatCompletionRequest {
+    /// JSON with keys sorted at every level (including inside function schemas), so equal requests
+    /// serialize identically for snapshots and cassettes.
+    pub fn canonical_json(&self) -> String {
+        canonical_json(self)
+    }
+
+    /// A stable hash of the canonical JSON, for cache keys.
+    pub fn cache_key(&self) -> String {
+        canonical_hash(self)
+    }
+
+    /// A copy of the request without any parameters its model doesn't accept.
+    pub fn for_model_capabilities(&self) -> std::borrow::Cow<'_, Self> {
+        let capabilities = self.model.capabilities();
+        let strip_temperature = !capabilities.temperature && self.temperature.is_some();
+        let strip_sampling = !capabilities.sampling
+            && (self.top_p.is_some() || self.presence_penalty.is_some() || self.frequency_penalty.is_some() || self.logit_bias.is_some());
+        if !strip_temperature && !strip_sampling {
+            return std::borrow::Cow::Borrowed(self);
+        }
+        let mut req = self.clone();
+        if strip_temperature {
+            req.temperature = None;
+        }
+        if strip_sampling {
+            req.top_p = None;
+            req.presence_penalty = None;
+            req.frequency_penalty = None;
+            req.logit_bias = None;
+        }
+        std::borrow::Cow::Owned(req)
+    }
+}
+
+/// The format a model must reply in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    /// Any valid JSON object. The messages must ask for JSON, or providers reject the request.
+    JsonObject,
 }
 
-#[derive(Debug, Deserialize)]
+// Defaults on response fields let servers that only approximate the OpenAI format (llama.cpp, vLLM,
+// Ollama) still deserialize
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Choice {
+    #[serde(default)]
     pub index: i32,
     pub message: Message,
+    #[serde(default)]
     pub finish_reason: String,
+    /// Present when requested with `ChatCompletionRequest::logprobs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Logprobs>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Logprobs {
+    /// One entry per token of the message content
+    #[serde(default)]
+    pub content: Option<Vec<TokenLogprob>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    /// Natural log of the token's probability
+    pub logprob: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_logprobs: Vec<TopLogprob>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// One of the most likely tokens at a position, whether or not it was chosen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f64,
+}
+
+impl Logprobs {
+    pub fn tokens(&self) -> &[TokenLogprob] {
+        self.content.as_deref().unwrap_or_default()
+    }
+
+    /// The probability of the whole sequence of tokens: how confident the model was in this exact
+    /// reply. For a classification-style reply (one short label), a low value flags an uncertain call.
+    pub fn probability(&self) -> f64 {
+        self.tokens().iter().map(|token| token.logprob).sum::<f64>().exp()
+    }
+
+    /// The lowest probability of any single token, i.e. the model's least certain decision.
+    pub fn min_token_probability(&self) -> Option<f64> {
+        self.tokens().iter().map(|token| token.logprob.exp()).min_by(f64::total_cmp)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Usage {
     pub prompt_tokens: i32,
     pub completion_tokens: i32,
     pub total_tokens: i32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChatCompletionResponse {
+    #[serde(default)]
     pub created: u64,
+    #[serde(default)]
     pub model: String,
     pub choices: Vec<Choice>,
+    #[serde(default)]
     pub usage: Usage,
+    /// Identifies the backend configuration that served the response; a change means results for
+    /// the same `seed` may differ
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_fingerprint: Option<String>,
+    /// Filled in by the backend that served the response
+    #[serde(skip)]
+    pub attribution: Option<Attribution>,
 }
 
-pub struct OpenAIClient {
-    client: Client,
-    api_key: String,
+/// Dialect of JSON schema produced by `schema_with_mode`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaMode {
+    /// JSON schema as accepted by OpenAI function calling
+    #[default]
+    OpenAi,
+    /// The OpenAPI subset accepted by Gemini `function_declarations`
+    Gemini,
 }
 
-impl OpenAIClient {
-    pub fn new() -> Self {
-        let api_key = std::env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
-        Self {
-            client: Client::new(),
-            api_key: api_key.to_string(),
-        }
+pub fn schema_with_mode<T: JsonSchema>(mode: SchemaMode) -> serde_json::Value {
+    match mode {
+        SchemaMode::OpenAi => schema::<T>(),
+        SchemaMode::Gemini => gemini_schema(&schema::<T>()),
     }
+}
 
-    pub async fn chat_completion(
-        &self,
-        req: &ChatCompletionRequest,
-    ) -> Result<ChatCompletionResponse, reqwest::Error> {
-    
-        let mut wait_time = Duration::from_secs(1); // Initial wait time of 1 second
-        let max_wait_time = Duration::from_secs(60); // Maximum wait time of 60 seconds
-    
-        loop {
-            let res = self
-                .client
-                .post("https://api.openai.com/v1/chat/completions")
-                .header("Authorization", format!("Bearer {}", self.api_key))
-                .json(req)
-                .send()
-                .await?;
-
-            match res.status() {
-                reqwest::StatusCode::TOO_MANY_REQUESTS => {
-                    if wait_time < max_wait_time {
-                        eprint!("Too many requests, waiting {:?}...", wait_time);
-                        tokio::time::sleep(wait_time).await;
-                        wait_time *= 2; // Double the wait time for the next loop
-                    } else {
-                        panic!("Exceeded max wait time");
-                    }
-                }
-                _ => {
+/// Order of object keys in schemas produced by `schema`, which is also their order on the wire.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaOrdering {
+    /// Properties in the order fields are declared, which is the order the model tends to fill them
+    #[default]
+    Declaration,
+    /// Every object's keys sorted, so schemas stay byte-identical when fields are reordered
+    Sorted,
+}
 
-                    let body = res.text().await.unwrap();
+static SORTED_SCHEMAS: AtomicBool = AtomicBool::new(false);
 
-                    return Ok(serde_json::from_str::<ChatCompletionResponse>(&body).unwrap());
-                }
-            }
-        }
-    }
+/// Set the ordering used by `schema` (and so by every `#[ai_function]`) from now on.
+pub fn set_schema_ordering(ordering: SchemaOrdering) {
+    SORTED_SCHEMAS.store(ordering == SchemaOrdering::Sorted, AtomicOrdering::Relaxed);
 }
 
+pub fn schema_ordering() -> SchemaOrdering {
+    match SORTED_SCHEMAS.load(AtomicOrdering::Relaxed) {
+        true => SchemaOrdering::Sorted,
+        false => SchemaOrdering::Declaration,
+    }
+}
 
+/// The schema for `T` with keys in the global `schema_ordering`, limited to the global
+/// `max_schema_depth` if one is set.
 pub fn schema<T: JsonSchema>() -> serde_json::Value {
+    schema_with_depth::<T>(max_schema_depth())
+}
+
+/// The schema for `T` with keys in the global `schema_ordering`, limited to `max_depth` levels of
+/// nesting (see `limit_schema_depth`) rather than the global `max_schema_depth`.
+pub fn schema_with_depth<T: JsonSchema>(max_depth: Option<usize>) -> serde_json::Value {
+    let value = schema_with_ordering::<T>(SchemaOrdering::Declaration);
+    let value = match max_depth {
+        Some(depth) => limit_schema_depth(&value, depth),
+        None => value,
+    };
+    match schema_ordering() {
+        SchemaOrdering::Declaration => value,
+        SchemaOrdering::Sorted => canonical::sort_keys(value),
+    }
+}
+
+pub fn schema_with_ordering<T: JsonSchema>(ordering: SchemaOrdering) -> serde_json::Value {
 
     #[derive(Debug, Clone)]    
     struct MyVisitor;
@@ -193,8 +721,13 @@ pub fn schema<T: JsonSchema>() -> serde_json::Value {
         }
     
         fn visit_schema_object(&mut self, schema: &mut SchemaObject) {
+            // Integers keep their range (e.g. 0 to 255 for u8, from 1 for NonZeroU32)
+            let minimum = schema.number.as_ref().and_then(|number| number.minimum);
+            let bounds = schema.format.as_deref().and_then(numeric::integer_bounds);
+            schema.number = bounds.map(|(min, maximum)| {
+                Box::new(NumberValidation { minimum: minimum.or(min), maximum, ..Default::default() })
+            });
             schema.format = None;
-            schema.number = None;
             if let Some(enums) = &schema.enum_values {
                 if enums.len() == 1 {
                     schema.const_value = Some(enums[0].clone());
@@ -216,7 +749,10 @@ pub fn schema<T: JsonSchema>() -> serde_json::Value {
     let schema = gen.into_root_schema_for::<T>();
     let mut value = serde_json::to_value(&schema).unwrap();
     value.as_object_mut().unwrap().remove("title");
-    value
+    match ordering {
+        SchemaOrdering::Declaration => value,
+        SchemaOrdering::Sorted => canonical::sort_keys(value),
+    }
 }
 
 #[derive(Debug)]
@@ -242,9 +778,47 @@ impl From<serde_json::Error> for AiFunctionError {
 pub enum AiFunctionResponse {
     Done,
     Prompt {
-        temperature: f32,
+        /// `None` sends no temperature at all
+        temperature: Option<f32>,
         prompt: String,
         functions: Vec<String>,
+        pinned: Vec<PinnedArgument>,
+        /// Boxed, since it's large and usually left at its default
+        options: Box<PromptOptions>,
+    }
+}
+
+/// Less common settings for a prompt, given as trailing `key = value` pairs in `prompt!`, e.g.
+/// `prompt!(0.5, "..." => [research], conversation = "research")`.
+#[derive(Debug, Clone, Default)]
+pub struct PromptOptions {
+    /// Run the prompt in this named conversation, continuing its history. Prompts without one start
+    /// from an empty history. Moving back to an outer conversation summarizes the nested one into it.
+    pub conversation: Option<String>,
+    /// Override how the model is asked to call functions. By default a prompt offering one function
+    /// requires calling it (`Exact`) and one offering several uses `Auto`. With `Auto` or `None` the
+    /// model may reply with text, which is passed to `AiInitialState::text_reply`.
+    pub function_call: Option<FunctionCall>,
+    /// IDs of uploaded files to attach to the prompt, e.g. `files = vec![report.id.clone()]`
+    pub files: Option<Vec<String>>,
+    /// Image URLs to attach to the prompt, e.g. `images = vec![image_file_data_url("shot.png")?]`
+    pub images: Option<Vec<String>>,
+    /// See the fields of the same names on `ChatCompletionRequest`, e.g. `top_p = 0.9`
+    pub top_p: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub frequency_penalty: Option<f32>,
+    pub stop: Option<Vec<String>>,
+    pub logit_bias: Option<BTreeMap<u32, f32>>,
+}
+
+/// Conversion used by `prompt!` to set `PromptOptions` fields from plain values.
+pub trait IntoPromptOption<T> {
+    fn into_prompt_option(self) -> T;
+}
+
+impl<T, U: Into<T>> IntoPromptOption<Option<T>> for U {
+    fn into_prompt_option(self) -> Option<T> {
+        Some(self.into())
     }
 }
 
@@ -252,76 +826,103 @@ pub type AiFunctionResult = Result<AiFunctionResponse, AiFunctionError>;
 
 pub trait AiInitialState {
     fn initial(&mut self) -> AiFunctionResponse;
+
+    /// Called when the model replies with text instead of calling a function. By default the model
+    /// is told to call one of the provided functions.
+    fn text_reply(&mut self, _content: &str) -> AiFunctionResult {
+        recoverable_err("You must call one of the provided functions")
+    }
 }
 
 pub trait AiState : AiInitialState {
+    /// Machine-readable result of a finished run, produced by the `#[ai_output]` method
+    /// (or `()` if the impl has none).
+    type Output: Serialize;
+
     fn json_schema_for_function(function_name: &str) -> Option<Function>;
+    /// The `concurrency_group` declared in the function's `#[ai_function]` attribute, if any. Runs
+    /// sharing a `ConcurrencyGroups` never run two functions of the same group at once.
+    fn concurrency_group(_function_name: &str) -> Option<&'static str> {
+        None
+    }
+    /// The `weight` declared in the function's `#[ai_function]` attribute, or 0. Offered functions
+    /// are presented to the model in descending weight, ties keeping the order the prompt listed.
+    fn function_weight(_function_name: &str) -> i32 {
+        0
+    }
+    /// Functions marked `#[ai_function(escape_hatch)]`, e.g. `give_up` or `ask_user`, which are
+    /// offered alongside every prompt's own functions so each step has a way out.
+    fn escape_hatches() -> &'static [&'static str] {
+        &[]
+    }
+    /// `(alias, parameter)` pairs declared with `#[ai_function(arg_alias(...))]`. The driver renames
+    /// aliased keys to the parameter's name before validating arguments.
+    fn argument_aliases(_function_name: &str) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+    /// The agent's functions, their attributes, and the state graph between them.
+    fn manifest() -> AgentManifest {
+        AgentManifest::empty(std::any::type_name::<Self>())
+    }
     fn call_function(&mut self, function_name: &str, arg: &str) -> AiFunctionResult;
+    fn output(&self) -> Self::Output;
 }
 
+/// The object-safe face of `AiState`, implemented for every `AiState`, so agents of different types
+/// chosen at runtime can be held as `Box<dyn DynAiState>` and driven with `drive_with`. The static
+/// lookups become methods and the output is serialized to JSON.
+pub trait DynAiState: AiInitialState {
+    /// The concrete state's type name, for tracing.
+    fn state_name(&self) -> &'static str;
+    fn function_schema(&self, function_name: &str) -> Option<Function>;
+    fn function_concurrency_group(&self, function_name: &str) -> Option<&'static str>;
+    fn function_weight_of(&self, function_name: &str) -> i32;
+    fn escape_hatch_functions(&self) -> &'static [&'static str];
+    fn function_aliases(&self, function_name: &str) -> &'static [(&'static str, &'static str)];
+    fn agent_manifest(&self) -> AgentManifest;
+    fn call(&mut self, function_name: &str, arg: &str) -> AiFunctionResult;
+    fn output_json(&self) -> Result<serde_json::Value, serde_json::Error>;
+}
 
-pub async fn drive<S: AiState>(state: &mut S) -> Result<(), String> {
-    let mut next_prompt = state.initial();
-
-    let client = OpenAIClient::new();
-
-    'next: loop {
-        match next_prompt {
-            AiFunctionResponse::Done => return Ok(()),
-            AiFunctionResponse::Prompt { temperature, prompt, functions } => {
-
-                let mut messages = vec![Message::user(prompt)];
-
-                let functions: Vec<_> = functions
-                    .into_iter()
-                    .map(|f| S::json_schema_for_function(&f).unwrap())
-                    .collect();
-
-                let function_call = if functions.len() == 1 {
-                    FunctionCall::Exact { name: functions[0].name.clone() }
-                } else {
-                    FunctionCall::Auto
-                };
-
-                for _ in 0..5 {
-                    let request = ChatCompletionRequestBuilder::default()
-                        .model(Model::Gpt3p5Turbo)
-                        .messages(messages.clone())
-                        .functions(functions.clone())
-                        .function_call(function_call.clone())
-                        .temperature(temperature)
-                        .build()
-                        .unwrap();
-
-                    let response = client.chat_completion(&request).await.unwrap();
-                    let message = response.choices[0].message.clone();
-                    messages.push(message.clone().function_to_content());
-                    match message.function_call {
-                        None => {
-                            messages.push(Message::user("You must call one of the provided functions"));
-                        },
-                        Some(CalledFunction { name, arguments }) => {
-                            match state.call_function(&name, &arguments) {
-                                Ok(next) => {
-                                    next_prompt = next;
-                                    continue 'next;
-                                }
-                                Err(AiFunctionError::Recoverable(e)) => {
-                                    messages.push(Message::user(format!("Error: {}", e)));
-                                },
-                                Err(AiFunctionError::Unrecoverable(e)) => {
-                                    return Err(e);
-                                }
-                            }
-                        }
-                    }
-                }
-                return Err("Too many errors".to_string());
-            }
-        }
+impl<S: AiState> DynAiState for S {
+    fn state_name(&self) -> &'static str {
+        std::any::type_name::<S>()
+    }
+
+    fn function_schema(&self, function_name: &str) -> Option<Function> {
+        S::json_schema_for_function(function_name)
+    }
+
+    fn function_concurrency_group(&self, function_name: &str) -> Option<&'static str> {
+        S::concurrency_group(function_name)
+    }
+
+    fn function_weight_of(&self, function_name: &str) -> i32 {
+        S::function_weight(function_name)
+    }
+
+    fn escape_hatch_functions(&self) -> &'static [&'static str] {
+        S::escape_hatches()
+    }
+
+    fn function_aliases(&self, function_name: &str) -> &'static [(&'static str, &'static str)] {
+        S::argument_aliases(function_name)
+    }
+
+    fn agent_manifest(&self) -> AgentManifest {
+        S::manifest()
+    }
+
+    fn call(&mut self, function_name: &str, arg: &str) -> AiFunctionResult {
+        self.call_function(function_name, arg)
+    }
+
+    fn output_json(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(self.output())
     }
 }
 
+
 pub trait IntoOk<T> {
     fn into_ok(self) -> T;
 }
@@ -340,18 +941,27 @@ impl IntoOk<AiFunctionResponse> for AiFunctionResponse {
 
 #[macro_export]
 macro_rules! prompt {
-    ($temp:literal, $prompt:literal => [$($fns:ident),*]) => {{
+    ($prompt:literal => [$($fns:ident $(($($arg:ident = $val:expr),*))?),*] $(, $option:ident = $option_value:expr)*) => {
+        prompt!(0.0, $prompt => [$($fns $(($($arg = $val),*))?),*] $(, $option = $option_value)*)
+    };
+
+    // The temperature may be `None` for models that don't accept one
+    ($temp:expr, $prompt:literal => [$($fns:ident $(($($arg:ident = $val:expr),*))?),*] $(, $option:ident = $option_value:expr)*) => {{
         // Verify that the functions exist
         $(let _ = Self::$fns;)*
+        #[allow(unused_mut)]
+        let mut pinned = vec![];
+        $($($(pinned.push($crate::PinnedArgument::new(stringify!($fns), stringify!($arg), $val));)*)?)*
+        #[allow(unused_mut)]
+        let mut options = $crate::PromptOptions::default();
+        $(options.$option = $crate::IntoPromptOption::into_prompt_option($option_value);)*
         let response = $crate::AiFunctionResponse::Prompt {
-            temperature: $temp,
+            temperature: ::core::convert::Into::<Option<f32>>::into($temp),
             prompt: format!($prompt),
             functions: vec![$(stringify!($fns).to_string()),*],
+            pinned,
+            options: ::std::boxed::Box::new(options),
         };
         $crate::IntoOk::into_ok(response)
     }};
-
-    ($prompt:literal => [$($fns:ident),*]) => {
-        prompt!(0.0, $prompt => [$($fns),*])
-    }
 }
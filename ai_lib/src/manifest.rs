@@ -0,0 +1,58 @@
+use serde::Serialize;
+
+use crate::Function;
+
+/// Everything an agent can do, as one JSON document for catalogs, tool pickers, and compliance
+/// reviews. Generated by `#[ai_functions]` as `AiState::manifest`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgentManifest {
+    /// The state's type name
+    pub agent: String,
+    pub functions: Vec<FunctionManifest>,
+}
+
+/// One `#[ai_function]`: its declaration as offered to the model plus its attributes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionManifest {
+    #[serde(flatten)]
+    pub function: Function,
+    /// Declared with `#[ai_function(tags("network", ...))]`
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency_group: Option<String>,
+    pub weight: i32,
+    pub escape_hatch: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    /// Functions offered by the `prompt!`s in this function's body: its edges in the state graph.
+    /// Prompts built elsewhere (e.g. in a helper method) aren't seen.
+    pub next: Vec<String>,
+}
+
+impl AgentManifest {
+    /// An agent with no declared functions.
+    pub fn empty(agent: impl Into<String>) -> Self {
+        Self { agent: agent.into(), functions: vec![] }
+    }
+
+    pub fn function(&self, name: &str) -> Option<&FunctionManifest> {
+        self.functions.iter().find(|function| function.function.name == name)
+    }
+
+    /// Functions carrying `tag`, e.g. everything marked `payments` for a review.
+    pub fn tagged(&self, tag: &str) -> Vec<&FunctionManifest> {
+        self.functions.iter().filter(|function| function.tags.iter().any(|t| t == tag)).collect()
+    }
+
+    /// The state graph as `(from, to)` pairs.
+    pub fn edges(&self) -> Vec<(&str, &str)> {
+        self.functions
+            .iter()
+            .flat_map(|function| function.next.iter().map(|next| (function.function.name.as_str(), next.as_str())))
+            .collect()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
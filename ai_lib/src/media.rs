@@ -0,0 +1,53 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{ChatError, OpenAIClient};
+
+/// Model used by `generate_image`
+const IMAGE_MODEL: &str = "gpt-image-1";
+/// Model used by `speech`
+const SPEECH_MODEL: &str = "gpt-4o-mini-tts";
+
+/// An image produced by `generate_image`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeneratedImage {
+    /// The image, base64-encoded
+    #[serde(default)]
+    pub b64_json: Option<String>,
+    /// Where to download the image, for models that host it instead of returning it inline
+    #[serde(default)]
+    pub url: Option<String>,
+    /// The prompt the model actually drew from, if it rewrote the one given
+    #[serde(default)]
+    pub revised_prompt: Option<String>,
+}
+
+impl GeneratedImage {
+    /// The decoded image, if it was returned inline.
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        base64::engine::general_purpose::STANDARD.decode(self.b64_json.as_ref()?).ok()
+    }
+}
+
+#[derive(Deserialize)]
+struct ImageList {
+    data: Vec<GeneratedImage>,
+}
+
+impl OpenAIClient {
+    /// Generate an image from `prompt` at `size` (e.g. `1024x1024`), such as an illustration for a
+    /// story. Retried like chat completions per the client's `RetryPolicy`.
+    pub async fn generate_image(&self, prompt: &str, size: &str) -> Result<GeneratedImage, ChatError> {
+        let body = json!({ "model": IMAGE_MODEL, "prompt": prompt, "size": size, "n": 1 });
+        let list: ImageList = self.post_json("images/generations", &body, IMAGE_MODEL).await?;
+        list.data.into_iter().next().ok_or_else(|| ChatError::Other("Empty image generation response".to_string()))
+    }
+
+    /// Read `input` aloud in `voice` (e.g. `alloy`), returning MP3 audio. Retried like chat
+    /// completions.
+    pub async fn speech(&self, input: &str, voice: &str) -> Result<Vec<u8>, ChatError> {
+        let body = json!({ "model": SPEECH_MODEL, "input": input, "voice": voice, "response_format": "mp3" });
+        self.post_bytes("audio/speech", &body, SPEECH_MODEL).await
+    }
+}
@@ -0,0 +1,134 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Time spent in one external call (an HTTP request, a database query, ...) made by a function,
+/// as recorded with `external_call`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExternalCall {
+    pub name: String,
+    pub duration: Duration,
+}
+
+/// Timing of one driver step: waiting for the model, then running the state's own code on its reply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StepTiming {
+    /// The function called, or `None` for a text reply
+    pub function: Option<String>,
+    /// Time waiting for the chat completion, including the backend's retries
+    pub model_latency: Duration,
+    /// Wall time of `call_function` (or `text_reply`), including its external calls
+    pub execution: Duration,
+    pub external_calls: Vec<ExternalCall>,
+    /// Whether the function accepted the call
+    pub succeeded: bool,
+}
+
+impl StepTiming {
+    /// Execution time not accounted for by external calls.
+    pub fn own_time(&self) -> Duration {
+        self.execution.saturating_sub(self.external_calls.iter().map(|call| call.duration).sum())
+    }
+}
+
+/// Aggregate timings of one function over a session.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FunctionStats {
+    pub calls: u64,
+    pub failures: u64,
+    pub execution: Duration,
+    pub max_execution: Duration,
+    pub external: Duration,
+    /// Time spent waiting for the model on the steps that called this function
+    pub model_latency: Duration,
+}
+
+/// Step timings accumulated over a session, to tell whether slowness comes from the model or from
+/// the state's own function implementations. Cheap to clone; clones share the same records, so one
+/// can be handed to the driver and another kept for reporting.
+#[derive(Debug, Clone, Default)]
+pub struct SessionStats {
+    steps: Arc<Mutex<Vec<StepTiming>>>,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, step: StepTiming) {
+        self.steps.lock().unwrap().push(step);
+    }
+
+    pub fn steps(&self) -> Vec<StepTiming> {
+        self.steps.lock().unwrap().clone()
+    }
+
+    /// Total time waiting for the model.
+    pub fn model_latency(&self) -> Duration {
+        self.steps.lock().unwrap().iter().map(|step| step.model_latency).sum()
+    }
+
+    /// Total time running functions, including their external calls.
+    pub fn execution(&self) -> Duration {
+        self.steps.lock().unwrap().iter().map(|step| step.execution).sum()
+    }
+
+    /// Stats per function; text replies are grouped under `"(text reply)"`.
+    pub fn by_function(&self) -> BTreeMap<String, FunctionStats> {
+        let mut functions: BTreeMap<String, FunctionStats> = BTreeMap::new();
+        for step in self.steps.lock().unwrap().iter() {
+            let name = step.function.clone().unwrap_or_else(|| "(text reply)".to_string());
+            let stats = functions.entry(name).or_default();
+            stats.calls += 1;
+            stats.failures += u64::from(!step.succeeded);
+            stats.execution += step.execution;
+            stats.max_execution = stats.max_execution.max(step.execution);
+            stats.external += step.external_calls.iter().map(|call| call.duration).sum::<Duration>();
+            stats.model_latency += step.model_latency;
+        }
+        functions
+    }
+
+    /// A JSON report with session totals (in seconds) and per-function stats.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "model_latency_secs": self.model_latency().as_secs_f64(),
+            "execution_secs": self.execution().as_secs_f64(),
+            "by_function": self.by_function(),
+        })
+    }
+}
+
+thread_local! {
+    /// External calls made by the function currently being executed on this thread
+    static EXTERNAL_CALLS: RefCell<Option<Vec<ExternalCall>>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, accounting its wall time as an external call named `name` in the current step's
+/// `StepTiming`. Call it from inside an `#[ai_function]` around slow work that isn't the function's
+/// own computation; outside the driver it just runs `f`.
+pub fn external_call<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    let call = ExternalCall { name: name.to_string(), duration: start.elapsed() };
+    EXTERNAL_CALLS.with(|calls| {
+        if let Some(calls) = calls.borrow_mut().as_mut() {
+            calls.push(call);
+        }
+    });
+    result
+}
+
+/// Run `f`, returning its wall time and the external calls it recorded.
+pub(crate) fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration, Vec<ExternalCall>) {
+    let outer = EXTERNAL_CALLS.with(|calls| calls.borrow_mut().replace(vec![]));
+    let start = Instant::now();
+    let result = f();
+    let execution = start.elapsed();
+    let external_calls = EXTERNAL_CALLS.with(|calls| std::mem::replace(&mut *calls.borrow_mut(), outer)).unwrap_or_default();
+    (result, execution, external_calls)
+}
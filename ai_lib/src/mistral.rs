@@ -0,0 +1,100 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::{
+    ChatBackend, ChatCompletionRequest, ChatCompletionResponse, ChatError, ConfigError, FunctionCall,
+    Message, Model, OpenAIClient,
+};
+
+const BASE_URL: &str = "https://api.mistral.ai/v1";
+
+/// Mistral's chat API. It's close to OpenAI's, but function calling goes through `tools` and
+/// `tool_calls`, and `tool_choice` can't name a specific function.
+pub struct MistralBackend {
+    client: OpenAIClient,
+}
+
+impl MistralBackend {
+    /// `model` is a Mistral model name such as `mistral-large-latest`.
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Result<Self, ConfigError> {
+        let mut client = OpenAIClient::builder()
+            .api_key(api_key)
+            .base_url(BASE_URL)
+            .default_model(Model::custom(model))
+            .try_build()?;
+        client.provider_name = Some("mistral".to_string());
+        Ok(Self { client })
+    }
+
+    /// Configure from the `MISTRAL_API_KEY` environment variable.
+    pub fn from_env(model: impl Into<String>) -> Result<Self, ConfigError> {
+        let api_key = std::env::var("MISTRAL_API_KEY").map_err(|_| ConfigError::MissingApiKey)?;
+        Self::new(api_key, model)
+    }
+
+    /// Send requests through this HTTP client, e.g. one configured with a proxy or custom root certificates.
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client.set_http_client(client);
+        self
+    }
+
+    fn request_body(req: &ChatCompletionRequest) -> Value {
+        let mut body = serde_json::to_value(req).unwrap();
+        let body_object = body.as_object_mut().unwrap();
+        body_object.remove("function_call");
+        // Mistral has no file inputs; images are sent as content parts
+        let messages: Vec<_> = req
+            .messages
+            .iter()
+            .map(|message| Message { files: vec![], ..message.clone() }.to_wire())
+            .collect();
+        body_object.insert("messages".to_string(), messages.into());
+
+        if let Some(functions) = body_object.remove("functions") {
+            let functions = functions.as_array().cloned().unwrap_or_default();
+            // An exact function call is expressed by offering only that function and requiring a call
+            let (functions, tool_choice) = match &req.function_call {
+                Some(FunctionCall::Exact { name }) => {
+                    (functions.into_iter().filter(|f| f["name"] == *name).collect(), "any")
+                }
+                Some(FunctionCall::None) => (functions, "none"),
+                _ => (functions, "auto"),
+            };
+            let tools: Vec<_> = functions.into_iter().map(|f| json!({ "type": "function", "function": f })).collect();
+            body_object.insert("tools".to_string(), tools.into());
+            body_object.insert("tool_choice".to_string(), tool_choice.into());
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl ChatBackend for MistralBackend {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        let res = self.client.post("chat/completions", &Self::request_body(req), req).await?;
+        let mut body: Value = res.json().await?;
+        if let Some(message) = body.get("message").filter(|_| body.get("choices").is_none()) {
+            return Err(ChatError::Other(format!("Mistral error: {message}")));
+        }
+
+        // Move the first tool call into the OpenAI-style function_call the driver expects
+        for choice in body["choices"].as_array_mut().into_iter().flatten() {
+            let message = &mut choice["message"];
+            if let Some(call) = message["tool_calls"].get(0).map(|call| call["function"].clone()) {
+                message["function_call"] = call;
+            }
+            if let Some(message) = message.as_object_mut() {
+                message.remove("tool_calls");
+            }
+        }
+
+        let mut response: ChatCompletionResponse =
+            serde_json::from_value(body).map_err(|e| ChatError::Other(format!("Unexpected Mistral response: {e}")))?;
+        response.attribution = Some(self.client.attribution());
+        Ok(response)
+    }
+
+    fn default_model(&self) -> Model {
+        self.client.default_model()
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::{
+    Attribution, CalledFunction, ChatBackend, ChatCompletionRequest, ChatCompletionResponse, ChatError, Choice,
+    Message, Model, Usage,
+};
+
+/// A backend for tests that answers each request with the next scripted response instead of calling
+/// a model, so `#[ai_functions]` state machines can be driven deterministically. Clones share the
+/// script and the record of received requests, so keep one to inspect after handing another to
+/// `DriveOptions::backend`.
+///
+/// ```ignore
+/// let mock = MockBackend::new()
+///     .call("pick_title", json!({ "args": { "title": "Dune" } }))
+///     .expecting("Pick a title")
+///     .call("done", json!({}));
+/// drive_with(&mut state, &DriveOptionsBuilder::default().backend(mock.clone()).build()?).await?;
+/// mock.assert_finished();
+/// ```
+///
+/// Expectations and running past the end of the script panic, like any other test assertion.
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    script: Arc<Mutex<VecDeque<Step>>>,
+    requests: Arc<Mutex<Vec<ChatCompletionRequest>>>,
+}
+
+struct Step {
+    response: Result<Message, ChatError>,
+    expected_prompt: Option<String>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Respond to the next request with a call to `name` with `arguments`.
+    pub fn call(self, name: impl Into<String>, arguments: impl Serialize) -> Self {
+        let arguments = serde_json::to_string(&arguments).expect("Mock arguments must serialize");
+        self.push(Ok(Message {
+            role: "assistant".to_string(),
+            content: None,
+            function_call: Some(CalledFunction { name: name.into(), arguments }),
+            files: vec![],
+            images: vec![],
+        }))
+    }
+
+    /// Respond to the next request with a text reply instead of a function call.
+    pub fn reply(self, content: impl Into<String>) -> Self {
+        self.push(Ok(Message {
+            role: "assistant".to_string(),
+            content: Some(content.into()),
+            function_call: None,
+            files: vec![],
+            images: vec![],
+        }))
+    }
+
+    /// Fail the next request with `error`.
+    pub fn fail(self, error: ChatError) -> Self {
+        self.push(Err(error))
+    }
+
+    /// Assert that the request answered by the most recently scripted response has a latest user
+    /// message containing `text`.
+    pub fn expecting(self, text: impl Into<String>) -> Self {
+        self.script.lock().unwrap().back_mut().expect("expecting() must follow a scripted response").expected_prompt =
+            Some(text.into());
+        self
+    }
+
+    /// Every request received so far, in order.
+    pub fn requests(&self) -> Vec<ChatCompletionRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// How many scripted responses haven't been used yet.
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+
+    /// Panic if any scripted responses weren't used.
+    pub fn assert_finished(&self) {
+        let remaining = self.remaining();
+        assert!(remaining == 0, "MockBackend has {remaining} unused scripted responses");
+    }
+
+    fn push(self, response: Result<Message, ChatError>) -> Self {
+        self.script.lock().unwrap().push_back(Step { response, expected_prompt: None });
+        self
+    }
+}
+
+/// The content of the latest user message, which is the prompt or the feedback on the last call.
+fn latest_prompt(req: &ChatCompletionRequest) -> &str {
+    req.messages.iter().rev().find(|m| m.role == "user").and_then(|m| m.content.as_deref()).unwrap_or_default()
+}
+
+#[async_trait]
+impl ChatBackend for MockBackend {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        self.requests.lock().unwrap().push(req.clone());
+        let prompt = latest_prompt(req);
+        let Some(step) = self.script.lock().unwrap().pop_front() else {
+            panic!("MockBackend received an unscripted request for prompt: {prompt}");
+        };
+        if let Some(expected) = &step.expected_prompt {
+            assert!(prompt.contains(expected.as_str()), "MockBackend expected a prompt containing {expected:?}, got: {prompt}");
+        }
+
+        let message = step.response?;
+        Ok(ChatCompletionResponse {
+            created: 0,
+            model: req.model.as_str().to_string(),
+            choices: vec![Choice { index: 0, message, finish_reason: "stop".to_string(), logprobs: None }],
+            usage: Usage::default(),
+            system_fingerprint: None,
+            attribution: Some(Attribution::new("mock", None)),
+        })
+    }
+
+    fn default_model(&self) -> Model {
+        Model::custom("mock")
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{ChatError, OpenAIClient};
+
+/// Model used by `moderate`
+const MODERATION_MODEL: &str = "omni-moderation-latest";
+
+/// Whether some input violates the provider's usage policies, and in which categories.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModerationResult {
+    pub flagged: bool,
+    /// Whether each category (e.g. `harassment`, `self-harm/intent`) was flagged
+    #[serde(default)]
+    pub categories: BTreeMap<String, bool>,
+    /// Confidence per category, from 0.0 to 1.0
+    #[serde(default)]
+    pub category_scores: BTreeMap<String, f64>,
+}
+
+impl ModerationResult {
+    /// The categories that were flagged.
+    pub fn flagged_categories(&self) -> Vec<&str> {
+        self.categories.iter().filter(|(_, flagged)| **flagged).map(|(category, _)| category.as_str()).collect()
+    }
+
+    /// Categories scoring at least `threshold`, for applications stricter than the provider's own
+    /// flagging.
+    pub fn categories_above(&self, threshold: f64) -> Vec<&str> {
+        self.category_scores.iter().filter(|(_, score)| **score >= threshold).map(|(category, _)| category.as_str()).collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationResult>,
+}
+
+impl OpenAIClient {
+    /// Screen `input` with the moderation endpoint, e.g. before user-supplied material goes into a
+    /// prompt. Retried like chat completions.
+    pub async fn moderate(&self, input: &str) -> Result<ModerationResult, ChatError> {
+        let results = self.moderate_all(&[input]).await?;
+        results.into_iter().next().ok_or_else(|| ChatError::Other("Empty moderation response".to_string()))
+    }
+
+    /// Screen several inputs in one request, returning one result per input in the same order.
+    pub async fn moderate_all(&self, inputs: &[impl AsRef<str>]) -> Result<Vec<ModerationResult>, ChatError> {
+        let input: Vec<&str> = inputs.iter().map(AsRef::as_ref).collect();
+        let body = json!({ "model": MODERATION_MODEL, "input": input });
+        let response: ModerationResponse = self.post_json("moderations", &body, MODERATION_MODEL).await?;
+        if response.results.len() != inputs.len() {
+            return Err(ChatError::Other(format!("Expected {} moderation results, got {}", inputs.len(), response.results.len())));
+        }
+        Ok(response.results)
+    }
+}
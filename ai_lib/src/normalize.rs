@@ -0,0 +1,79 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Clean-ups applied to every string in a function call's arguments before it is validated and
+/// dispatched. All off by default; `StringNormalization::all()` turns everything on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StringNormalization {
+    /// Unicode NFC, so e.g. `e` + combining acute becomes a single `é`
+    pub nfc: bool,
+    /// Remove leading and trailing whitespace
+    pub trim: bool,
+    /// Replace each run of whitespace (including non-breaking spaces) with a single space, or with a
+    /// single newline if the run contains one, so line structure survives
+    pub collapse_whitespace: bool,
+    /// Replace typographic quotes (`“ ” ‘ ’` and friends) with ASCII `"` and `'`
+    pub ascii_quotes: bool,
+}
+
+impl StringNormalization {
+    pub fn all() -> Self {
+        Self { nfc: true, trim: true, collapse_whitespace: true, ascii_quotes: true }
+    }
+
+    pub fn is_noop(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(&self, s: &str) -> String {
+        let mut s = if self.nfc { s.nfc().collect() } else { s.to_string() };
+        if self.ascii_quotes {
+            s = s
+                .chars()
+                .map(|c| match c {
+                    '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => '"',
+                    '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => '\'',
+                    c => c,
+                })
+                .collect();
+        }
+        if self.collapse_whitespace {
+            s = collapse_whitespace(&s);
+        }
+        if self.trim {
+            s = s.trim().to_string();
+        }
+        s
+    }
+
+    /// Apply to every string in a JSON value.
+    pub fn apply_value(&self, value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::String(s) => self.apply(&s).into(),
+            serde_json::Value::Array(items) => items.into_iter().map(|item| self.apply_value(item)).collect(),
+            serde_json::Value::Object(object) => object.into_iter().map(|(k, v)| (k, self.apply_value(v))).collect(),
+            other => other,
+        }
+    }
+}
+
+fn collapse_whitespace(s: &str) -> String {
+    let mut collapsed = String::with_capacity(s.len());
+    let mut pending: Option<char> = None;
+    for c in s.chars() {
+        if c.is_whitespace() {
+            // A run containing a newline collapses to a newline, otherwise to a space
+            if c == '\n' || pending.is_none() {
+                pending = Some(if c == '\n' { '\n' } else { ' ' });
+            }
+        } else {
+            if let Some(whitespace) = pending.take() {
+                collapsed.push(whitespace);
+            }
+            collapsed.push(c);
+        }
+    }
+    if let Some(whitespace) = pending {
+        collapsed.push(whitespace);
+    }
+    collapsed
+}
@@ -0,0 +1,144 @@
+use convert_case::{Case, Casing};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::argdiff::ArgumentDiff;
+use crate::AiFunctionError;
+
+/// `(minimum, maximum)` for a schemars integer `format`, kept in function schemas so the model sees
+/// the range. 64- and 128-bit maximums are left out: they're too large to be a useful hint.
+pub(crate) fn integer_bounds(format: &str) -> Option<(Option<f64>, Option<f64>)> {
+    let bounds = match format {
+        "int8" => (Some(i8::MIN as f64), Some(i8::MAX as f64)),
+        "int16" => (Some(i16::MIN as f64), Some(i16::MAX as f64)),
+        "int32" => (Some(i32::MIN as f64), Some(i32::MAX as f64)),
+        "uint8" => (Some(0.0), Some(u8::MAX as f64)),
+        "uint16" => (Some(0.0), Some(u16::MAX as f64)),
+        "uint32" => (Some(0.0), Some(u32::MAX as f64)),
+        "uint64" | "uint128" | "uint" => (Some(0.0), None),
+        _ => return None,
+    };
+    Some(bounds)
+}
+
+/// Deserialize a function's arguments. Used by `#[ai_function]`; if serde rejects them, integer
+/// arguments are checked against `parameters()` (the function's schema): whole numbers written as
+/// floats (`3.0`) or strings (`"3"`) are accepted, and values out of the type's range (or `0` for a
+/// `NonZero` type) are reported as recoverable errors naming the limit, rather than as serde's
+/// `invalid value: integer 300, expected u8`. Other failures are reported as a diff against the
+/// schema: missing required fields, undeclared fields, and values of the wrong type.
+pub fn parse_arguments<T: DeserializeOwned>(
+    arguments: &str,
+    parameters: impl FnOnce() -> Option<Value>,
+) -> Result<T, AiFunctionError> {
+    let error = match serde_json::from_str(arguments) {
+        Ok(args) => return Ok(args),
+        Err(e) => e,
+    };
+    let (Some(schema), Ok(mut value)) = (parameters(), serde_json::from_str::<Value>(arguments)) else {
+        return Err(error.into());
+    };
+    let definitions = schema.get("$defs").or_else(|| schema.get("definitions")).cloned().unwrap_or(Value::Null);
+    let mut problems = vec![];
+    if let Some(object) = value.as_object_mut() {
+        // Top-level arguments may come in snake, camel, or pascal case; the schema uses camel
+        for (key, value) in object.iter_mut() {
+            if let Some(property) = schema["properties"].get(key.to_case(Case::Camel)) {
+                coerce(value, property, &definitions, key, &mut problems, 0);
+            }
+        }
+    }
+    if !problems.is_empty() {
+        return Err(AiFunctionError::Recoverable(problems.join("\n")));
+    }
+    serde_json::from_value(value.clone()).map_err(|_| {
+        // Where the arguments differ from the schema says more than serde's first complaint
+        let diff = ArgumentDiff::new(&value, &schema);
+        match diff.is_empty() {
+            true => error.into(),
+            false => AiFunctionError::Recoverable(diff.to_string()),
+        }
+    })
+}
+
+fn coerce(value: &mut Value, schema: &Value, definitions: &Value, path: &str, problems: &mut Vec<String>, depth: usize) {
+    if depth > 16 {
+        return;
+    }
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.rsplit('/').next().unwrap_or_default();
+        if let Some(definition) = definitions.get(name) {
+            coerce(value, definition, definitions, path, problems, depth + 1);
+        }
+        return;
+    }
+    // Option<T> is anyOf [T, null]
+    if let Some(variants) = schema.get("anyOf").and_then(Value::as_array) {
+        let non_null: Vec<_> = variants.iter().filter(|variant| variant["type"] != "null").collect();
+        if let ([variant], false) = (non_null.as_slice(), value.is_null()) {
+            coerce(value, variant, definitions, path, problems, depth + 1);
+        }
+        return;
+    }
+
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if let Some(property) = schema["properties"].get(key.as_str()) {
+                    coerce(value, property, definitions, &format!("{path}.{key}"), problems, depth + 1);
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item) = schema.get("items").filter(|item| item.is_object()) {
+                for (i, value) in items.iter_mut().enumerate() {
+                    coerce(value, item, definitions, &format!("{path}[{i}]"), problems, depth + 1);
+                }
+            }
+        }
+        _ if is_integer(schema) => {
+            if let Err(problem) = coerce_integer(value, schema) {
+                problems.push(format!("`{path}` {problem}"));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_integer(schema: &Value) -> bool {
+    match &schema["type"] {
+        Value::String(t) => t == "integer",
+        Value::Array(types) => types.iter().any(|t| t == "integer"),
+        _ => false,
+    }
+}
+
+/// Turn a whole-number float or numeric string into an integer, then check it against the
+/// schema's bounds.
+fn coerce_integer(value: &mut Value, schema: &Value) -> Result<(), String> {
+    let number = match &*value {
+        Value::Number(number) => number.as_f64().unwrap_or_default(),
+        Value::String(s) => s.trim().parse::<f64>().map_err(|_| format!("must be an integer, got {value}"))?,
+        _ => return Ok(()),
+    };
+    if !number.is_finite() || number.fract() != 0.0 {
+        return Err(format!("must be a whole number, got {value}"));
+    }
+    if let Some(minimum) = schema["minimum"].as_f64().filter(|&minimum| number < minimum) {
+        return Err(format!("must be at least {minimum}, got {value}"));
+    }
+    if let Some(maximum) = schema["maximum"].as_f64().filter(|&maximum| number > maximum) {
+        return Err(format!("must be at most {maximum}, got {value}"));
+    }
+    if number == 0.0 && schema["not"]["const"] == 0 {
+        return Err("must not be 0".to_string());
+    }
+    // Leave anything that isn't exactly representable alone, for serde to report
+    if number.abs() < 2f64.powi(53) {
+        *value = match number < 0.0 {
+            true => (number as i64).into(),
+            false => (number as u64).into(),
+        };
+    }
+    Ok(())
+}
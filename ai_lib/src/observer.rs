@@ -0,0 +1,16 @@
+use crate::{Diagnostic, EditDiff, SloViolation, StepTiming};
+
+/// Receives events as a run progresses, e.g. for display in a UI. Every event has an empty
+/// default implementation, so observers only implement what they care about.
+pub trait Observer: Send + Sync {
+    /// A new version of an edited text was recorded in an `EditHistory`.
+    fn edit_diff(&self, _diff: &EditDiff) {}
+    /// The model called `give_up`, ending the run.
+    fn gave_up(&self, _reason: &str) {}
+    /// A step finished: the model replied and the state's function (or `text_reply`) ran.
+    fn step(&self, _timing: &StepTiming) {}
+    /// A run broke one of its service-level objectives.
+    fn slo_violated(&self, _violation: &SloViolation) {}
+    /// The library worked around a problem on its own, e.g. a retry or failover.
+    fn diagnostic(&self, _diagnostic: &Diagnostic) {}
+}
@@ -0,0 +1,91 @@
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::{ChatBackend, ChatCompletionRequest, ChatCompletionResponse, ChatError, ConfigError, Model, OpenAIClient};
+
+const BASE_URL: &str = "https://openrouter.ai/api/v1";
+
+/// OpenRouter's `provider` request field, controlling which upstream providers may serve a request.
+/// See <https://openrouter.ai/docs/provider-routing>.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProviderPreferences {
+    /// Providers to try, in order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<Vec<String>>,
+    /// Whether providers outside `order` may be used if those fail
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_fallbacks: Option<bool>,
+    /// Only use providers that support every parameter in the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_parameters: Option<bool>,
+    /// `"allow"` or `"deny"` providers that may store prompts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_collection: Option<String>,
+    /// Providers never to use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore: Option<Vec<String>>,
+}
+
+/// OpenRouter, which routes one OpenAI-compatible API across many hosted models and providers, with
+/// fallbacks handled server-side. Responses report the model that actually served them.
+pub struct OpenRouterBackend {
+    client: OpenAIClient,
+}
+
+impl OpenRouterBackend {
+    /// `model` is an OpenRouter model id such as `anthropic/claude-3.5-sonnet`.
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Result<Self, ConfigError> {
+        let mut client = OpenAIClient::builder()
+            .api_key(api_key)
+            .base_url(BASE_URL)
+            .default_model(Model::custom(model))
+            .try_build()?;
+        client.provider_name = Some("openrouter".to_string());
+        Ok(Self { client })
+    }
+
+    /// Configure from the `OPENROUTER_API_KEY` environment variable.
+    pub fn from_env(model: impl Into<String>) -> Result<Self, ConfigError> {
+        let api_key = std::env::var("OPENROUTER_API_KEY").map_err(|_| ConfigError::MissingApiKey)?;
+        Self::new(api_key, model)
+    }
+
+    /// Identify your app in OpenRouter's rankings via the `HTTP-Referer` and `X-Title` headers.
+    pub fn app(mut self, url: impl Into<String>, title: impl Into<String>) -> Self {
+        self.client.extra_headers.push(("HTTP-Referer".to_string(), url.into()));
+        self.client.extra_headers.push(("X-Title".to_string(), title.into()));
+        self
+    }
+
+    /// Send requests through this HTTP client, e.g. one configured with a proxy or custom root certificates.
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client.set_http_client(client);
+        self
+    }
+
+    pub fn provider(mut self, preferences: ProviderPreferences) -> Self {
+        let preferences = serde_json::to_value(preferences).unwrap();
+        self.client.extra_body.insert("provider".to_string(), preferences);
+        self
+    }
+
+    /// Models to fall back to, in order, if the primary model is unavailable (`route: "fallback"`).
+    pub fn fallback_models(mut self, models: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let mut all = vec![self.client.default_model().as_str().to_string()];
+        all.extend(models.into_iter().map(Into::into));
+        self.client.extra_body.insert("models".to_string(), all.into());
+        self.client.extra_body.insert("route".to_string(), "fallback".into());
+        self
+    }
+}
+
+#[async_trait]
+impl ChatBackend for OpenRouterBackend {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        self.client.chat(req).await
+    }
+
+    fn default_model(&self) -> Model {
+        self.client.default_model()
+    }
+}
@@ -0,0 +1,62 @@
+use convert_case::{Case, Casing};
+use serde::Serialize;
+
+use crate::Function;
+
+/// A function argument whose value is fixed by the prompt rather than chosen by the model. Pinned
+/// arguments are left out of the schema sent to the model and injected when the function is called.
+#[derive(Debug, Clone)]
+pub struct PinnedArgument {
+    pub function: String,
+    pub argument: String,
+    pub value: serde_json::Value,
+}
+
+impl PinnedArgument {
+    pub fn new(function: impl ToString, argument: impl ToString, value: impl Serialize) -> Self {
+        let argument = argument.to_string();
+        let value = serde_json::to_value(value)
+            .unwrap_or_else(|e| panic!("Pinned argument {argument} is not serializable: {e}"));
+        Self { function: function.to_string(), argument, value }
+    }
+}
+
+/// Remove pinned arguments from the function's schema so the model is never asked for them.
+pub(crate) fn unpin_schema(function: &mut Function, pinned: &[PinnedArgument]) {
+    for pin in pinned.iter().filter(|pin| pin.function == function.name) {
+        let property = pin.argument.to_case(Case::Camel);
+        if let Some(properties) = function.parameters.get_mut("properties").and_then(|p| p.as_object_mut()) {
+            properties.remove(&property);
+        }
+        if let Some(required) = function.parameters.get_mut("required").and_then(|r| r.as_array_mut()) {
+            required.retain(|r| r.as_str() != Some(property.as_str()));
+        }
+    }
+}
+
+/// Inject pinned values into the model's arguments, overriding anything it supplied for them.
+pub(crate) fn pin_arguments(function_name: &str, arguments: &str, pinned: &[PinnedArgument]) -> Result<String, serde_json::Error> {
+    let overrides: serde_json::Map<_, _> = pinned
+        .iter()
+        .filter(|pin| pin.function == function_name)
+        .map(|pin| (pin.argument.clone(), pin.value.clone()))
+        .collect();
+    if overrides.is_empty() {
+        return Ok(arguments.to_string());
+    }
+    merge_arguments(arguments, overrides)
+}
+
+/// Overwrite top-level arguments with `overrides`, treating keys that only differ in case
+/// convention (`chapter_index` vs `chapterIndex`) as the same argument.
+pub(crate) fn merge_arguments(arguments: &str, overrides: serde_json::Map<String, serde_json::Value>) -> Result<String, serde_json::Error> {
+    let mut value: serde_json::Value = serde_json::from_str(arguments)?;
+    if let Some(object) = value.as_object_mut() {
+        for (key, override_value) in overrides {
+            let property = key.to_case(Case::Camel);
+            object.retain(|key, _| key.to_case(Case::Camel) != property);
+            object.insert(property, override_value);
+        }
+    }
+    serde_json::to_string(&value)
+}
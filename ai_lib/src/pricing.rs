@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Usage;
+
+const BUNDLED_PRICING: &str = include_str!("pricing.toml");
+
+/// Price of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.prompt + usage.completion_tokens as f64 * self.completion) / 1_000_000.0
+    }
+}
+
+/// Prices keyed by the model name sent on the wire (e.g. `gpt-4-0613`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PricingTable {
+    pub models: BTreeMap<String, ModelPrice>,
+}
+
+impl PricingTable {
+    /// The table shipped with this crate.
+    pub fn bundled() -> Self {
+        Self::from_toml_str(BUNDLED_PRICING).expect("Bundled pricing table is invalid")
+    }
+
+    pub fn from_toml_str(toml: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(toml)
+    }
+
+    pub fn load_toml(path: impl AsRef<Path>) -> Result<Self, String> {
+        let toml = std::fs::read_to_string(path.as_ref()).map_err(|e| format!("Failed to read {}: {e}", path.as_ref().display()))?;
+        Self::from_toml_str(&toml).map_err(|e| e.to_string())
+    }
+
+    pub fn set(&mut self, model: impl Into<String>, price: ModelPrice) {
+        self.models.insert(model.into(), price);
+    }
+
+    /// Add or replace every price in `other`, keeping the rest of this table.
+    pub fn merge(&mut self, other: PricingTable) {
+        self.models.extend(other.models);
+    }
+
+    pub fn price(&self, model: &str) -> Option<ModelPrice> {
+        self.models.get(model).copied()
+    }
+
+    /// Cost in USD of `usage` on `model`, or `None` if the model isn't priced.
+    pub fn cost(&self, model: &str, usage: &Usage) -> Option<f64> {
+        self.price(model).map(|price| price.cost(usage))
+    }
+}
+
+fn global() -> &'static RwLock<PricingTable> {
+    static PRICING: OnceLock<RwLock<PricingTable>> = OnceLock::new();
+    PRICING.get_or_init(|| RwLock::new(PricingTable::bundled()))
+}
+
+/// The pricing table used for cost tracking: the bundled one, plus any runtime overrides.
+pub fn global_pricing() -> PricingTable {
+    global().read().unwrap().clone()
+}
+
+/// Replace the pricing table used for cost tracking.
+pub fn set_global_pricing(table: PricingTable) {
+    *global().write().unwrap() = table;
+}
+
+/// Add or replace prices in the table used for cost tracking, e.g. from a TOML file loaded with
+/// `PricingTable::load_toml`.
+pub fn extend_global_pricing(table: PricingTable) {
+    global().write().unwrap().merge(table);
+}
@@ -0,0 +1,68 @@
+use derive_builder::Builder;
+use serde_json::json;
+
+use crate::{ApprovalPolicy, Budget, Function, Model, SloPolicy};
+
+/// One tenant's configuration, e.g. a customer of a SaaS host: the system prompt, which functions
+/// the model may see, the model, spend limit, and guardrails. Runs started with a profile (see
+/// `drive_with_profile` or `DriveOptions::profile`) use its settings in place of the corresponding
+/// `DriveOptions`, so one tenant's configuration can't leak into another's runs.
+#[derive(Debug, Clone, Default, Builder)]
+#[builder(setter(into), default)]
+pub struct Profile {
+    /// Identifies the profile in audit records
+    pub name: String,
+    /// Sent as the system message at the start of every conversation
+    #[builder(setter(into, strip_option))]
+    pub system_prompt: Option<String>,
+    /// The only functions the model is offered, whatever a prompt lists. `None` allows every function.
+    #[builder(setter(strip_option))]
+    pub allowed_functions: Option<Vec<String>>,
+    /// Requested instead of the backend's default model
+    #[builder(setter(strip_option))]
+    pub model: Option<Model>,
+    /// Spend limit shared by every run with this profile (clones share spend). Once reached, runs
+    /// fail with `ChatError::BudgetExceeded`.
+    #[builder(setter(strip_option))]
+    pub budget: Option<Budget>,
+    #[builder(setter(strip_option))]
+    pub approval: Option<ApprovalPolicy>,
+    /// See `DriveOptions::language`
+    #[builder(setter(into, strip_option))]
+    pub language: Option<String>,
+    #[builder(setter(strip_option))]
+    pub slo: Option<SloPolicy>,
+}
+
+impl Profile {
+    pub fn allows(&self, function: &str) -> bool {
+        self.allowed_functions.as_ref().is_none_or(|allowed| allowed.iter().any(|f| f == function))
+    }
+
+    /// Keep only the functions the profile allows.
+    pub(crate) fn scope(&self, functions: Vec<Function>) -> Vec<Function> {
+        functions.into_iter().filter(|function| self.allows(&function.name)).collect()
+    }
+
+    /// The profile's configuration and spend so far, for an audit log.
+    pub fn audit(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "system_prompt": self.system_prompt,
+            "allowed_functions": self.allowed_functions,
+            "model": self.model.as_ref().map(Model::as_str),
+            "budget": self.budget.as_ref().map(|budget| json!({
+                "spent_tokens": budget.spent_tokens(),
+                "spent_cost": budget.spent_cost(),
+                "exceeded": budget.exceeded().map(|limit| limit.to_string()),
+            })),
+            "approval": self.approval.is_some(),
+            "language": self.language,
+            "slo": self.slo.as_ref().map(|slo| json!({
+                "max_p95_step_latency_ms": slo.max_p95_step_latency.map(|latency| latency.as_millis() as u64),
+                "max_session_cost": slo.max_session_cost,
+                "abort": slo.abort,
+            })),
+        })
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::Mutex;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Where the client's random choices come from: retry jitter and idempotency keys. A `SeededRandom`
+/// makes them the same on every run, for simulation tests and replays.
+pub trait RandomSource: Send + Sync {
+    fn next_u64(&self) -> u64;
+
+    /// A sample from `[0, 1)`.
+    fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The thread-local generator, seeded by the OS. The default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadRandom;
+
+impl RandomSource for ThreadRandom {
+    fn next_u64(&self) -> u64 {
+        rand::random()
+    }
+}
+
+/// A generator that yields the same sequence for the same seed.
+#[derive(Debug)]
+pub struct SeededRandom {
+    rng: Mutex<StdRng>,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: Mutex::new(StdRng::seed_from_u64(seed)) }
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next_u64(&self) -> u64 {
+        rand::RngCore::next_u64(&mut *self.rng.lock().unwrap())
+    }
+}
+
+/// A v4 UUID drawn from `random`.
+pub(crate) fn uuid_v4(random: &dyn RandomSource) -> uuid::Uuid {
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&random.next_u64().to_le_bytes());
+    bytes[8..].copy_from_slice(&random.next_u64().to_le_bytes());
+    uuid::Builder::from_random_bytes(bytes).into_uuid()
+}
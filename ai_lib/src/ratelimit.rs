@@ -0,0 +1,144 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{ChatCompletionRequest, Clock, SystemClock};
+
+/// Client-side token buckets for requests-per-minute and tokens-per-minute budgets, so requests wait
+/// locally instead of being rejected with 429s. Cheap to clone; clones share the same buckets, so
+/// several clients (or concurrently driven states) can share one budget. Waiting requests are
+/// served in FIFO order.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    requests_per_minute: Option<u32>,
+    tokens_per_minute: Option<u32>,
+    buckets: Arc<Mutex<Buckets>>,
+    /// Held by the caller at the front of the line while it waits, so waiters go in FIFO order
+    /// without blocking `settle`
+    queue: Arc<tokio::sync::Mutex<()>>,
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// `None` leaves that dimension unlimited.
+    pub fn new(requests_per_minute: Option<u32>, tokens_per_minute: Option<u32>) -> Self {
+        Self::with_clock(requests_per_minute, tokens_per_minute, Arc::new(SystemClock))
+    }
+
+    /// Like `new`, refilling and waiting by `clock`, e.g. a `SimulatedClock` in tests.
+    pub fn with_clock(requests_per_minute: Option<u32>, tokens_per_minute: Option<u32>, clock: Arc<dyn Clock>) -> Self {
+        let buckets = Buckets {
+            requests: requests_per_minute.unwrap_or(0) as f64,
+            tokens: tokens_per_minute.unwrap_or(0) as f64,
+            refilled_at: clock.now(),
+        };
+        Self {
+            requests_per_minute,
+            tokens_per_minute,
+            buckets: Arc::new(Mutex::new(buckets)),
+            queue: Arc::new(tokio::sync::Mutex::new(())),
+            clock,
+        }
+    }
+
+    /// Wait until one request costing `tokens` fits in the budget, then take it. A request larger
+    /// than the whole per-minute token budget waits for a full bucket rather than forever.
+    pub async fn acquire(&self, tokens: u32) {
+        let _front = self.queue.lock().await;
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap();
+                self.refill(&mut buckets);
+                let mut wait = Duration::ZERO;
+                if let Some(rpm) = self.requests_per_minute {
+                    wait = wait.max(time_until(buckets.requests, 1.0, rpm));
+                }
+                if let Some(tpm) = self.tokens_per_minute {
+                    wait = wait.max(time_until(buckets.tokens, (tokens as f64).min(tpm as f64), tpm));
+                }
+                if wait.is_zero() {
+                    buckets.requests -= 1.0;
+                    buckets.tokens -= tokens as f64;
+                    return;
+                }
+                wait
+            };
+            self.clock.sleep(wait).await;
+        }
+    }
+
+    /// Like `acquire`, returning a reservation that gives the estimate back if it's dropped without
+    /// being settled, e.g. because the request failed or was cancelled.
+    pub(crate) async fn reserve(&self, tokens: u32) -> Reservation<'_> {
+        self.acquire(tokens).await;
+        Reservation { limiter: self, estimated_tokens: tokens, settled: false }
+    }
+
+    /// Correct an estimate made before sending once the actual token usage is known.
+    pub fn settle(&self, estimated_tokens: u32, actual_tokens: u32) {
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.tokens += estimated_tokens as f64 - actual_tokens as f64;
+    }
+
+    fn refill(&self, buckets: &mut Buckets) {
+        let now = self.clock.now();
+        let minutes = (now - buckets.refilled_at).as_secs_f64() / 60.0;
+        buckets.refilled_at = now;
+        if let Some(rpm) = self.requests_per_minute {
+            buckets.requests = (buckets.requests + minutes * rpm as f64).min(rpm as f64);
+        }
+        if let Some(tpm) = self.tokens_per_minute {
+            buckets.tokens = (buckets.tokens + minutes * tpm as f64).min(tpm as f64);
+        }
+    }
+}
+
+/// Tokens taken from a `RateLimiter` for one request.
+pub(crate) struct Reservation<'a> {
+    limiter: &'a RateLimiter,
+    estimated_tokens: u32,
+    settled: bool,
+}
+
+impl Reservation<'_> {
+    /// Replace the estimate with the tokens the request actually used.
+    pub fn settle(mut self, actual_tokens: u32) {
+        self.limiter.settle(self.estimated_tokens, actual_tokens);
+        self.settled = true;
+    }
+
+    /// Keep the estimate, for responses that don't report usage.
+    pub fn keep(mut self) {
+        self.settled = true;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.limiter.settle(self.estimated_tokens, 0);
+        }
+    }
+}
+
+/// How long until a bucket refilling at `per_minute` holds `needed`.
+fn time_until(available: f64, needed: f64, per_minute: u32) -> Duration {
+    if available >= needed || per_minute == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64((needed - available) * 60.0 / per_minute as f64)
+}
+
+/// A rough token count for a request, before it is sent: about four characters per token of the
+/// serialized messages and functions, plus the completion budget.
+pub(crate) fn estimate_tokens(req: &ChatCompletionRequest) -> u32 {
+    let prompt_chars = serde_json::to_string(&req.messages).map(|s| s.len()).unwrap_or(0)
+        + serde_json::to_string(&req.functions).map(|s| s.len()).unwrap_or(0);
+    (prompt_chars / 4) as u32 + req.max_tokens.unwrap_or(0).max(0) as u32
+}
@@ -0,0 +1,251 @@
+use base64::Engine;
+use futures_util::stream::SplitStream;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::driver::{offered_functions, resolve_aliases};
+use crate::{
+    pinned, AiFunctionError, AiFunctionResponse, ChatError, DriveError, DynAiState, Function, OpenAIClient,
+    PinnedArgument, GIVE_UP_FUNCTION,
+};
+
+/// An event sent by the server during a realtime session. Events without a variant of their own
+/// arrive as `Other`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum RealtimeEvent {
+    #[serde(rename = "session.created")]
+    SessionCreated,
+    #[serde(rename = "session.updated")]
+    SessionUpdated,
+    /// Speech was detected in the input audio buffer
+    #[serde(rename = "input_audio_buffer.speech_started")]
+    SpeechStarted,
+    /// The user's speech, transcribed
+    #[serde(rename = "conversation.item.input_audio_transcription.completed")]
+    InputTranscript { transcript: String },
+    #[serde(rename = "response.text.delta")]
+    TextDelta { delta: String },
+    /// A chunk of the reply's audio, base64-encoded 16-bit PCM
+    #[serde(rename = "response.audio.delta")]
+    AudioDelta { delta: String },
+    /// A chunk of the transcript of the reply's audio
+    #[serde(rename = "response.audio_transcript.delta")]
+    AudioTranscriptDelta { delta: String },
+    /// The model called a function; answer with `RealtimeSender::function_output`
+    #[serde(rename = "response.function_call_arguments.done")]
+    FunctionCall { call_id: String, name: String, arguments: String },
+    #[serde(rename = "response.done")]
+    ResponseDone,
+    #[serde(rename = "error")]
+    Error { error: RealtimeError },
+    #[serde(other)]
+    Other,
+}
+
+/// The `error` of a realtime error event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RealtimeError {
+    pub message: String,
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+/// Sends client events to a realtime session. Cheap to clone, so one task can stream microphone
+/// audio while another reads events.
+#[derive(Clone)]
+pub struct RealtimeSender {
+    outgoing: mpsc::UnboundedSender<WsMessage>,
+}
+
+impl RealtimeSender {
+    /// Send a raw client event, e.g. `{"type": "response.cancel"}`.
+    pub fn send(&self, event: Value) -> Result<(), ChatError> {
+        self.outgoing
+            .send(WsMessage::Text(event.to_string()))
+            .map_err(|_| ChatError::Other("Realtime session is closed".to_string()))
+    }
+
+    /// Set the session's instructions and offer `functions` as its tools.
+    pub fn update_session(&self, instructions: &str, functions: &[Function]) -> Result<(), ChatError> {
+        let tools: Vec<_> = functions
+            .iter()
+            .map(|f| json!({ "type": "function", "name": f.name, "description": f.description, "parameters": f.parameters }))
+            .collect();
+        self.send(json!({
+            "type": "session.update",
+            "session": { "instructions": instructions, "tools": tools, "tool_choice": "auto" },
+        }))
+    }
+
+    /// Add a user text message to the conversation and ask for a response.
+    pub fn send_text(&self, text: &str) -> Result<(), ChatError> {
+        self.send(json!({
+            "type": "conversation.item.create",
+            "item": { "type": "message", "role": "user", "content": [{ "type": "input_text", "text": text }] },
+        }))?;
+        self.send(json!({ "type": "response.create" }))
+    }
+
+    /// Append 16-bit PCM audio to the input buffer. With the server's voice activity detection on
+    /// (the default), responses start by themselves when the user stops speaking.
+    pub fn append_audio(&self, pcm16: &[u8]) -> Result<(), ChatError> {
+        let audio = base64::engine::general_purpose::STANDARD.encode(pcm16);
+        self.send(json!({ "type": "input_audio_buffer.append", "audio": audio }))
+    }
+
+    /// Answer the function call `call_id` with `output` and ask for a response.
+    pub fn function_output(&self, call_id: &str, output: &str) -> Result<(), ChatError> {
+        self.send(json!({
+            "type": "conversation.item.create",
+            "item": { "type": "function_call_output", "call_id": call_id, "output": output },
+        }))?;
+        self.send(json!({ "type": "response.create" }))
+    }
+}
+
+/// A WebSocket session with the realtime API, opened with `OpenAIClient::realtime`.
+pub struct RealtimeSession {
+    sender: RealtimeSender,
+    incoming: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+}
+
+impl RealtimeSession {
+    pub fn sender(&self) -> &RealtimeSender {
+        &self.sender
+    }
+
+    /// The next server event, or `None` once the session is closed.
+    pub async fn next_event(&mut self) -> Option<Result<RealtimeEvent, ChatError>> {
+        loop {
+            let message = match self.incoming.next().await? {
+                Ok(message) => message,
+                Err(e) => return Some(Err(ChatError::Other(format!("Realtime session failed: {e}")))),
+            };
+            match message {
+                WsMessage::Text(text) => {
+                    return Some(
+                        serde_json::from_str(&text).map_err(|e| ChatError::Other(format!("Failed to parse event ({e}): {text}"))),
+                    )
+                }
+                WsMessage::Close(_) => return None,
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl OpenAIClient {
+    /// Open a realtime session with `model` (e.g. `gpt-4o-realtime-preview`) over a WebSocket.
+    pub async fn realtime(&self, model: &str) -> Result<RealtimeSession, ChatError> {
+        let (base_url, api_key) = self.base_url_and_key();
+        let url = format!("{}/realtime?model={model}", base_url.replacen("http", "ws", 1));
+        let mut request = url
+            .as_str()
+            .into_client_request()
+            .map_err(|e| ChatError::Other(format!("Invalid realtime URL {url}: {e}")))?;
+        let headers = request.headers_mut();
+        if let Some(api_key) = api_key {
+            let authorization = format!("Bearer {api_key}").parse().map_err(|_| ChatError::Other("Invalid API key".to_string()))?;
+            headers.insert("Authorization", authorization);
+        }
+        headers.insert("OpenAI-Beta", "realtime=v1".parse().unwrap());
+
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(|e| ChatError::Other(format!("Failed to open realtime session: {e}")))?;
+        let (mut sink, incoming) = socket.split();
+        let (outgoing, mut queued) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(message) = queued.recv().await {
+                if sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(RealtimeSession { sender: RealtimeSender { outgoing }, incoming })
+    }
+}
+
+/// Drive `state` over a realtime session, e.g. for a voice agent. Each prompt becomes the session's
+/// instructions and tools, and each function call the model makes is answered with the function's
+/// result or the next prompt. Every other event is passed to `on_event`, e.g. to play audio. Returns
+/// once a function finishes the state, or with `DriveError::Chat` if the session closes first.
+pub async fn drive_realtime<S: DynAiState + ?Sized>(
+    state: &mut S,
+    session: &mut RealtimeSession,
+    mut on_event: impl FnMut(&RealtimeEvent),
+) -> Result<(), DriveError> {
+    let sender = session.sender().clone();
+    let initial = state.initial();
+    let Some(prompt) = offer(state, &sender, initial)? else {
+        return Ok(());
+    };
+    sender.send_text(&prompt.text)?;
+    let mut pinned = prompt.pinned;
+
+    while let Some(event) = session.next_event().await {
+        let (call_id, name, arguments) = match event? {
+            RealtimeEvent::FunctionCall { call_id, name, arguments } => (call_id, name, arguments),
+            event => {
+                on_event(&event);
+                continue;
+            }
+        };
+
+        let arguments = resolve_aliases(state.function_aliases(&name), arguments);
+        let result = match pinned::pin_arguments(&name, &arguments, &pinned) {
+            Ok(arguments) => state.call(&name, &arguments),
+            Err(e) => Err(AiFunctionError::Recoverable(e.to_string())),
+        };
+        match result {
+            Ok(_) if name == GIVE_UP_FUNCTION => {
+                let reason = serde_json::from_str::<Value>(&arguments)
+                    .ok()
+                    .and_then(|arguments| arguments["reason"].as_str().map(String::from))
+                    .unwrap_or(arguments);
+                return Err(DriveError::ModelGaveUp { reason, transcript: vec![] });
+            }
+            Ok(next) => match offer(state, &sender, next)? {
+                Some(prompt) => {
+                    sender.function_output(&call_id, &prompt.text)?;
+                    pinned = prompt.pinned;
+                }
+                None => {
+                    sender.function_output(&call_id, "Done")?;
+                    return Ok(());
+                }
+            },
+            Err(AiFunctionError::Recoverable(e)) => sender.function_output(&call_id, &e)?,
+            Err(AiFunctionError::Unrecoverable(e)) => return Err(DriveError::Unrecoverable(e)),
+        }
+    }
+    Err(DriveError::Chat(ChatError::Other("Realtime session closed before the run finished".to_string())))
+}
+
+struct OfferedPrompt {
+    text: String,
+    pinned: Vec<PinnedArgument>,
+}
+
+/// Point the session at `response`'s prompt, or `None` if the state is done.
+fn offer<S: DynAiState + ?Sized>(
+    state: &S,
+    sender: &RealtimeSender,
+    response: AiFunctionResponse,
+) -> Result<Option<OfferedPrompt>, ChatError> {
+    match response {
+        AiFunctionResponse::Done => Ok(None),
+        AiFunctionResponse::Prompt { prompt, functions, pinned, .. } => {
+            let functions = offered_functions(state, functions, &pinned).map_err(ChatError::Other)?;
+            sender.update_session(&prompt, &functions)?;
+            Ok(Some(OfferedPrompt { text: prompt, pinned }))
+        }
+    }
+}
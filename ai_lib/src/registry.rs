@@ -0,0 +1,121 @@
+use std::fmt;
+
+use convert_case::{Case, Casing};
+
+use crate::{clarify, AgentManifest, AiState, Function};
+
+/// The source named for functions the driver offers the model itself.
+const DRIVER_SOURCE: &str = "the driver";
+
+/// Functions merged from several sources, e.g. multiple `#[ai_functions]` impls plus dynamically
+/// defined tools, each remembering where it came from. Registering a function whose name is
+/// already taken fails with a `NameCollision` naming both sources, unless namespacing is on.
+#[derive(Debug, Clone)]
+pub struct FunctionRegistry {
+    functions: Vec<RegisteredFunction>,
+    namespacing: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisteredFunction {
+    pub function: Function,
+    /// What declared the function, e.g. a state's type name
+    pub source: String,
+}
+
+/// Two sources declared a function of the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameCollision {
+    pub name: String,
+    /// The source that registered the name first
+    pub existing: String,
+    pub incoming: String,
+}
+
+impl fmt::Display for NameCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Function {} is declared by both {} and {}; rename one, or register them with namespacing",
+            self.name, self.existing, self.incoming
+        )
+    }
+}
+
+impl std::error::Error for NameCollision {}
+
+impl Default for FunctionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FunctionRegistry {
+    /// A registry holding only the driver's own functions, so nothing registered can shadow them.
+    pub fn new() -> Self {
+        let clarify = Function {
+            name: clarify::CLARIFY_FUNCTION.to_string(),
+            description: "Provide corrected values for the invalid fields of a call".to_string(),
+            parameters: serde_json::json!({ "type": "object" }),
+        };
+        Self { functions: vec![RegisteredFunction { function: clarify, source: DRIVER_SOURCE.to_string() }], namespacing: false }
+    }
+
+    /// Resolve a collision by registering the incoming function under its source's name as a
+    /// prefix, e.g. `search` from `billing::Agent` becomes `agent_search`, instead of failing.
+    pub fn with_namespacing(mut self) -> Self {
+        self.namespacing = true;
+        self
+    }
+
+    /// Register `function` as declared by `source`, returning the name it was registered under.
+    pub fn register(&mut self, source: impl Into<String>, mut function: Function) -> Result<String, NameCollision> {
+        let source = source.into();
+        if let Some(existing) = self.source(&function.name) {
+            let collision = NameCollision { name: function.name.clone(), existing: existing.to_string(), incoming: source.clone() };
+            if !self.namespacing {
+                return Err(collision);
+            }
+            function.name = format!("{}_{}", namespace(&source), function.name);
+            if let Some(existing) = self.source(&function.name) {
+                return Err(NameCollision { name: function.name, existing: existing.to_string(), incoming: source });
+            }
+        }
+        let name = function.name.clone();
+        self.functions.push(RegisteredFunction { function, source });
+        Ok(name)
+    }
+
+    /// Register every function of an agent's manifest, with the agent as their source.
+    pub fn register_manifest(&mut self, manifest: &AgentManifest) -> Result<(), NameCollision> {
+        for function in &manifest.functions {
+            self.register(manifest.agent.clone(), function.function.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Register the `#[ai_function]`s of `S`.
+    pub fn register_state<S: AiState>(&mut self) -> Result<(), NameCollision> {
+        self.register_manifest(&S::manifest())
+    }
+
+    /// The registered functions, in registration order, excluding the driver's own.
+    pub fn functions(&self) -> impl Iterator<Item = &RegisteredFunction> {
+        self.functions.iter().filter(|registered| registered.source != DRIVER_SOURCE)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegisteredFunction> {
+        self.functions.iter().find(|registered| registered.function.name == name)
+    }
+
+    /// The source that declared `name`.
+    pub fn source(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|registered| registered.source.as_str())
+    }
+}
+
+/// A prefix for functions from `source`: its last path segment, without generics, in snake case.
+fn namespace(source: &str) -> String {
+    let source = source.split('<').next().unwrap_or(source);
+    source.rsplit("::").next().unwrap_or(source).to_case(Case::Snake)
+}
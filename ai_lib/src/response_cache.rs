@@ -0,0 +1,125 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::ChatCompletionResponse;
+
+/// Persistent storage behind a `ResponseCache`, consulted when a response isn't in memory.
+pub trait CacheStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<ChatCompletionResponse>;
+    fn put(&self, key: &str, response: &ChatCompletionResponse);
+}
+
+/// One JSON file per cached response in a directory, so a cache survives between runs during
+/// development. Unreadable entries are treated as misses and write failures are ignored.
+#[derive(Debug, Clone)]
+pub struct DiskCacheStore {
+    dir: PathBuf,
+}
+
+impl DiskCacheStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+impl CacheStore for DiskCacheStore {
+    fn get(&self, key: &str) -> Option<ChatCompletionResponse> {
+        let json = std::fs::read_to_string(self.path(key)).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    fn put(&self, key: &str, response: &ChatCompletionResponse) {
+        let _ = std::fs::create_dir_all(&self.dir);
+        let _ = std::fs::write(self.path(key), serde_json::to_string(response).unwrap());
+    }
+}
+
+/// Caches responses to temperature-0 requests, keyed by request hash, so repeated identical prompts
+/// during development don't re-bill and re-wait. Holds up to `capacity` responses in memory, evicting
+/// the least recently used, in front of an optional `CacheStore`. Set it with
+/// `OpenAIClientBuilder::response_cache`; clones share the same entries, so several clients can
+/// share one cache.
+#[derive(Clone)]
+pub struct ResponseCache {
+    memory: Arc<Mutex<Lru>>,
+    store: Option<Arc<dyn CacheStore>>,
+}
+
+struct Lru {
+    capacity: usize,
+    entries: HashMap<String, ChatCompletionResponse>,
+    /// Keys from least to most recently used
+    order: VecDeque<String>,
+}
+
+impl Lru {
+    fn touch(&mut self, key: &str) {
+        self.order.retain(|k| k != key);
+        self.order.push_back(key.to_string());
+    }
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize) -> Self {
+        let lru = Lru { capacity, entries: HashMap::new(), order: VecDeque::new() };
+        Self { memory: Arc::new(Mutex::new(lru)), store: None }
+    }
+
+    pub fn with_store(mut self, store: impl CacheStore + 'static) -> Self {
+        self.store = Some(Arc::new(store));
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<ChatCompletionResponse> {
+        let mut memory = self.memory.lock().unwrap();
+        if let Some(response) = memory.entries.get(key).cloned() {
+            memory.touch(key);
+            return Some(response);
+        }
+        drop(memory);
+        let response = self.store.as_ref()?.get(key)?;
+        self.remember(key, &response);
+        Some(response)
+    }
+
+    pub fn put(&self, key: &str, response: &ChatCompletionResponse) {
+        self.remember(key, response);
+        if let Some(store) = &self.store {
+            store.put(key, response);
+        }
+    }
+
+    /// Number of responses held in memory.
+    pub fn len(&self) -> usize {
+        self.memory.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget everything held in memory; the store is left as is.
+    pub fn clear(&self) {
+        let mut memory = self.memory.lock().unwrap();
+        memory.entries.clear();
+        memory.order.clear();
+    }
+
+    fn remember(&self, key: &str, response: &ChatCompletionResponse) {
+        let mut memory = self.memory.lock().unwrap();
+        if memory.capacity == 0 {
+            return;
+        }
+        memory.entries.insert(key.to_string(), response.clone());
+        memory.touch(key);
+        while memory.entries.len() > memory.capacity {
+            let Some(oldest) = memory.order.pop_front() else { break };
+            memory.entries.remove(&oldest);
+        }
+    }
+}
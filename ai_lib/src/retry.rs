@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use crate::{RandomSource, ThreadRandom};
+
+/// How a client backs off and retries rate limits and transient transport errors.
+/// `RetryPolicy::default()` waits 1s, doubling up to 60s, for at most 7 attempts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Wait before the first retry
+    pub initial_delay: Duration,
+    /// Factor the wait grows by after each retry
+    pub multiplier: f64,
+    /// Longest single wait
+    pub max_delay: Duration,
+    /// Fraction (0.0 to 1.0) of each wait that is randomized, so many clients rate limited at the
+    /// same moment don't all retry in lockstep
+    pub jitter: f64,
+    /// Total attempts, including the first, before giving up
+    pub max_attempts: u32,
+    /// Give up rather than wait past this much time since the first attempt
+    pub max_elapsed: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(60),
+            jitter: 0.0,
+            max_attempts: 7,
+            max_elapsed: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry; the first failure is returned as is.
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Self::default() }
+    }
+
+    /// How long to wait after `attempt` (starting at 1) failed, or `None` to give up.
+    pub fn delay(&self, attempt: u32, elapsed: Duration) -> Option<Duration> {
+        self.delay_with_hint(attempt, elapsed, None)
+    }
+
+    /// Like `delay`, but a server-indicated wait (e.g. from `Retry-After`) replaces the computed
+    /// backoff. Attempt and elapsed-time limits still apply.
+    pub fn delay_with_hint(&self, attempt: u32, elapsed: Duration, hint: Option<Duration>) -> Option<Duration> {
+        self.delay_with_random(attempt, elapsed, hint, &ThreadRandom)
+    }
+
+    /// Like `delay_with_hint`, with the jitter drawn from `random`.
+    pub fn delay_with_random(&self, attempt: u32, elapsed: Duration, hint: Option<Duration>, random: &dyn RandomSource) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let base = self.initial_delay.as_secs_f64() * self.multiplier.powi(attempt as i32 - 1);
+        let base = base.min(self.max_delay.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let delay = match hint {
+            Some(hint) => hint,
+            None => Duration::from_secs_f64(base * (1.0 - jitter * random.next_f64())),
+        };
+        match self.max_elapsed {
+            Some(max_elapsed) if elapsed + delay > max_elapsed => None,
+            _ => Some(delay),
+        }
+    }
+}
+
+/// The wait a 429 response asks for: `Retry-After` (in seconds) if present, otherwise the longest of
+/// OpenAI's `x-ratelimit-reset-requests`/`x-ratelimit-reset-tokens` (durations like `6m0s` or `20ms`).
+pub(crate) fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(seconds) = header("retry-after").and_then(|value| value.trim().parse::<f64>().ok()) {
+        return Some(Duration::from_secs_f64(seconds.max(0.0)));
+    }
+    ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"]
+        .into_iter()
+        .filter_map(|name| header(name).and_then(parse_reset_duration))
+        .max()
+}
+
+/// Parse Go-style durations such as `1s`, `6m0s`, `1h2m3.5s`, or `20ms`.
+fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(rest.len());
+        let seconds = match &rest[..unit_len] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => return None,
+        };
+        total += number * seconds;
+        rest = &rest[unit_len..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
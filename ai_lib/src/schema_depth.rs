@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde_json::{Map, Value};
+
+/// 0 while unset
+static MAX_SCHEMA_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Limit every schema produced by `schema` (and so by every `#[ai_function]`) to `depth` levels of
+/// nested objects and arrays from now on, or lift the limit with `None`. See `limit_schema_depth`.
+/// A function's own `#[ai_function(max_schema_depth = ...)]` takes precedence.
+pub fn set_max_schema_depth(depth: Option<usize>) {
+    MAX_SCHEMA_DEPTH.store(depth.unwrap_or(0), Ordering::Relaxed);
+}
+
+pub fn max_schema_depth() -> Option<usize> {
+    match MAX_SCHEMA_DEPTH.load(Ordering::Relaxed) {
+        0 => None,
+        depth => Some(depth),
+    }
+}
+
+/// `schema` with every `$ref` inlined and `$defs` removed, so there are no reference cycles for a
+/// provider to reject, and with anything nested deeper than `max_depth` objects or arrays (or
+/// inside its own type, for a recursive type) replaced by a description naming its type. Values
+/// there can still be given; they just aren't described.
+///
+/// Panics, naming the type, if the schema refers to a type it doesn't define.
+pub fn limit_schema_depth(schema: &Value, max_depth: usize) -> Value {
+    let mut root = schema.clone();
+    let definitions = match root.as_object_mut() {
+        Some(object) => object.shift_remove("$defs").or_else(|| object.shift_remove("definitions")).unwrap_or_default(),
+        None => return root,
+    };
+    let mut limiter = DepthLimiter { definitions: &definitions, max_depth, expanding: vec![] };
+    limiter.limit(&root, 0)
+}
+
+struct DepthLimiter<'a> {
+    definitions: &'a Value,
+    max_depth: usize,
+    /// Types being inlined, outermost first
+    expanding: Vec<String>,
+}
+
+impl DepthLimiter<'_> {
+    fn limit(&mut self, schema: &Value, depth: usize) -> Value {
+        let Some(object) = schema.as_object() else {
+            return schema.clone();
+        };
+        if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+            let name = reference.rsplit('/').next().unwrap_or_default().to_string();
+            let Some(definition) = self.definitions.get(&name) else {
+                panic!("Schema refers to type {name}, which it doesn't define");
+            };
+            if self.expanding.contains(&name) {
+                return truncated(definition, format!("a {name}, with the same fields as the enclosing {name}"));
+            }
+            if depth > self.max_depth {
+                return truncated(definition, format!("a {name}, nested too deeply for its fields to be listed"));
+            }
+            self.expanding.push(name);
+            let mut inlined = self.limit(definition, depth);
+            self.expanding.pop();
+            // Keys beside the $ref (such as a field's description) override the definition's
+            if let Some(inlined) = inlined.as_object_mut() {
+                for (key, value) in object.iter().filter(|(key, _)| *key != "$ref") {
+                    inlined.insert(key.clone(), value.clone());
+                }
+            }
+            return inlined;
+        }
+
+        let nested = object.contains_key("properties") || object.contains_key("items");
+        if nested && depth > self.max_depth {
+            let what = match object.get("title").and_then(Value::as_str) {
+                Some(name) => format!("a {name}, nested too deeply for its fields to be listed"),
+                None => "nested too deeply for its contents to be listed".to_string(),
+            };
+            return truncated(schema, what);
+        }
+        let mut limited = Map::new();
+        for (key, value) in object {
+            let value = match (key.as_str(), value) {
+                ("properties", Value::Object(properties)) => Value::Object(
+                    properties.iter().map(|(name, property)| (name.clone(), self.limit(property, depth + 1))).collect(),
+                ),
+                ("items" | "additionalProperties", _) => self.limit(value, depth + 1),
+                ("anyOf" | "oneOf" | "allOf", Value::Array(variants)) => {
+                    Value::Array(variants.iter().map(|variant| self.limit(variant, depth)).collect())
+                }
+                _ => value.clone(),
+            };
+            limited.insert(key.clone(), value);
+        }
+        Value::Object(limited)
+    }
+}
+
+/// A stand-in for `schema` that keeps its type and description but not its structure, and says why.
+fn truncated(schema: &Value, why: String) -> Value {
+    let mut stand_in = Map::new();
+    if let Some(ty) = schema.get("type") {
+        stand_in.insert("type".to_string(), ty.clone());
+    }
+    let description = match schema.get("description").and_then(Value::as_str) {
+        Some(description) => format!("{description} ({why})"),
+        None => {
+            let mut chars = why.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+        }
+    };
+    stand_in.insert("description".to_string(), description.into());
+    Value::Object(stand_in)
+}
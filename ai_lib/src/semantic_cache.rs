@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::{
+    canonical_hash, cosine_similarity, validate_arguments, Attribution, ChatBackend, ChatCompletionRequest,
+    ChatCompletionResponse, ChatError, Model, OpenAIClient, Usage,
+};
+
+/// Turns text into an embedding vector for similarity search.
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ChatError>;
+}
+
+/// Embeddings from OpenAI's `embeddings` endpoint, e.g. with `text-embedding-3-small`.
+pub struct OpenAIEmbedder {
+    client: OpenAIClient,
+    model: String,
+}
+
+impl OpenAIEmbedder {
+    pub fn new(client: OpenAIClient, model: impl Into<String>) -> Self {
+        Self { client, model: model.into() }
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAIEmbedder {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, ChatError> {
+        let embeddings = self.client.embeddings(&self.model, &[text]).await?;
+        embeddings.into_iter().next().ok_or_else(|| ChatError::Other("Empty embeddings response".to_string()))
+    }
+}
+
+/// An approximate cache in front of a backend: a request whose conversation embeds within
+/// `threshold` cosine similarity of a cached one gets the cached response, for high-volume
+/// near-duplicate workloads like support-ticket triage. Only requests with the same model, functions,
+/// and `function_call` can match, and hits are attributed to `semantic-cache` with zero usage.
+///
+/// With `verify`, a cached function call is only reused if the new request offers that function and
+/// the cached arguments still validate against its schema; otherwise the request goes to the backend.
+pub struct SemanticCache<B> {
+    backend: B,
+    embedder: Arc<dyn Embedder>,
+    threshold: f32,
+    verify: bool,
+    capacity: usize,
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+struct Entry {
+    context: String,
+    embedding: Vec<f32>,
+    response: ChatCompletionResponse,
+}
+
+impl<B: ChatBackend> SemanticCache<B> {
+    /// A cache of up to 1000 responses, verifying hits. Thresholds around 0.95 suit most embedding models.
+    pub fn new(backend: B, embedder: impl Embedder + 'static, threshold: f32) -> Self {
+        Self {
+            backend,
+            embedder: Arc::new(embedder),
+            threshold,
+            verify: true,
+            capacity: 1000,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// The most responses kept; the oldest are evicted first.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// The cached response most similar to `embedding` within the threshold.
+    fn lookup(&self, context: &str, embedding: &[f32]) -> Option<ChatCompletionResponse> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.context == context)
+            .map(|entry| (entry, cosine_similarity(&entry.embedding, embedding)))
+            .filter(|(_, similarity)| *similarity >= self.threshold)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(entry, _)| entry.response.clone())
+    }
+
+    fn insert(&self, context: String, embedding: Vec<f32>, response: ChatCompletionResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(Entry { context, embedding, response });
+    }
+}
+
+/// Everything that must match exactly for a cached response to apply.
+fn context(req: &ChatCompletionRequest) -> String {
+    canonical_hash(&json!({ "model": req.model, "functions": req.functions, "function_call": req.function_call }))
+}
+
+/// The conversation as the text to embed.
+fn conversation_text(req: &ChatCompletionRequest) -> String {
+    req.messages
+        .iter()
+        .map(|m| format!("{}: {}", m.role, m.content.as_deref().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether every function call in `response` is offered by `req` with arguments valid for it.
+fn still_valid(req: &ChatCompletionRequest, response: &ChatCompletionResponse) -> bool {
+    response.choices.iter().filter_map(|choice| choice.message.function_call.as_ref()).all(|call| {
+        let function = req.functions.iter().flatten().find(|f| f.name == call.name);
+        function.is_some_and(|f| validate_arguments(&f.parameters, &call.arguments).is_ok())
+    })
+}
+
+#[async_trait]
+impl<B: ChatBackend> ChatBackend for SemanticCache<B> {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        let context = context(req);
+        let embedding = self.embedder.embed(&conversation_text(req)).await?;
+        if let Some(mut cached) = self.lookup(&context, &embedding) {
+            if !self.verify || still_valid(req, &cached) {
+                cached.usage = Usage::default();
+                cached.attribution = Some(Attribution::new("semantic-cache", None));
+                return Ok(cached);
+            }
+        }
+
+        let response = self.backend.chat(req).await?;
+        self.insert(context, embedding, response.clone());
+        Ok(response)
+    }
+
+    fn default_model(&self) -> Model {
+        self.backend.default_model()
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::{canonical_hash, global_pricing, ChatBackend, ChatCompletionRequest, ChatCompletionResponse, ChatError, Message, Model};
+
+/// How a candidate's reply compared with the control's for the same step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ShadowAgreement {
+    /// The same function with the same arguments, or the same text
+    Same,
+    /// The same function with different arguments
+    SameFunction,
+    Different,
+    /// The candidate request failed
+    CandidateFailed,
+}
+
+/// One step answered by both the control and the candidate model.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShadowRecord {
+    pub control_model: String,
+    pub candidate_model: String,
+    pub control: Message,
+    /// The candidate's reply, or its error message
+    pub candidate: Result<Message, String>,
+    pub agreement: ShadowAgreement,
+    pub control_latency: Duration,
+    pub candidate_latency: Duration,
+    /// USD per the global pricing table, if the model is in it
+    pub control_cost: Option<f64>,
+    pub candidate_cost: Option<f64>,
+}
+
+/// The records collected by a `ShadowBackend`. Cheap to clone; clones share the same records.
+#[derive(Debug, Clone, Default)]
+pub struct ShadowLog {
+    records: Arc<Mutex<Vec<ShadowRecord>>>,
+}
+
+impl ShadowLog {
+    pub fn records(&self) -> Vec<ShadowRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fraction of shadowed steps where the candidate replied the same as the control, or `None`
+    /// if nothing has been shadowed yet.
+    pub fn agreement_rate(&self) -> Option<f64> {
+        let records = self.records.lock().unwrap();
+        let same = records.iter().filter(|record| record.agreement == ShadowAgreement::Same).count();
+        (!records.is_empty()).then(|| same as f64 / records.len() as f64)
+    }
+}
+
+type OnRecord = Arc<dyn Fn(&ShadowRecord) + Send + Sync>;
+
+/// Duplicates a fraction of requests to a candidate model, to evaluate a model upgrade on real
+/// traffic without changing behavior: the control backend's response is always the one returned,
+/// and the candidate's reply is only compared and logged. Candidate requests run in the background
+/// on the tokio runtime, so they add no latency; their failures are recorded, never returned.
+///
+/// Selection is sticky per conversation: whether a request is shadowed depends only on its
+/// messages up to the first user message, so a session is either shadowed at every step or not at
+/// all, and the same sessions are picked on every run.
+pub struct ShadowBackend<B> {
+    control: B,
+    candidate: Arc<dyn ChatBackend>,
+    model: Model,
+    fraction: f64,
+    log: ShadowLog,
+    on_record: Option<OnRecord>,
+}
+
+impl<B: ChatBackend> ShadowBackend<B> {
+    /// Shadow every conversation to `model` on `candidate`; narrow it down with `fraction`.
+    pub fn new(control: B, candidate: impl ChatBackend + 'static, model: Model) -> Self {
+        Self { control, candidate: Arc::new(candidate), model, fraction: 1.0, log: ShadowLog::default(), on_record: None }
+    }
+
+    /// Fraction (0.0 to 1.0) of conversations to shadow.
+    pub fn fraction(mut self, fraction: f64) -> Self {
+        self.fraction = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Also call `f` with each record as it's made, e.g. to forward it to a log or scoring pipeline.
+    pub fn on_record(mut self, f: impl Fn(&ShadowRecord) + Send + Sync + 'static) -> Self {
+        self.on_record = Some(Arc::new(f));
+        self
+    }
+
+    pub fn log(&self) -> ShadowLog {
+        self.log.clone()
+    }
+
+    fn is_shadowed(&self, req: &ChatCompletionRequest) -> bool {
+        let opening = match req.messages.iter().position(|message| message.role == "user") {
+            Some(i) => &req.messages[..=i],
+            None => &req.messages[..],
+        };
+        let hash = canonical_hash(&opening);
+        let bucket = u64::from_str_radix(&hash[..16], 16).unwrap() as f64 / u64::MAX as f64;
+        bucket < self.fraction
+    }
+}
+
+#[async_trait]
+impl<B: ChatBackend> ChatBackend for ShadowBackend<B> {
+    async fn chat(&self, req: &ChatCompletionRequest) -> Result<ChatCompletionResponse, ChatError> {
+        let start = Instant::now();
+        let response = self.control.chat(req).await?;
+        let control_latency = start.elapsed();
+        let Some(control) = response.choices.first().map(|choice| choice.message.clone()) else {
+            return Ok(response);
+        };
+        if !self.is_shadowed(req) {
+            return Ok(response);
+        }
+
+        let mut candidate_req = req.clone();
+        candidate_req.model = self.model.clone();
+        let candidate = self.candidate.clone();
+        let log = self.log.clone();
+        let on_record = self.on_record.clone();
+        let control_model = response.model.clone();
+        let control_cost = global_pricing().cost(&response.model, &response.usage);
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let result = candidate.chat(&candidate_req).await;
+            let candidate_latency = start.elapsed();
+            let (candidate, candidate_cost) = match result {
+                Ok(response) => {
+                    let cost = global_pricing().cost(&response.model, &response.usage);
+                    match response.choices.into_iter().next() {
+                        Some(choice) => (Ok(choice.message), cost),
+                        None => (Err("No choices in response".to_string()), cost),
+                    }
+                }
+                Err(e) => (Err(e.to_string()), None),
+            };
+            let record = ShadowRecord {
+                control_model,
+                candidate_model: candidate_req.model.as_str().to_string(),
+                agreement: compare(&control, &candidate),
+                control,
+                candidate,
+                control_latency,
+                candidate_latency,
+                control_cost,
+                candidate_cost,
+            };
+            if let Some(on_record) = on_record {
+                on_record(&record);
+            }
+            log.records.lock().unwrap().push(record);
+        });
+        Ok(response)
+    }
+
+    fn default_model(&self) -> Model {
+        self.control.default_model()
+    }
+}
+
+fn compare(control: &Message, candidate: &Result<Message, String>) -> ShadowAgreement {
+    let Ok(candidate) = candidate else {
+        return ShadowAgreement::CandidateFailed;
+    };
+    match (&control.function_call, &candidate.function_call) {
+        (Some(a), Some(b)) if a.name == b.name => {
+            let parse = |arguments: &str| serde_json::from_str::<serde_json::Value>(arguments).ok();
+            match (parse(&a.arguments), parse(&b.arguments)) {
+                (Some(a), Some(b)) if a == b => ShadowAgreement::Same,
+                _ if a.arguments == b.arguments => ShadowAgreement::Same,
+                _ => ShadowAgreement::SameFunction,
+            }
+        }
+        (None, None) if control.content == candidate.content => ShadowAgreement::Same,
+        _ => ShadowAgreement::Different,
+    }
+}
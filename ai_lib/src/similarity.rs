@@ -0,0 +1,51 @@
+/// Edit distance between two strings, counted in chars.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Levenshtein distance normalized into a 0.0 (nothing in common) to 1.0 (identical) similarity.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let len = a.chars().count().max(b.chars().count());
+    if len == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / len as f64
+}
+
+/// The candidate most similar to `name`, ignoring case, if it scores at least `threshold`.
+pub fn closest_match<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>, threshold: f64) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    candidates
+        .into_iter()
+        .map(|candidate| (candidate, similarity(&name, &candidate.to_lowercase())))
+        .filter(|(_, score)| *score >= threshold)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(candidate, _)| candidate)
+}
+
+/// Cosine similarity of two embedding vectors, from -1.0 to 1.0 (identical direction). Vectors of
+/// different lengths or with zero magnitude score 0.0.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let magnitude = norm(a) * norm(b);
+    if magnitude == 0.0 {
+        0.0
+    } else {
+        dot / magnitude
+    }
+}
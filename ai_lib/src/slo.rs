@@ -0,0 +1,71 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::{global_pricing, ChatCompletionResponse};
+
+/// Service-level objectives for a run. Violations are reported to observers and, with
+/// `abort: true`, end the run with `DriveError::SloViolated`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SloPolicy {
+    /// Highest acceptable 95th-percentile latency of a single chat completion
+    pub max_p95_step_latency: Option<Duration>,
+    /// Highest acceptable total cost of the run, in USD, priced with the global pricing table
+    pub max_session_cost: Option<f64>,
+    /// End the run on a violation instead of only warning
+    pub abort: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SloViolation {
+    StepLatency { p95: Duration, limit: Duration },
+    SessionCost { cost: f64, limit: f64 },
+}
+
+impl fmt::Display for SloViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SloViolation::StepLatency { p95, limit } => write!(f, "p95 step latency {p95:?} exceeds {limit:?}"),
+            SloViolation::SessionCost { cost, limit } => write!(f, "session cost ${cost:.4} exceeds ${limit:.4}"),
+        }
+    }
+}
+
+/// Tracks a run against an `SloPolicy`. Each kind of violation is reported once.
+#[derive(Debug, Default)]
+pub(crate) struct SloTracker {
+    latencies: Vec<Duration>,
+    cost: f64,
+    latency_reported: bool,
+    cost_reported: bool,
+}
+
+impl SloTracker {
+    /// Record a step and return any objectives it newly violates.
+    pub fn record(&mut self, policy: &SloPolicy, latency: Duration, response: &ChatCompletionResponse) -> Vec<SloViolation> {
+        self.latencies.push(latency);
+        self.cost += global_pricing().cost(&response.model, &response.usage).unwrap_or(0.0);
+
+        let mut violations = vec![];
+        if let Some(limit) = policy.max_p95_step_latency {
+            let p95 = self.p95();
+            if p95 > limit && !self.latency_reported {
+                self.latency_reported = true;
+                violations.push(SloViolation::StepLatency { p95, limit });
+            }
+        }
+        if let Some(limit) = policy.max_session_cost {
+            if self.cost > limit && !self.cost_reported {
+                self.cost_reported = true;
+                violations.push(SloViolation::SessionCost { cost: self.cost, limit });
+            }
+        }
+        violations
+    }
+
+    fn p95(&self) -> Duration {
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        let rank = (sorted.len() as f64 * 0.95).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1]
+    }
+}
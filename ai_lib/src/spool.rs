@@ -0,0 +1,68 @@
+use std::fs::File;
+use std::io::{self, BufReader, Seek, SeekFrom};
+
+use serde::Deserialize;
+use tempfile::NamedTempFile;
+use tokio::io::AsyncWriteExt;
+
+use crate::{ChatCompletionResponse, Usage};
+
+/// A response body that was streamed to a temporary file chunk by chunk, so memory use stays
+/// bounded no matter how large the completion is. The file is deleted when this is dropped.
+pub struct SpooledResponse {
+    file: NamedTempFile,
+    len: u64,
+}
+
+impl SpooledResponse {
+    pub(crate) async fn from_response(mut res: reqwest::Response) -> Result<Self, reqwest::Error> {
+        let file = NamedTempFile::new().expect("Failed to create temporary file");
+        let mut writer = tokio::fs::File::from_std(file.reopen().expect("Failed to open temporary file"));
+        let mut len = 0;
+        while let Some(chunk) = res.chunk().await? {
+            writer.write_all(&chunk).await.expect("Failed to write temporary file");
+            len += chunk.len() as u64;
+        }
+        writer.flush().await.expect("Failed to write temporary file");
+        Ok(Self { file, len })
+    }
+
+    /// Size of the body in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        self.file.path()
+    }
+
+    /// A fresh reader positioned at the start of the body.
+    pub fn reader(&self) -> io::Result<BufReader<File>> {
+        let mut file = self.file.reopen()?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(BufReader::new(file))
+    }
+
+    /// The model and token usage the body reports, read without keeping the rest of it.
+    pub(crate) fn accounting(&self) -> Option<(String, Usage)> {
+        #[derive(Deserialize)]
+        struct Accounting {
+            #[serde(default)]
+            model: String,
+            #[serde(default)]
+            usage: Usage,
+        }
+        let accounting: Accounting = serde_json::from_reader(self.reader().ok()?).ok()?;
+        Some((accounting.model, accounting.usage))
+    }
+
+    /// Parse the body without loading it into a `String` first.
+    pub fn parse(&self) -> Result<ChatCompletionResponse, serde_json::Error> {
+        let reader = self.reader().map_err(serde_json::Error::io)?;
+        serde_json::from_reader(reader)
+    }
+}
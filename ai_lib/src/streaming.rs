@@ -0,0 +1,206 @@
+use serde::Deserialize;
+
+use crate::{ApiError, CalledFunction, ChatCompletionResponse, ChatError, Choice, Message, Usage};
+
+/// A fragment of a function call's arguments, as the model generates them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgumentsDelta<'a> {
+    /// The function being called. Providers send the name before any arguments.
+    pub function: &'a str,
+    /// The text that just arrived
+    pub fragment: &'a str,
+    /// Everything received so far, including `fragment`
+    pub arguments: &'a str,
+}
+
+impl ArgumentsDelta<'_> {
+    /// The arguments received so far, parsed as if the JSON ended here: open strings, arrays, and
+    /// objects are closed, so e.g. a partial `premise` string can be shown while it's generated.
+    /// `None` if the cut falls somewhere that can't be closed, such as in the middle of a key.
+    pub fn partial_value(&self) -> Option<serde_json::Value> {
+        parse_partial_json(self.arguments)
+    }
+}
+
+/// The callback streamed completions report argument fragments to.
+pub type OnArgumentsDelta<'f> = dyn FnMut(&ArgumentsDelta<'_>) + Send + 'f;
+
+/// Parse a prefix of a JSON document by closing whatever is still open.
+pub(crate) fn parse_partial_json(text: &str) -> Option<serde_json::Value> {
+    let mut closers = vec![];
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => closers.push('}'),
+            '[' => closers.push(']'),
+            '}' | ']' => {
+                closers.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let mut completed = text.to_string();
+    if in_string {
+        if escaped {
+            completed.pop();
+        }
+        completed.push('"');
+    }
+    let trimmed = completed.trim_end();
+    let mut completed = match trimmed.chars().last() {
+        Some(',') => trimmed[..trimmed.len() - 1].to_string(),
+        Some(':') => format!("{trimmed} null"),
+        _ => trimmed.to_string(),
+    };
+    completed.extend(closers.into_iter().rev());
+    serde_json::from_str(&completed).ok()
+}
+
+#[derive(Deserialize)]
+struct Chunk {
+    #[serde(default)]
+    created: u64,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+    #[serde(default)]
+    system_fingerprint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    index: i32,
+    #[serde(default)]
+    delta: Delta,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct Delta {
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    function_call: Option<FunctionCallDelta>,
+}
+
+#[derive(Default, Deserialize)]
+struct FunctionCallDelta {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+}
+
+/// Read a server-sent event stream of chat completion chunks into the response they add up to,
+/// passing each fragment of function call arguments to `on_delta` as it arrives.
+pub(crate) async fn read_stream(
+    mut res: reqwest::Response,
+    on_delta: &mut OnArgumentsDelta<'_>,
+) -> Result<ChatCompletionResponse, ChatError> {
+    let mut response = ChatCompletionResponse {
+        created: 0,
+        model: String::new(),
+        choices: vec![],
+        usage: Usage::default(),
+        system_fingerprint: None,
+        attribution: None,
+    };
+    let mut buffer = Vec::new();
+    while let Some(bytes) = res.chunk().await? {
+        buffer.extend_from_slice(&bytes);
+        while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            if data == "[DONE]" {
+                return finish(response);
+            }
+            let chunk = serde_json::from_str::<Chunk>(data).map_err(|e| match ApiError::from_body(data) {
+                Some(error) => ChatError::Api { status: 200, error },
+                None => ChatError::Other(format!("Failed to parse stream chunk ({e}): {data}")),
+            })?;
+            apply_chunk(&mut response, chunk, on_delta);
+        }
+    }
+    // Some servers close the stream without sending `[DONE]`
+    finish(response)
+}
+
+/// `response`, unless the stream ended before any choice arrived.
+fn finish(response: ChatCompletionResponse) -> Result<ChatCompletionResponse, ChatError> {
+    if response.choices.is_empty() {
+        return Err(ChatError::Other("The stream ended without any choices".to_string()));
+    }
+    Ok(response)
+}
+
+fn apply_chunk(response: &mut ChatCompletionResponse, chunk: Chunk, on_delta: &mut OnArgumentsDelta<'_>) {
+    if response.model.is_empty() {
+        response.model = chunk.model;
+        response.created = chunk.created;
+    }
+    if chunk.system_fingerprint.is_some() {
+        response.system_fingerprint = chunk.system_fingerprint;
+    }
+    if let Some(usage) = chunk.usage {
+        response.usage = usage;
+    }
+    for delta in chunk.choices {
+        let position = match response.choices.iter().position(|choice| choice.index == delta.index) {
+            Some(position) => position,
+            None => {
+                response.choices.push(Choice {
+                    index: delta.index,
+                    message: Message { role: "assistant".to_string(), content: None, function_call: None, files: vec![], images: vec![] },
+                    finish_reason: String::new(),
+                    logprobs: None,
+                });
+                response.choices.len() - 1
+            }
+        };
+        let choice = &mut response.choices[position];
+        if let Some(finish_reason) = delta.finish_reason {
+            choice.finish_reason = finish_reason;
+        }
+        let message = &mut choice.message;
+        if let Some(role) = delta.delta.role {
+            message.role = role;
+        }
+        if let Some(content) = delta.delta.content {
+            message.content.get_or_insert_with(String::new).push_str(&content);
+        }
+        if let Some(function_call) = delta.delta.function_call {
+            let called = message.function_call.get_or_insert_with(|| CalledFunction { name: String::new(), arguments: String::new() });
+            if let Some(name) = function_call.name {
+                called.name.push_str(&name);
+            }
+            if let Some(fragment) = function_call.arguments.filter(|fragment| !fragment.is_empty()) {
+                called.arguments.push_str(&fragment);
+                on_delta(&ArgumentsDelta { function: &called.name, fragment: &fragment, arguments: &called.arguments });
+            }
+        }
+    }
+}
+
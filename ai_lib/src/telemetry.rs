@@ -0,0 +1,143 @@
+/// A metric recorded by the client, labeled by model. Set a sink with `OpenAIClientBuilder::metrics`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// Chat completions requested, counting each once however many times it was retried
+    Requests,
+    /// Chat completions that ultimately failed
+    Errors,
+    /// Attempts repeated after a transient failure
+    Retries,
+    /// 429 responses
+    RateLimited,
+    /// Seconds per chat completion, including retries (a histogram)
+    Latency,
+    PromptTokens,
+    CompletionTokens,
+    /// USD, per the global pricing table
+    Cost,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 8] = [
+        Metric::Requests,
+        Metric::Errors,
+        Metric::Retries,
+        Metric::RateLimited,
+        Metric::Latency,
+        Metric::PromptTokens,
+        Metric::CompletionTokens,
+        Metric::Cost,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::Requests => "ai_requests_total",
+            Metric::Errors => "ai_request_errors_total",
+            Metric::Retries => "ai_retries_total",
+            Metric::RateLimited => "ai_rate_limited_total",
+            Metric::Latency => "ai_request_duration_seconds",
+            Metric::PromptTokens => "ai_prompt_tokens_total",
+            Metric::CompletionTokens => "ai_completion_tokens_total",
+            Metric::Cost => "ai_cost_usd_total",
+        }
+    }
+
+    pub fn help(&self) -> &'static str {
+        match self {
+            Metric::Requests => "Chat completion requests",
+            Metric::Errors => "Chat completion requests that failed",
+            Metric::Retries => "Chat completion attempts retried after a transient failure",
+            Metric::RateLimited => "Chat completion attempts rejected with 429",
+            Metric::Latency => "Chat completion latency including retries",
+            Metric::PromptTokens => "Prompt tokens used",
+            Metric::CompletionTokens => "Completion tokens used",
+            Metric::Cost => "Cost in USD",
+        }
+    }
+
+    /// Whether the metric is a histogram of observations rather than a counter.
+    pub fn is_histogram(&self) -> bool {
+        *self == Metric::Latency
+    }
+}
+
+/// Receives the client's metrics, to forward them to a monitoring system. Implemented for
+/// Prometheus (`PrometheusMetrics`, with the `prometheus` feature) and OpenTelemetry (`OtelMetrics`,
+/// with the `opentelemetry` feature); implement it to export anywhere else.
+pub trait MetricsSink: Send + Sync {
+    /// Add `value` to a counter, or observe it for a histogram.
+    fn record(&self, metric: Metric, model: &str, value: f64);
+}
+
+/// Histogram buckets for `Metric::Latency`, in seconds.
+pub const LATENCY_BUCKETS: [f64; 11] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0];
+
+/// Registers every `Metric` in a Prometheus registry, labeled by `model`.
+#[cfg(feature = "prometheus")]
+pub struct PrometheusMetrics {
+    counters: std::collections::HashMap<Metric, prometheus::CounterVec>,
+    latency: prometheus::HistogramVec,
+}
+
+#[cfg(feature = "prometheus")]
+impl PrometheusMetrics {
+    pub fn new(registry: &prometheus::Registry) -> prometheus::Result<Self> {
+        let mut counters = std::collections::HashMap::new();
+        for metric in Metric::ALL.into_iter().filter(|metric| !metric.is_histogram()) {
+            let counter = prometheus::CounterVec::new(prometheus::Opts::new(metric.name(), metric.help()), &["model"])?;
+            registry.register(Box::new(counter.clone()))?;
+            counters.insert(metric, counter);
+        }
+        let opts = prometheus::HistogramOpts::new(Metric::Latency.name(), Metric::Latency.help())
+            .buckets(LATENCY_BUCKETS.to_vec());
+        let latency = prometheus::HistogramVec::new(opts, &["model"])?;
+        registry.register(Box::new(latency.clone()))?;
+        Ok(Self { counters, latency })
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl MetricsSink for PrometheusMetrics {
+    fn record(&self, metric: Metric, model: &str, value: f64) {
+        match self.counters.get(&metric) {
+            Some(counter) => counter.with_label_values(&[model]).inc_by(value),
+            None => self.latency.with_label_values(&[model]).observe(value),
+        }
+    }
+}
+
+/// Creates an OpenTelemetry instrument for every `Metric` on a meter, with a `model` attribute.
+#[cfg(feature = "opentelemetry")]
+pub struct OtelMetrics {
+    counters: std::collections::HashMap<Metric, opentelemetry::metrics::Counter<f64>>,
+    latency: opentelemetry::metrics::Histogram<f64>,
+}
+
+#[cfg(feature = "opentelemetry")]
+impl OtelMetrics {
+    pub fn new(meter: &opentelemetry::metrics::Meter) -> Self {
+        let counters = Metric::ALL
+            .into_iter()
+            .filter(|metric| !metric.is_histogram())
+            .map(|metric| (metric, meter.f64_counter(metric.name()).with_description(metric.help()).build()))
+            .collect();
+        let latency = meter
+            .f64_histogram(Metric::Latency.name())
+            .with_description(Metric::Latency.help())
+            .with_unit("s")
+            .with_boundaries(LATENCY_BUCKETS.to_vec())
+            .build();
+        Self { counters, latency }
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+impl MetricsSink for OtelMetrics {
+    fn record(&self, metric: Metric, model: &str, value: f64) {
+        let attributes = [opentelemetry::KeyValue::new("model", model.to_string())];
+        match self.counters.get(&metric) {
+            Some(counter) => counter.add(value, &attributes),
+            None => self.latency.record(value, &attributes),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Compile-time checks that the types users share across threads stay `Send` and `Sync`, so a
+//! field added later can't quietly take that away.
+//!
+//! The guarantees: clients and backends are `Send + Sync` and can be shared behind an `Arc`;
+//! `DriveOptions`, transcripts, and usage and stats recorders are `Send + Sync` and cheap to clone
+//! into other tasks; and the future returned by `drive_with` is `Send` whenever the state is, so a
+//! run can be spawned onto a multi-threaded runtime.
+
+use crate::{
+    AiFunctionError, AiFunctionResponse, ChatCompletionRequest, ChatCompletionResponse, Message, Transcript,
+    TranscriptEntry,
+};
+#[cfg(feature = "openai")]
+use crate::{
+    drive_with, BatchClient, Budget, ChatError, DriveError, DriveOptions, DynAiState, FallbackBackend, MockBackend,
+    OpenAIClient, ResponseCache, SessionStats, SessionUsage,
+};
+
+const fn assert_send_sync<T: Send + Sync + ?Sized>() {}
+
+const _: () = {
+    assert_send_sync::<ChatCompletionRequest>();
+    assert_send_sync::<ChatCompletionResponse>();
+    assert_send_sync::<Message>();
+    assert_send_sync::<AiFunctionResponse>();
+    assert_send_sync::<AiFunctionError>();
+    assert_send_sync::<Transcript>();
+    assert_send_sync::<TranscriptEntry>();
+};
+
+#[cfg(feature = "openai")]
+const _: () = {
+    assert_send_sync::<OpenAIClient>();
+    assert_send_sync::<ChatError>();
+    assert_send_sync::<BatchClient>();
+    assert_send_sync::<FallbackBackend>();
+    assert_send_sync::<MockBackend>();
+    assert_send_sync::<ResponseCache>();
+    assert_send_sync::<DriveOptions>();
+    assert_send_sync::<DriveError>();
+    assert_send_sync::<SessionUsage>();
+    assert_send_sync::<SessionStats>();
+    assert_send_sync::<Budget>();
+};
+
+#[cfg(feature = "blocking")]
+const _: () = assert_send_sync::<crate::blocking::OpenAIClient>();
+
+/// Fails to compile if a run over a `Send` state stops being spawnable.
+#[cfg(feature = "openai")]
+#[allow(dead_code)]
+fn drive_is_send<'a, S: DynAiState + Send + ?Sized>(
+    state: &'a mut S,
+    options: &'a DriveOptions,
+) -> impl std::future::Future<Output = Result<(), DriveError>> + Send + 'a {
+    drive_with(state, options)
+}
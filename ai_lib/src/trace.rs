@@ -0,0 +1,102 @@
+//! `tracing` spans for the client and driver, compiled away without the `tracing` feature. Spans nest
+//! as `drive` > `prompt` (one per prompt iteration) > `chat_completion` and `call_function`.
+
+use std::future::Future;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+#[cfg(feature = "tracing")]
+impl Span {
+    pub fn drive(state: &str) -> Self {
+        Self { span: tracing::info_span!("drive", state) }
+    }
+
+    pub fn prompt(&self, iteration: u64, functions: &[String]) -> Self {
+        let functions = functions.join(",");
+        let span = tracing::info_span!(parent: &self.span, "prompt", iteration, functions, attempts = tracing::field::Empty);
+        Self { span }
+    }
+
+    /// A child of the current span, so it nests under the driver's prompt when called from it.
+    pub fn chat_completion(model: &str) -> Self {
+        let span = tracing::info_span!(
+            "chat_completion",
+            model,
+            prompt_tokens = tracing::field::Empty,
+            completion_tokens = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+            retries = tracing::field::Empty,
+        );
+        Self { span }
+    }
+
+    pub fn call_function(&self, function: &str) -> Self {
+        let span = tracing::info_span!(
+            parent: &self.span,
+            "call_function",
+            function,
+            latency_ms = tracing::field::Empty,
+            succeeded = tracing::field::Empty,
+        );
+        Self { span }
+    }
+
+    /// Record `value` for one of the span's declared fields.
+    pub fn record(&self, field: &'static str, value: u64) {
+        self.span.record(field, value);
+    }
+
+    pub fn record_bool(&self, field: &'static str, value: bool) {
+        self.span.record(field, value);
+    }
+
+    /// Record on whichever span is current, e.g. the retry count on `chat_completion`.
+    pub fn record_current(field: &'static str, value: u64) {
+        tracing::Span::current().record(field, value);
+    }
+
+    pub fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.span.in_scope(f)
+    }
+
+    pub async fn instrument<F: Future>(&self, future: F) -> F::Output {
+        tracing::Instrument::instrument(future, self.span.clone()).await
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub fn drive(_state: &str) -> Self {
+        Self {}
+    }
+
+    pub fn prompt(&self, _iteration: u64, _functions: &[String]) -> Self {
+        Self {}
+    }
+
+    pub fn chat_completion(_model: &str) -> Self {
+        Self {}
+    }
+
+    pub fn call_function(&self, _function: &str) -> Self {
+        Self {}
+    }
+
+    pub fn record(&self, _field: &'static str, _value: u64) {}
+
+    pub fn record_bool(&self, _field: &'static str, _value: bool) {}
+
+    pub fn record_current(_field: &'static str, _value: u64) {}
+
+    pub fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        f()
+    }
+
+    pub async fn instrument<F: Future>(&self, future: F) -> F::Output {
+        future.await
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{CalledFunction, ChatCompletionRequest, ChatCompletionResponse, Message};
+
+/// One request sent during a run and the response it got.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub request: ChatCompletionRequest,
+    pub response: ChatCompletionResponse,
+    /// Whether the driver accepted the reply and moved on, rather than retrying the prompt
+    pub accepted: bool,
+}
+
+/// Every request and response of a run, in order. Cheap to clone; clones share the same entries,
+/// so one can be handed to the driver and another kept for inspection.
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    entries: Arc<Mutex<Vec<TranscriptEntry>>>,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, request: &ChatCompletionRequest, response: &ChatCompletionResponse) {
+        let entry = TranscriptEntry { request: request.clone(), response: response.clone(), accepted: false };
+        self.entries.lock().unwrap().push(entry);
+    }
+
+    /// Mark the most recent entry as accepted.
+    pub(crate) fn accept_last(&self) {
+        if let Some(entry) = self.entries.lock().unwrap().last_mut() {
+            entry.accepted = true;
+        }
+    }
+
+    pub fn entries(&self) -> Vec<TranscriptEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// The distinct `system_fingerprint`s of the responses, in order of first appearance. More than
+    /// one means the backend changed during the run, so rerunning it with the same seed may diverge.
+    pub fn system_fingerprints(&self) -> Vec<String> {
+        let mut fingerprints: Vec<String> = vec![];
+        for entry in self.entries.lock().unwrap().iter() {
+            if let Some(fingerprint) = &entry.response.system_fingerprint {
+                if !fingerprints.contains(fingerprint) {
+                    fingerprints.push(fingerprint.clone());
+                }
+            }
+        }
+        fingerprints
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self.entries()).expect("Transcript is serializable")
+    }
+
+    /// A copy of the transcript that is safe to attach to a bug report. Function schemas, models,
+    /// and the shape of every call are kept, so the run can still be diagnosed; prompt text and the
+    /// data flowing through function arguments are hashed, templated, or masked per `profile`.
+    pub fn redacted(&self, profile: &RedactionProfile) -> Transcript {
+        let mut redactor = Redactor { profile, templates: HashMap::new() };
+        let entries = self
+            .entries()
+            .into_iter()
+            .map(|mut entry| {
+                for message in &mut entry.request.messages {
+                    redactor.message(message);
+                }
+                for choice in &mut entry.response.choices {
+                    redactor.message(&mut choice.message);
+                }
+                entry
+            })
+            .collect();
+        Transcript { entries: Arc::new(Mutex::new(entries)) }
+    }
+}
+
+/// What happens to the text of system and user messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptRedaction {
+    /// Leave prompts as they are
+    Keep,
+    /// Replace each prompt with a short SHA-256 prefix, so identical prompts can still be matched up
+    /// (and checked against a prompt the reporter shares privately)
+    #[default]
+    Hash,
+    /// Replace each distinct prompt with a numbered placeholder such as `{{prompt_2}} (184 chars)`
+    Template,
+}
+
+/// How much of a transcript to hide when sharing it. `RedactionProfile::default()` hashes prompts
+/// and masks user data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactionProfile {
+    pub prompts: PromptRedaction,
+    /// Mask every string in function-call arguments and any free-text assistant replies, keeping
+    /// object keys, numbers, booleans, and array lengths
+    pub mask_user_data: bool,
+}
+
+impl Default for RedactionProfile {
+    fn default() -> Self {
+        Self { prompts: PromptRedaction::Hash, mask_user_data: true }
+    }
+}
+
+impl RedactionProfile {
+    /// Keep prompts readable but mask user data, for when only the data is sensitive.
+    pub fn data_only() -> Self {
+        Self { prompts: PromptRedaction::Keep, mask_user_data: true }
+    }
+
+    /// Template prompts and mask user data.
+    pub fn templated() -> Self {
+        Self { prompts: PromptRedaction::Template, mask_user_data: true }
+    }
+}
+
+struct Redactor<'a> {
+    profile: &'a RedactionProfile,
+    templates: HashMap<String, usize>,
+}
+
+impl Redactor<'_> {
+    fn message(&mut self, message: &mut Message) {
+        if let Some(function_call) = &mut message.function_call {
+            self.function_call(function_call);
+        }
+        let Some(content) = &mut message.content else {
+            return;
+        };
+        if message.role == "assistant" {
+            // The driver feeds calls back into the history as JSON content
+            match serde_json::from_str::<CalledFunction>(content) {
+                Ok(mut function_call) => {
+                    self.function_call(&mut function_call);
+                    *content = serde_json::to_string(&function_call).unwrap();
+                }
+                Err(_) if self.profile.mask_user_data => *content = mask_string(content),
+                Err(_) => {}
+            }
+        } else {
+            *content = self.prompt(content);
+        }
+    }
+
+    fn function_call(&self, function_call: &mut CalledFunction) {
+        if !self.profile.mask_user_data {
+            return;
+        }
+        function_call.arguments = match serde_json::from_str(&function_call.arguments) {
+            Ok(arguments) => mask_value(arguments).to_string(),
+            Err(_) => mask_string(&function_call.arguments),
+        };
+    }
+
+    fn prompt(&mut self, prompt: &str) -> String {
+        match self.profile.prompts {
+            PromptRedaction::Keep => prompt.to_string(),
+            PromptRedaction::Hash => {
+                let digest = Sha256::digest(prompt.as_bytes());
+                let hex: String = digest.iter().take(8).map(|b| format!("{b:02x}")).collect();
+                format!("sha256:{hex}")
+            }
+            PromptRedaction::Template => {
+                let next = self.templates.len() + 1;
+                let n = *self.templates.entry(prompt.to_string()).or_insert(next);
+                format!("{{{{prompt_{n}}}}} ({} chars)", prompt.chars().count())
+            }
+        }
+    }
+}
+
+fn mask_string(s: &str) -> String {
+    format!("<redacted, {} chars>", s.chars().count())
+}
+
+fn mask_value(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(s) => mask_string(&s).into(),
+        serde_json::Value::Array(items) => items.into_iter().map(mask_value).collect(),
+        serde_json::Value::Object(object) => object.into_iter().map(|(k, v)| (k, mask_value(v))).collect(),
+        other => other,
+    }
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ChatError, OpenAIClient};
+
+/// Model used by `transcribe`
+const TRANSCRIPTION_MODEL: &str = "whisper-1";
+
+/// The text of an audio recording, e.g. a voice note to feed into a prompt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transcription {
+    pub text: String,
+    /// The detected language, e.g. `english`
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Length of the recording in seconds
+    #[serde(default)]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub segments: Vec<TranscriptionSegment>,
+}
+
+/// A stretch of the recording and its text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionSegment {
+    /// Offsets into the recording in seconds
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+impl OpenAIClient {
+    /// Transcribe an audio recording (e.g. MP3, WAV, or M4A, its format taken from `filename`).
+    /// Retried like chat completions per the client's `RetryPolicy`.
+    pub async fn transcribe(&self, filename: impl Into<String>, audio: Vec<u8>) -> Result<Transcription, ChatError> {
+        let filename = filename.into();
+        let form = || {
+            reqwest::multipart::Form::new()
+                .text("model", TRANSCRIPTION_MODEL)
+                .text("response_format", "verbose_json")
+                .part("file", reqwest::multipart::Part::bytes(audio.clone()).file_name(filename.clone()))
+        };
+        self.post_multipart("audio/transcriptions", form, TRANSCRIPTION_MODEL).await
+    }
+
+    /// Transcribe the audio file at `path`.
+    pub async fn transcribe_file(&self, path: impl AsRef<std::path::Path>) -> Result<Transcription, ChatError> {
+        let path = path.as_ref();
+        let audio = tokio::fs::read(path).await.map_err(|e| ChatError::Other(format!("Failed to read {}: {e}", path.display())))?;
+        let filename = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        self.transcribe(filename, audio).await
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::{global_pricing, ChatCompletionResponse, Usage};
+
+/// Which provider and API key served a response, for cost attribution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Attribution {
+    pub provider: String,
+    /// A masked identifier for the API key (never the key itself), if the backend uses one
+    pub key_id: Option<String>,
+}
+
+impl Attribution {
+    pub fn new(provider: impl Into<String>, api_key: Option<&str>) -> Self {
+        Self { provider: provider.into(), key_id: api_key.map(mask_key) }
+    }
+}
+
+/// Identify a key by its last four characters, e.g. `sk-...WXYZ`, so reports can be reconciled
+/// against provider invoices without leaking secrets.
+pub fn mask_key(api_key: &str) -> String {
+    let tail: String = api_key.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+    format!("sk-...{tail}")
+}
+
+/// The usage of a single response.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageRecord {
+    pub provider: String,
+    pub key_id: Option<String>,
+    pub model: String,
+    /// The function the response called, if any
+    pub function: Option<String>,
+    /// The `drive` run the response belongs to, numbered from 1 per `SessionUsage`
+    pub run: Option<u64>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Cost in USD, or `None` if the model isn't in the pricing table
+    pub cost: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+    /// Requests whose model had no known price, and so aren't included in `cost`
+    pub unpriced_requests: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, record: &UsageRecord) {
+        self.requests += 1;
+        self.prompt_tokens += record.prompt_tokens;
+        self.completion_tokens += record.completion_tokens;
+        match record.cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced_requests += 1,
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// e.g. `3 requests, 1234 prompt + 56 completion tokens, $0.4200`
+impl fmt::Display for UsageTotals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requests, {} prompt + {} completion tokens, ${:.4}",
+            self.requests, self.prompt_tokens, self.completion_tokens, self.cost,
+        )?;
+        if self.unpriced_requests > 0 {
+            write!(f, " ({} unpriced)", self.unpriced_requests)?;
+        }
+        Ok(())
+    }
+}
+
+/// Usage accumulated over a session, broken down by provider, API key, model, function, and
+/// `drive` run, priced with the global pricing table. Cheap to clone; clones share the same
+/// underlying records, so one can be handed to the driver and another kept for reporting.
+#[derive(Debug, Clone, Default)]
+pub struct SessionUsage {
+    records: Arc<Mutex<Vec<UsageRecord>>>,
+    runs: Arc<AtomicU64>,
+}
+
+impl SessionUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the usage of a response, priced with the global pricing table.
+    pub fn record_response(&self, response: &ChatCompletionResponse) {
+        self.record_with(response, None, None);
+    }
+
+    /// Number a new `drive` run, to attribute its responses to with `record_step`.
+    pub(crate) fn start_run(&self) -> u64 {
+        self.runs.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Record a response received during `run`, attributed to the function it called.
+    pub(crate) fn record_step(&self, run: u64, response: &ChatCompletionResponse) {
+        let function = response.choices.first().and_then(|choice| choice.message.function_call.as_ref());
+        self.record_with(response, Some(run), function.map(|function| function.name.clone()));
+    }
+
+    fn record_with(&self, response: &ChatCompletionResponse, run: Option<u64>, function: Option<String>) {
+        let (provider, key_id) = match &response.attribution {
+            Some(attribution) => (attribution.provider.clone(), attribution.key_id.clone()),
+            None => ("unknown".to_string(), None),
+        };
+        self.push(UsageRecord { function, run, ..Self::new_record(provider, key_id, &response.model, &response.usage) });
+    }
+
+    pub fn record(&self, provider: impl Into<String>, key_id: Option<String>, model: &str, usage: &Usage) {
+        self.push(Self::new_record(provider.into(), key_id, model, usage));
+    }
+
+    fn new_record(provider: String, key_id: Option<String>, model: &str, usage: &Usage) -> UsageRecord {
+        UsageRecord {
+            provider,
+            key_id,
+            model: model.to_string(),
+            function: None,
+            run: None,
+            prompt_tokens: usage.prompt_tokens.max(0) as u64,
+            completion_tokens: usage.completion_tokens.max(0) as u64,
+            cost: global_pricing().cost(model, usage),
+        }
+    }
+
+    fn push(&self, record: UsageRecord) {
+        self.records.lock().unwrap().push(record);
+    }
+
+    pub fn records(&self) -> Vec<UsageRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    pub fn total(&self) -> UsageTotals {
+        let mut totals = UsageTotals::default();
+        for record in self.records.lock().unwrap().iter() {
+            totals.add(record);
+        }
+        totals
+    }
+
+    pub fn by_provider(&self) -> BTreeMap<String, UsageTotals> {
+        self.group_by(|record| record.provider.clone())
+    }
+
+    /// Totals per `(provider, key_id)`; records without a key are grouped under `"-"`.
+    pub fn by_key(&self) -> BTreeMap<(String, String), UsageTotals> {
+        self.group_by(|record| (record.provider.clone(), record.key_id.clone().unwrap_or_else(|| "-".to_string())))
+    }
+
+    pub fn by_model(&self) -> BTreeMap<String, UsageTotals> {
+        self.group_by(|record| record.model.clone())
+    }
+
+    /// Totals per function called; text replies are grouped under `"(text reply)"`. Only responses
+    /// recorded by the driver are included.
+    pub fn by_function(&self) -> BTreeMap<String, UsageTotals> {
+        let mut groups: BTreeMap<String, UsageTotals> = BTreeMap::new();
+        for record in self.records.lock().unwrap().iter().filter(|record| record.run.is_some()) {
+            let function = record.function.clone().unwrap_or_else(|| "(text reply)".to_string());
+            groups.entry(function).or_default().add(record);
+        }
+        groups
+    }
+
+    /// Totals per `drive` run, in the order they started.
+    pub fn by_run(&self) -> BTreeMap<u64, UsageTotals> {
+        let mut groups: BTreeMap<u64, UsageTotals> = BTreeMap::new();
+        for record in self.records.lock().unwrap().iter() {
+            if let Some(run) = record.run {
+                groups.entry(run).or_default().add(record);
+            }
+        }
+        groups
+    }
+
+    /// Totals of the most recently started `drive` run, e.g. to print "this run cost $0.42".
+    pub fn last_run(&self) -> Option<UsageTotals> {
+        self.by_run().pop_last().map(|(_, totals)| totals)
+    }
+
+    fn group_by<K: Ord>(&self, key: impl Fn(&UsageRecord) -> K) -> BTreeMap<K, UsageTotals> {
+        let mut groups: BTreeMap<K, UsageTotals> = BTreeMap::new();
+        for record in self.records.lock().unwrap().iter() {
+            groups.entry(key(record)).or_default().add(record);
+        }
+        groups
+    }
+
+    /// A JSON report with the session total and per-provider, per-key, per-model, per-function, and
+    /// per-run breakdowns.
+    pub fn to_json(&self) -> serde_json::Value {
+        let by_key: Vec<_> = self
+            .by_key()
+            .into_iter()
+            .map(|((provider, key_id), totals)| serde_json::json!({ "provider": provider, "key_id": key_id, "totals": totals }))
+            .collect();
+        serde_json::json!({
+            "total": self.total(),
+            "by_provider": self.by_provider(),
+            "by_key": by_key,
+            "by_model": self.by_model(),
+            "by_function": self.by_function(),
+            "by_run": self.by_run(),
+        })
+    }
+
+    /// One CSV row per `(provider, key, model)`, for reconciling against invoices.
+    pub fn to_csv(&self) -> String {
+        let groups = self.group_by(|record| {
+            (record.provider.clone(), record.key_id.clone().unwrap_or_default(), record.model.clone())
+        });
+        let mut csv = "provider,key_id,model,requests,prompt_tokens,completion_tokens,cost_usd\n".to_string();
+        for ((provider, key_id, model), totals) in groups {
+            csv.push_str(&format!(
+                "{provider},{key_id},{model},{},{},{},{:.6}\n",
+                totals.requests, totals.prompt_tokens, totals.completion_tokens, totals.cost,
+            ));
+        }
+        csv
+    }
+}
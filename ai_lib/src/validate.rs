@@ -0,0 +1,98 @@
+use convert_case::{Case, Casing};
+use jsonschema::error::ValidationErrorKind;
+use jsonschema::paths::PathChunk;
+use jsonschema::{Draft, JSONSchema};
+
+use crate::elide::{elide_value, DEFAULT_ECHO_LIMIT};
+use crate::AiFunctionError;
+
+/// A schema violation, along with the top-level argument it belongs to (if any).
+pub(crate) struct SchemaError {
+    pub field: Option<String>,
+    pub message: String,
+}
+
+/// Validate raw function-call arguments against the function's JSON schema, before serde gets a
+/// look at them. Errors are reported against the offending path, e.g. `items[2]: "ab" is shorter
+/// than 3 characters`, so the model can see exactly which value to fix.
+///
+/// Long offending values are elided to their head and tail, so retries don't re-send them in full.
+pub fn validate_arguments(schema: &serde_json::Value, arguments: &str) -> Result<(), AiFunctionError> {
+    validate_arguments_with_limit(schema, arguments, DEFAULT_ECHO_LIMIT)
+}
+
+pub(crate) fn validate_arguments_with_limit(schema: &serde_json::Value, arguments: &str, echo_limit: usize) -> Result<(), AiFunctionError> {
+    let errors = schema_errors(schema, arguments, echo_limit)?;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors_to_recoverable(&errors))
+    }
+}
+
+pub(crate) fn errors_to_recoverable(errors: &[SchemaError]) -> AiFunctionError {
+    let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+    AiFunctionError::Recoverable(format!("Arguments do not match the schema:\n{}", messages.join("\n")))
+}
+
+pub(crate) fn schema_errors(schema: &serde_json::Value, arguments: &str, echo_limit: usize) -> Result<Vec<SchemaError>, AiFunctionError> {
+    let mut instance: serde_json::Value = serde_json::from_str(arguments)?;
+
+    // The schema uses camelCase property names, but the generated Args structs accept snake/camel/pascal
+    // aliases for top-level arguments, so normalize those before validating
+    if let Some(object) = instance.as_object_mut() {
+        *object = std::mem::take(object)
+            .into_iter()
+            .map(|(key, value)| (key.to_case(Case::Camel), value))
+            .collect();
+    }
+
+    let compiled = JSONSchema::options()
+        .with_draft(Draft::Draft201909)
+        .compile(schema)
+        .map_err(|e| AiFunctionError::Unrecoverable(format!("Invalid function schema: {e}")))?;
+
+    let result = compiled.validate(&instance);
+    let errors = match result {
+        Ok(()) => vec![],
+        Err(errors) => errors
+            .map(|e| {
+                let field = match (e.instance_path.iter().next(), &e.kind) {
+                    (Some(PathChunk::Property(name)), _) => Some(name.to_string()),
+                    (None, ValidationErrorKind::Required { property }) => property.as_str().map(String::from),
+                    _ => None,
+                };
+                let path = format_path(e.instance_path.iter());
+                let e = jsonschema::ValidationError { instance: std::borrow::Cow::Owned(elide_value(&e.instance, echo_limit)), ..e };
+                let message = match path {
+                    path if path.is_empty() => e.to_string(),
+                    path => format!("{path}: {e}"),
+                };
+                SchemaError { field, message }
+            })
+            .collect(),
+    };
+    Ok(errors)
+}
+
+fn format_path<'a>(chunks: impl Iterator<Item = &'a PathChunk>) -> String {
+    let mut path = String::new();
+    for chunk in chunks {
+        match chunk {
+            PathChunk::Property(name) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(name);
+            }
+            PathChunk::Index(i) => path.push_str(&format!("[{i}]")),
+            PathChunk::Keyword(keyword) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(keyword);
+            }
+        }
+    }
+    path
+}
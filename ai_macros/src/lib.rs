@@ -4,13 +4,23 @@ use convert_case::{Case, Casing};
 use proc_macro::TokenStream;
 use proc_macro2::Group;
 use quote::quote;
-use syn::{parse_macro_input, Ident, FnArg, Pat, PatIdent, AttributeArgs, NestedMeta, Meta, ItemImpl};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Attribute, Expr, ExprClosure, Ident, FnArg, Pat, PatIdent, AttributeArgs, NestedMeta, Meta, ItemImpl, Token, Type};
+
+/// Function names the driver offers the model itself, which an `#[ai_function]` must not reuse.
+/// Collisions between states and other tools are caught by `ai_lib::FunctionRegistry`.
+const RESERVED_FUNCTIONS: &[&str] = &["provide_missing_fields"];
 
 #[proc_macro_attribute]
 pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut item_impl = parse_macro_input!(item as ItemImpl);
 
     let struct_ident = item_impl.self_ty.clone();
+    let struct_name = match struct_ident.as_ref() {
+        Type::Path(path) => path.path.segments.last().map(|segment| segment.ident.to_string()),
+        _ => None,
+    };
     let (impl_generics, ty_generics, where_clause) = item_impl.generics.split_for_impl();
 
     // Add two methods:
@@ -19,17 +29,48 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
     let mut json_schema_branches = vec![];
     let mut json_call_branches = vec![];
+    let mut concurrency_group_branches = vec![];
+    let mut weight_branches = vec![];
+    let mut escape_hatches = vec![];
+    let mut alias_branches = vec![];
+    let mut manifest_entries = vec![];
+
+    // The method marked #[ai_output], if any, provides AiState::Output
+    let mut output = None;
 
     for item in item_impl.items.iter_mut() {
         if let syn::ImplItem::Method(method) = item {
 
             let fn_name = method.sig.ident.clone();
+            let block = &method.block;
+            let mut next_functions = vec![];
+            prompted_functions(quote! { #block }, &fn_name.to_string(), &mut next_functions);
+
+            let output_type = match &method.sig.output {
+                syn::ReturnType::Type(_, ty) => quote! { #ty },
+                syn::ReturnType::Default => quote! { () },
+            };
 
             method.attrs.retain_mut(|attr| {
-                if attr.path.get_ident().unwrap().to_string() == "ai_function" {
+                if *attr.path.get_ident().unwrap() == "ai_output" {
+                    if output.is_some() {
+                        panic!("Only one method can be marked #[ai_output], found another on {}", fn_name);
+                    }
+                    output = Some((fn_name.clone(), output_type.clone()));
+                    false
+                } else if *attr.path.get_ident().unwrap() == "ai_function" {
 
                     let mut description = None;
+                    let mut concurrency_group = None;
+                    let mut weight = None;
+                    let mut max_schema_depth = None;
+                    let mut escape_hatch = false;
+                    let mut deprecated = None;
+                    let mut examples = vec![];
+                    let mut tags = vec![];
                     let mut arg_descriptions = HashMap::new();
+                    let mut alias_cases = vec![Case::Snake, Case::Camel, Case::Pascal];
+                    let mut arg_aliases: Vec<(String, String)> = vec![];
 
                     if let Ok(group) = syn::parse_macro_input::parse::<Group>(attr.tokens.clone().into()) {
                         if let Ok(attr_args) = syn::parse_macro_input::parse::<AttributeArgs>(group.stream().into()) {
@@ -41,10 +82,54 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
                                                 let path = path.get_ident().unwrap().to_string();
                                                 if path == "fn_description" {
                                                     description = Some(lit_str.value());
+                                                } else if path == "concurrency_group" {
+                                                    concurrency_group = Some(lit_str.value());
+                                                } else if path == "deprecated" {
+                                                    deprecated = Some(lit_str.value());
+                                                } else if path == "example" {
+                                                    let example = lit_str.value();
+                                                    if let Err(e) = serde_json::from_str::<serde_json::Value>(&example) {
+                                                        panic!("example on {} isn't valid JSON: {}", fn_name, e);
+                                                    }
+                                                    examples.push(example);
                                                 } else {
                                                     arg_descriptions.insert(path.to_string(), lit_str.value());
                                                 }
                                             }
+                                            Meta::NameValue(syn::MetaNameValue { path, lit: syn::Lit::Int(lit_int), .. }) if path.is_ident("weight") => {
+                                                weight = Some(lit_int.base10_parse::<i32>().unwrap());
+                                            }
+                                            Meta::NameValue(syn::MetaNameValue { path, lit: syn::Lit::Int(lit_int), .. }) if path.is_ident("max_schema_depth") => {
+                                                match lit_int.base10_parse::<usize>() {
+                                                    Ok(depth) if depth > 0 => max_schema_depth = Some(depth),
+                                                    _ => panic!("max_schema_depth on {} must be a positive integer, got {}", fn_name, lit_int),
+                                                }
+                                            }
+                                            Meta::Path(path) if path.is_ident("escape_hatch") => {
+                                                escape_hatch = true;
+                                            }
+                                            Meta::List(list) if list.path.is_ident("alias_cases") => {
+                                                alias_cases = list.nested.iter().map(|case| match case {
+                                                    NestedMeta::Meta(Meta::Path(path)) => parse_case(&path.get_ident().unwrap().to_string()),
+                                                    _ => panic!("alias_cases takes case names, e.g. alias_cases(snake, kebab)"),
+                                                }).collect();
+                                            }
+                                            Meta::List(list) if list.path.is_ident("tags") => {
+                                                tags = list.nested.iter().map(|tag| match tag {
+                                                    NestedMeta::Lit(syn::Lit::Str(lit_str)) => lit_str.value(),
+                                                    _ => panic!("tags takes string literals, e.g. tags(\"network\", \"payments\")"),
+                                                }).collect();
+                                            }
+                                            Meta::List(list) if list.path.is_ident("arg_alias") => {
+                                                for alias in list.nested.iter() {
+                                                    match alias {
+                                                        NestedMeta::Meta(Meta::NameValue(syn::MetaNameValue { path, lit: syn::Lit::Str(lit_str), .. })) => {
+                                                            arg_aliases.push((path.get_ident().unwrap().to_string(), lit_str.value()));
+                                                        }
+                                                        _ => panic!("arg_alias takes argument = \"alias\" pairs"),
+                                                    }
+                                                }
+                                            }
                                             _ => todo!(),
                                         } 
                                     }
@@ -56,6 +141,9 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
                     let method_name = method.sig.ident.clone();
                     let method_str = method_name.to_string();
+                    if RESERVED_FUNCTIONS.contains(&method_str.as_str()) {
+                        panic!("{method_str} is reserved by the driver for its clarification dialogue and would shadow this function; rename it");
+                    }
     
                     let mut schema_struct_fields = vec![];
                     let mut args_struct_fields = vec![];
@@ -66,18 +154,41 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
                             if let Pat::Ident(PatIdent { ident, .. }) = arg.pat.as_ref() {
                                 let field_ident = Ident::new(&ident.to_string(), ident.span());
                                 let field_type = arg.ty.clone();
+                                let type_str = type_string(&field_type);
+                                if mentions_type(quote! { #field_type }, struct_name.as_deref()) {
+                                    panic!(
+                                        "Argument {} of {} has type {}, which contains the state type itself; \
+                                         a parameter schema can't describe the state, and would be recursive if it did",
+                                        ident, fn_name, type_str,
+                                    );
+                                }
+                                if let Some(max_depth) = max_schema_depth {
+                                    let depth = collection_depth(&field_type);
+                                    if depth > max_depth {
+                                        panic!(
+                                            "Argument {} of {} has type {}, nested {} levels deep, beyond its max_schema_depth of {}",
+                                            ident, fn_name, type_str, depth, max_depth,
+                                        );
+                                    }
+                                }
                                 let field_description = match arg_descriptions.get(&ident.to_string()) {
                                     Some(desc) => quote! { #[schemars(description = #desc)] },
                                     None => quote! {},
                                 };
                                 schema_struct_fields.push(quote! { #field_description #field_ident: #field_type });
 
-                                // Create aliases for all the different possible cases, e.g. snake, camel, pascal
-                                let mut serde_aliases = quote! {};
-                                for case in [Case::Snake, Case::Camel, Case::Pascal] {
-                                    let alias = ident.to_string().to_case(case);
-                                    serde_aliases = quote! { #serde_aliases #[serde(alias = #alias)] };
+                                // Create aliases for each configured case (snake, camel, and pascal by default),
+                                // plus any custom ones from arg_alias. Camel case is always accepted, since it's
+                                // what the schema declares and what pinned and clarified arguments are merged as
+                                let mut aliases: Vec<String> = vec![];
+                                let custom = arg_aliases.iter().filter(|(arg, _)| ident == arg).map(|(_, alias)| alias.clone());
+                                let cases = std::iter::once(&Case::Camel).chain(alias_cases.iter());
+                                for alias in cases.map(|case| ident.to_string().to_case(*case)).chain(custom) {
+                                    if !aliases.contains(&alias) {
+                                        aliases.push(alias);
+                                    }
                                 }
+                                let serde_aliases = quote! { #(#[serde(alias = #aliases)])* };
 
                                 args_struct_fields.push(quote! { #serde_aliases #field_ident: #field_type });
                                 field_names.push(field_ident);
@@ -85,14 +196,26 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
                         }
                     }
 
-                    for field_name in arg_descriptions.keys() {
-                        if !field_names.iter().any(|name| name.to_string() == *field_name) {
+                    for field_name in arg_descriptions.keys().chain(arg_aliases.iter().map(|(arg, _)| arg)) {
+                        if !field_names.iter().any(|name| name == field_name) {
                             panic!("Field {} does not exist in function {}", field_name, fn_name);
                         }
                     }
+                    if !arg_aliases.is_empty() {
+                        let pairs = arg_aliases.iter().map(|(arg, alias)| quote! { (#alias, #arg) });
+                        alias_branches.push(quote! { #method_str => &[#(#pairs),*] });
+                    }
 
                     let description = description.unwrap_or(method_str.clone());
+                    let description = match &deprecated {
+                        Some(message) => format!("Deprecated: {message}. {description}"),
+                        None => description,
+                    };
 
+                    let schema = match max_schema_depth {
+                        Some(depth) => quote! { ai_lib::schema_with_depth::<Args>(Some(#depth)) },
+                        None => quote! { ai_lib::schema::<Args>() },
+                    };
                     let json_schema_branch = quote! {
                         #method_str => {
                             #[derive(JsonSchema)]
@@ -102,7 +225,8 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
                                 #(#schema_struct_fields),*
                             }
                             
-                            let parameters = ai_lib::schema::<Args>();
+                            let mut parameters = #schema;
+                            ai_lib::add_examples(&mut parameters, &[#(#examples),*]);
 
                             Some(ai_lib::Function {
                                 name: #method_str.into(),
@@ -113,19 +237,61 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
                     };
                     json_schema_branches.push(json_schema_branch);
 
-                    let json_call_branch = quote! {
-                        #method_str => {
-                            #[derive(Deserialize)]
-                            struct Args {
-                                #(#args_struct_fields),*
+                    // Deprecated functions stay declared, so old prompts and recorded calls still resolve,
+                    // but calling one only tells the model what to use instead
+                    let json_call_branch = match &deprecated {
+                        Some(message) => {
+                            let nudge = format!("{method_str} is deprecated: {message}");
+                            quote! {
+                                #method_str => {
+                                    // The method is never called through the model, but isn't dead
+                                    let _ = Self::#fn_name;
+                                    ai_lib::recoverable_err(#nudge)
+                                }
                             }
-    
-                            let args: Args = serde_json::from_str(arg)?;
-                            Self::#fn_name(self, #(args.#field_names),*)
                         }
+                        None => quote! {
+                            #method_str => {
+                                #[derive(Deserialize)]
+                                struct Args {
+                                    #(#args_struct_fields),*
+                                }
+
+                                let args: Args = ai_lib::parse_arguments(arg, || {
+                                    Self::json_schema_for_function(#method_str).map(|function| function.parameters)
+                                })?;
+                                Self::#fn_name(self, #(args.#field_names),*)
+                            }
+                        },
                     };
                     json_call_branches.push(json_call_branch);
 
+                    if let Some(group) = concurrency_group {
+                        concurrency_group_branches.push(quote! { #method_str => Some(#group) });
+                    }
+                    if let Some(weight) = weight {
+                        weight_branches.push(quote! { #method_str => #weight });
+                    }
+                    if escape_hatch {
+                        escape_hatches.push(method_str.clone());
+                    }
+
+                    let deprecated = match &deprecated {
+                        Some(message) => quote! { Some(#message.to_string()) },
+                        None => quote! { None },
+                    };
+                    manifest_entries.push(quote! {
+                        ai_lib::FunctionManifest {
+                            function: Self::json_schema_for_function(#method_str).unwrap(),
+                            tags: vec![#(#tags.to_string()),*],
+                            concurrency_group: Self::concurrency_group(#method_str).map(String::from),
+                            weight: Self::function_weight(#method_str),
+                            escape_hatch: #escape_hatch,
+                            deprecated: #deprecated,
+                            next: vec![#(#next_functions.to_string()),*],
+                        }
+                    });
+
                     false
                 } else {
                     true
@@ -134,10 +300,27 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     }
 
+    let output_impl = match output {
+        Some((fn_name, output_type)) => quote! {
+            type Output = #output_type;
+
+            fn output(&self) -> Self::Output {
+                Self::#fn_name(self)
+            }
+        },
+        None => quote! {
+            type Output = ();
+
+            fn output(&self) -> Self::Output {}
+        },
+    };
+
     quote! {
         #item_impl
 
         impl #impl_generics ai_lib::AiState for #struct_ident #ty_generics #where_clause {
+            #output_impl
+
             fn json_schema_for_function(function_name: &str) -> Option<ai_lib::Function> {
                 match function_name {
                     #(#json_schema_branches),*
@@ -145,6 +328,38 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 }
             }
 
+            fn concurrency_group(function_name: &str) -> Option<&'static str> {
+                match function_name {
+                    #(#concurrency_group_branches,)*
+                    _ => None,
+                }
+            }
+
+            fn function_weight(function_name: &str) -> i32 {
+                match function_name {
+                    #(#weight_branches,)*
+                    _ => 0,
+                }
+            }
+
+            fn escape_hatches() -> &'static [&'static str] {
+                &[#(#escape_hatches),*]
+            }
+
+            fn argument_aliases(function_name: &str) -> &'static [(&'static str, &'static str)] {
+                match function_name {
+                    #(#alias_branches,)*
+                    _ => &[],
+                }
+            }
+
+            fn manifest() -> ai_lib::AgentManifest {
+                ai_lib::AgentManifest {
+                    agent: ::std::any::type_name::<Self>().to_string(),
+                    functions: vec![#(#manifest_entries),*],
+                }
+            }
+
             fn call_function(&mut self, function_name: &str, arg: &str) -> ai_lib::AiFunctionResult {
                 match function_name {
                     #(#json_call_branches),*
@@ -154,3 +369,291 @@ pub fn ai_functions(_attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     }.into()
 }
+
+/// Define and run a one-off agent inline, without naming a struct and writing impl blocks:
+///
+/// ```ignore
+/// let story = drive! {
+///     state { topic: String = String::new() },
+///     initial = prompt!("Write a random topic for a story" => [write_topic]),
+///     /// Write the topic
+///     write_topic = |state, topic: String| {
+///         state.topic = topic;
+///         done()
+///     },
+/// }.await?;
+/// println!("{}", story.topic);
+/// ```
+///
+/// Each function is a closure whose first parameter is the state and whose other parameters are the
+/// typed arguments the model fills in; doc comments become descriptions. An `options = <DriveOptions>`
+/// entry runs with `drive_with`. The macro is a future resolving to the final state.
+#[proc_macro]
+pub fn drive(input: TokenStream) -> TokenStream {
+    let DriveInput { fields, initial, options, functions } = parse_macro_input!(input as DriveInput);
+
+    let field_names: Vec<_> = fields.iter().map(|f| &f.name).collect();
+    let field_types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+    let field_values: Vec<_> = fields.iter().map(|f| &f.value).collect();
+
+    let mut methods = vec![];
+    for DriveFunction { attrs, name, closure } in functions {
+        let mut inputs = closure.inputs.iter();
+        let state = match inputs.next() {
+            Some(Pat::Ident(PatIdent { ident, .. })) => ident.clone(),
+            _ => panic!("The first parameter of {name} must name the state, e.g. |state, ...|"),
+        };
+
+        let mut params = vec![];
+        let mut arg_descriptions = vec![];
+        for input in inputs {
+            let Pat::Type(pat_type) = input else {
+                panic!("Parameters of {name} must have types, e.g. |state, topic: String|");
+            };
+            let Pat::Ident(PatIdent { ident, .. }) = pat_type.pat.as_ref() else {
+                panic!("Parameters of {name} must be plain identifiers");
+            };
+            if let Some(description) = doc_string(&pat_type.attrs) {
+                arg_descriptions.push(quote! { #ident = #description });
+            }
+            let ty = &pat_type.ty;
+            params.push(quote! { #ident: #ty });
+        }
+
+        let description = doc_string(&attrs).unwrap_or_else(|| name.to_string());
+        let body = &closure.body;
+        methods.push(quote! {
+            #[ai_function(fn_description = #description #(, #arg_descriptions)*)]
+            #[allow(unused_variables)]
+            fn #name(&mut self, #(#params),*) -> ai_lib::AiFunctionResult {
+                let #state = self;
+                #body
+            }
+        });
+    }
+
+    let run = match options {
+        Some(options) => quote! { ai_lib::drive_with(&mut state, &#options).await },
+        None => quote! { ai_lib::drive(&mut state).await },
+    };
+
+    quote! {
+        async move {
+            struct DriveState {
+                #(#field_names: #field_types),*
+            }
+
+            impl ai_lib::AiInitialState for DriveState {
+                fn initial(&mut self) -> ai_lib::AiFunctionResponse {
+                    #initial
+                }
+            }
+
+            #[::ai_macros::ai_functions]
+            impl DriveState {
+                #(#methods)*
+            }
+
+            let mut state = DriveState { #(#field_names: #field_values),* };
+            #run.map(|()| state)
+        }
+    }.into()
+}
+
+/// Collect the functions offered by `prompt!` invocations anywhere in `tokens`, in order, once each.
+fn prompted_functions(tokens: proc_macro2::TokenStream, fn_name: &str, found: &mut Vec<String>) {
+    use proc_macro2::{Delimiter, TokenTree};
+
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Ident(ident) if ident == "prompt" => {
+                let (Some(TokenTree::Punct(bang)), Some(TokenTree::Group(args))) = (tokens.get(i + 1), tokens.get(i + 2)) else {
+                    continue;
+                };
+                if bang.as_char() != '!' {
+                    continue;
+                }
+                // The function list is the bracketed group following `=>`
+                let args: Vec<TokenTree> = args.stream().into_iter().collect();
+                let arrow = args.windows(2).position(|pair| match pair {
+                    [TokenTree::Punct(eq), TokenTree::Punct(gt)] => eq.as_char() == '=' && gt.as_char() == '>',
+                    _ => false,
+                });
+                if let Some(TokenTree::Group(list)) = arrow.and_then(|arrow| args.get(arrow + 2)) {
+                    if list.delimiter() == Delimiter::Bracket {
+                        let mut listed = vec![];
+                        for function in list.stream() {
+                            if let TokenTree::Ident(function) = function {
+                                let function = function.to_string();
+                                if listed.contains(&function) {
+                                    panic!("A prompt! in {fn_name} lists {function} more than once");
+                                }
+                                listed.push(function.clone());
+                                if !found.contains(&function) {
+                                    found.push(function);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            TokenTree::Group(group) => prompted_functions(group.stream(), fn_name, found),
+            _ => {}
+        }
+    }
+}
+
+/// `ty` as written, e.g. `Vec<Option<String>>` rather than the token stream's `Vec < Option < String > >`.
+fn type_string(ty: &Type) -> String {
+    let mut string = quote! { #ty }.to_string();
+    for (spaced, tight) in [(" <", "<"), ("< ", "<"), (" >", ">"), (" ,", ","), (" :: ", "::"), ("& ", "&")] {
+        string = string.replace(spaced, tight);
+    }
+    string
+}
+
+/// Whether `tokens` (a type) names `Self` or the type called `name`.
+fn mentions_type(tokens: proc_macro2::TokenStream, name: Option<&str>) -> bool {
+    use proc_macro2::TokenTree;
+
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "Self" || name.is_some_and(|name| ident == name),
+        TokenTree::Group(group) => mentions_type(group.stream(), name),
+        _ => false,
+    })
+}
+
+/// How many arrays deep `ty`'s schema is, as far as can be seen from the type itself: each
+/// collection, array, slice, or tuple adds a level, and other types count as none.
+fn collection_depth(ty: &Type) -> usize {
+    const COLLECTIONS: &[&str] = &["Vec", "VecDeque", "LinkedList", "HashSet", "BTreeSet", "HashMap", "BTreeMap", "IndexMap", "IndexSet"];
+    const WRAPPERS: &[&str] = &["Option", "Box", "Rc", "Arc", "Cow"];
+
+    match ty {
+        Type::Path(path) => {
+            let Some(segment) = path.path.segments.last() else {
+                return 0;
+            };
+            let ident = segment.ident.to_string();
+            let level = match ident.as_str() {
+                ident if COLLECTIONS.contains(&ident) => 1,
+                ident if WRAPPERS.contains(&ident) => 0,
+                _ => return 0,
+            };
+            let inner = match &segment.arguments {
+                syn::PathArguments::AngleBracketed(args) => args
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        syn::GenericArgument::Type(ty) => Some(collection_depth(ty)),
+                        _ => None,
+                    })
+                    .max()
+                    .unwrap_or(0),
+                _ => 0,
+            };
+            level + inner
+        }
+        Type::Array(array) => 1 + collection_depth(&array.elem),
+        Type::Slice(slice) => 1 + collection_depth(&slice.elem),
+        Type::Tuple(tuple) if !tuple.elems.is_empty() => 1 + tuple.elems.iter().map(collection_depth).max().unwrap_or(0),
+        Type::Reference(reference) => collection_depth(&reference.elem),
+        Type::Paren(paren) => collection_depth(&paren.elem),
+        Type::Group(group) => collection_depth(&group.elem),
+        _ => 0,
+    }
+}
+
+/// A case accepted by `#[ai_function(alias_cases(...))]`; `lower` and `title` are space-separated,
+/// e.g. `random words` and `Random Words`.
+fn parse_case(name: &str) -> Case {
+    match name {
+        "snake" => Case::Snake,
+        "camel" => Case::Camel,
+        "pascal" => Case::Pascal,
+        "kebab" => Case::Kebab,
+        "train" => Case::Train,
+        "screaming_snake" => Case::ScreamingSnake,
+        "lower" => Case::Lower,
+        "title" => Case::Title,
+        "flat" => Case::Flat,
+        _ => panic!("Unknown alias case {name}; expected one of snake, camel, pascal, kebab, train, screaming_snake, lower, title, flat"),
+    }
+}
+
+fn doc_string(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<_> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(syn::MetaNameValue { lit: syn::Lit::Str(lit_str), .. })) => Some(lit_str.value().trim().to_string()),
+            _ => None,
+        })
+        .collect();
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
+
+struct DriveField {
+    name: Ident,
+    ty: Type,
+    value: Expr,
+}
+
+impl Parse for DriveField {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        Ok(Self { name, ty, value })
+    }
+}
+
+struct DriveFunction {
+    attrs: Vec<Attribute>,
+    name: Ident,
+    closure: ExprClosure,
+}
+
+struct DriveInput {
+    fields: Vec<DriveField>,
+    initial: Expr,
+    options: Option<Expr>,
+    functions: Vec<DriveFunction>,
+}
+
+impl Parse for DriveInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut fields = vec![];
+        let mut initial = None;
+        let mut options = None;
+        let mut functions = vec![];
+
+        while !input.is_empty() {
+            let attrs = input.call(Attribute::parse_outer)?;
+            let name: Ident = input.parse()?;
+            if name == "state" && input.peek(syn::token::Brace) {
+                let content;
+                syn::braced!(content in input);
+                fields.extend(Punctuated::<DriveField, Token![,]>::parse_terminated(&content)?);
+            } else {
+                input.parse::<Token![=]>()?;
+                if name == "initial" {
+                    initial = Some(input.parse()?);
+                } else if name == "options" {
+                    options = Some(input.parse()?);
+                } else {
+                    functions.push(DriveFunction { attrs, name, closure: input.parse()? });
+                }
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        let initial = initial.ok_or_else(|| input.error("drive! needs an `initial = prompt!(...)` entry"))?;
+        Ok(Self { fields, initial, options, functions })
+    }
+}